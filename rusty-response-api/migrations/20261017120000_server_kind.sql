@@ -0,0 +1,2 @@
+--- Check kind of the monitored server, e.g http, tcp. Not made with CHECK to not alter the table on kind add
+ALTER TABLE server ADD COLUMN kind TEXT NOT NULL DEFAULT 'http';
@@ -0,0 +1,2 @@
+--- Kind specific check options in JSON, e.g record type and expected answers for dns checks
+ALTER TABLE server ADD COLUMN check_options TEXT NOT NULL DEFAULT '{}';
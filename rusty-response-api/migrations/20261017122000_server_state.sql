@@ -0,0 +1,9 @@
+--- State the log line represents, e.g online, warning, unreachable. Not made with CHECK to not alter the table on state add
+ALTER TABLE server_log ADD COLUMN state TEXT NOT NULL DEFAULT 'online';
+UPDATE server_log SET state = 'unreachable' WHERE failed = 1;
+
+--- Peer certificate details, only filled by tls checks
+ALTER TABLE server_log ADD COLUMN cert_expires_at TIMESTAMP;
+ALTER TABLE server_log ADD COLUMN cert_issuer TEXT;
+
+ALTER TABLE server ADD COLUMN last_seen_state TEXT;
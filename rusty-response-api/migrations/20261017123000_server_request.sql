@@ -0,0 +1,4 @@
+--- Request performed by `http` checks
+ALTER TABLE server ADD COLUMN method TEXT NOT NULL DEFAULT 'GET';
+ALTER TABLE server ADD COLUMN headers TEXT NOT NULL DEFAULT '[]'; --- JSON list of headers, values of secret ones are encrypted
+ALTER TABLE server ADD COLUMN request_body TEXT;
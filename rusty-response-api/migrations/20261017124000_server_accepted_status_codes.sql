@@ -0,0 +1,2 @@
+--- Status codes considered healthy, lists and ranges like `200-299,401`
+ALTER TABLE server ADD COLUMN accepted_status_codes TEXT NOT NULL DEFAULT '200-299';
@@ -0,0 +1,6 @@
+--- Elapsed time of the probe, empty when the probe itself failed
+ALTER TABLE server_log ADD COLUMN latency_ms INTEGER;
+
+ALTER TABLE server ADD COLUMN last_latency_ms INTEGER;
+--- Online servers answering slower than this are degraded, empty to disable
+ALTER TABLE server ADD COLUMN degraded_threshold_ms INTEGER;
@@ -0,0 +1,5 @@
+--- Consecutive probes needed to confirm an outage or a recovery
+ALTER TABLE server ADD COLUMN failure_threshold INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE server ADD COLUMN recovery_threshold INTEGER NOT NULL DEFAULT 1;
+--- Interval used while a state change awaits confirmation, empty to keep the regular interval
+ALTER TABLE server ADD COLUMN recheck_interval INTEGER; --- seconds
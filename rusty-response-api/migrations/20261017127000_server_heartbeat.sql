@@ -0,0 +1,3 @@
+--- Secret part of `/api/v1/heartbeat/{token}`, only set for heartbeat servers
+ALTER TABLE server ADD COLUMN heartbeat_token TEXT;
+CREATE UNIQUE INDEX server_heartbeat_token ON server (heartbeat_token);
@@ -0,0 +1,2 @@
+--- Paused servers (is_turned_on = 0) are resumed automatically once this moment passes
+ALTER TABLE server ADD COLUMN paused_until TIMESTAMP;
@@ -0,0 +1,24 @@
+--- Maintenance windows, notifications of their servers are suppressed while a window is active
+CREATE TABLE maintenance (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    starts_at TIMESTAMP NOT NULL, --- start of a one-off window, recurring windows don't occur before it
+    ends_at TIMESTAMP, --- end of a one-off window, recurring windows don't occur after it
+    schedule TEXT, --- cron expression for recurring windows, NULL for one-off ones
+    duration INTEGER, --- minutes, length of every recurring window
+    timezone TEXT NOT NULL DEFAULT 'UTC', --- IANA name the schedule is evaluated in
+    notify INTEGER NOT NULL CHECK (notify IN (1, 0)) DEFAULT 0, --- 1 to notify when a window starts and ends
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY ("user_id") REFERENCES user ("id") ON DELETE CASCADE
+);
+
+--- Servers covered by a maintenance window
+CREATE TABLE maintenance_server (
+    maintenance_id INTEGER NOT NULL,
+    server_id INTEGER NOT NULL,
+    PRIMARY KEY (maintenance_id, server_id),
+    FOREIGN KEY ("maintenance_id") REFERENCES maintenance ("id") ON DELETE CASCADE,
+    FOREIGN KEY ("server_id") REFERENCES server ("id") ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+--- JSON array of parent server ids, failures of the server aren't notified while a parent is unreachable
+ALTER TABLE server ADD COLUMN parent_ids TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,4 @@
+--- Flapping detection, NULL threshold turns it off
+ALTER TABLE server ADD COLUMN flap_threshold INTEGER; --- state changes within the window to consider the server flapping
+ALTER TABLE server ADD COLUMN flap_window INTEGER NOT NULL DEFAULT 600; --- seconds
+ALTER TABLE server ADD COLUMN flapping INTEGER NOT NULL CHECK (flapping IN (1, 0)) DEFAULT 0;
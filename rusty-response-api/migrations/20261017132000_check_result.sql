@@ -0,0 +1,17 @@
+--- Which checks are recorded besides state transitions: 'transitions', 'every' or 'sampled'
+ALTER TABLE server ADD COLUMN record_mode TEXT NOT NULL DEFAULT 'transitions';
+ALTER TABLE server ADD COLUMN record_sample INTEGER NOT NULL DEFAULT 10; --- 'sampled' records one check out of this many
+
+--- Result of a single check, kept lightweight since there may be one per probe
+CREATE TABLE check_result (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    server_id INTEGER NOT NULL,
+    state TEXT NOT NULL,
+    status_code INTEGER, --- NULL when the probe failed before getting an answer
+    latency_ms INTEGER,
+    transition INTEGER NOT NULL CHECK (transition IN (1, 0)) DEFAULT 0, --- 1 if the check changed the state
+    checked_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY ("server_id") REFERENCES server ("id") ON DELETE CASCADE
+);
+
+CREATE INDEX idx_check_result_server_checked_at ON check_result (server_id, checked_at);
@@ -0,0 +1,17 @@
+--- Probe outcomes aggregated per server into hourly and daily buckets
+CREATE TABLE rollup (
+    server_id INTEGER NOT NULL,
+    period TEXT NOT NULL, --- 'hour' or 'day'
+    bucket_start TIMESTAMP NOT NULL, --- UTC start of the bucket
+    count INTEGER NOT NULL DEFAULT 0,
+    failures INTEGER NOT NULL DEFAULT 0, --- probes that found the server unreachable
+    latency_min INTEGER, --- milliseconds, NULL when no probe got an answer
+    latency_avg REAL,
+    latency_max INTEGER,
+    latency_p50 INTEGER,
+    latency_p95 INTEGER,
+    latency_p99 INTEGER,
+    histogram TEXT NOT NULL DEFAULT '{}', --- latency histogram the percentiles are estimated from
+    PRIMARY KEY (server_id, period, bucket_start),
+    FOREIGN KEY ("server_id") REFERENCES server ("id") ON DELETE CASCADE
+);
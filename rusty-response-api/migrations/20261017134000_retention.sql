@@ -0,0 +1,6 @@
+--- Days the server's logs and check results are kept, NULL for the global retention, 0 keeps them forever
+ALTER TABLE server ADD COLUMN log_retention_days INTEGER;
+
+--- Pruning looks rows up by their age
+CREATE INDEX idx_server_log_server_created_at ON server_log (server_id, created_at);
+CREATE INDEX idx_user_action_log_created_at ON user_action_log (created_at);
@@ -0,0 +1,32 @@
+--- Outage of a server, opened when it's found unreachable and resolved when it's found back
+CREATE TABLE incident (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    server_id INTEGER NOT NULL,
+    started_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    resolved_at TIMESTAMP, --- NULL while the incident is open
+    root_cause TEXT,
+    acknowledged_by INTEGER,
+    acknowledged_at TIMESTAMP, --- NULL until someone takes care of the incident
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY ("server_id") REFERENCES server ("id") ON DELETE CASCADE,
+    FOREIGN KEY ("acknowledged_by") REFERENCES user ("id") ON DELETE SET NULL
+);
+
+--- A server has at most one open incident
+CREATE UNIQUE INDEX idx_incident_open ON incident (server_id) WHERE resolved_at IS NULL;
+
+--- Notes left on an incident, e.g findings about its root cause
+CREATE TABLE incident_note (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    incident_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY ("incident_id") REFERENCES incident ("id") ON DELETE CASCADE,
+    FOREIGN KEY ("user_id") REFERENCES user ("id") ON DELETE CASCADE
+);
+
+--- Log lines written while an incident of their server was open
+ALTER TABLE server_log ADD COLUMN incident_id INTEGER REFERENCES incident ("id") ON DELETE SET NULL;
+CREATE INDEX idx_server_log_incident ON server_log (incident_id);
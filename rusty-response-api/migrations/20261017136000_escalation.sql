@@ -0,0 +1,16 @@
+--- Escalation policies, notifying more notifiers the longer an outage stays unacknowledged
+CREATE TABLE escalation_policy (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    steps TEXT NOT NULL DEFAULT '[]', --- JSON array of steps, each with `after` minutes and `notifier_ids`
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY ("user_id") REFERENCES user ("id") ON DELETE CASCADE
+);
+
+--- Policy escalating outages of the server, NULL to only notify its own notifiers
+ALTER TABLE server ADD COLUMN escalation_policy_id INTEGER REFERENCES escalation_policy ("id") ON DELETE SET NULL;
+
+--- Steps of the policy already notified for the incident, kept here so escalation survives restarts
+ALTER TABLE incident ADD COLUMN escalation_step INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,14 @@
+--- Minutes between reminders while the server stays down, NULL to only notify the transition
+ALTER TABLE notifier ADD COLUMN remind_every INTEGER;
+--- Reminders sent per incident at most
+ALTER TABLE notifier ADD COLUMN remind_limit INTEGER NOT NULL DEFAULT 12;
+
+--- Reminders sent by each notifier for an incident, kept here so reminders survive restarts
+CREATE TABLE incident_reminder (
+    incident_id INTEGER NOT NULL,
+    notifier_id INTEGER NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (incident_id, notifier_id),
+    FOREIGN KEY ("incident_id") REFERENCES incident ("id") ON DELETE CASCADE,
+    FOREIGN KEY ("notifier_id") REFERENCES notifier ("id") ON DELETE CASCADE
+);
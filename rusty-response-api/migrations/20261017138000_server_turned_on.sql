@@ -0,0 +1,10 @@
+--- Servers used to be monitored whatever is_turned_on said, and it defaulted to 0.
+--- Turns on every server that wasn't paused through the API, so upgrading doesn't stop monitoring them
+UPDATE server SET is_turned_on = 1
+WHERE is_turned_on = 0 AND NOT EXISTS (
+    SELECT 1 FROM user_action_log AS l
+    WHERE l.action = 'server_pause' AND l.id = (
+        SELECT MAX(id) FROM user_action_log
+        WHERE action_entity = server.id AND "action" IN ('server_pause', 'server_resume')
+    )
+);
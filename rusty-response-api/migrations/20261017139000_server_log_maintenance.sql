@@ -0,0 +1,2 @@
+--- Line was logged while the server was under maintenance, uptime figures leave it out
+ALTER TABLE server_log ADD COLUMN in_maintenance BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,321 @@
+//! Bounded channel with coalescing overflow.
+//!
+//! While the queue has room messages are delivered in order. Once it's full a message replaces
+//! the queued one with the same key if it supersedes it, disposable messages are dropped and the
+//! rest waits for the receiver to catch up.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use serde::Serialize;
+use tokio::sync::Notify;
+use utoipa::ToSchema;
+
+/// Messages the bounded channel knows how to coalesce
+pub trait Coalesce {
+    /// Messages with the same key can replace each other
+    fn key(&self) -> i64;
+
+    /// Whether `self` may replace the `queued` message with the same key
+    fn supersedes(&self, queued: &Self) -> bool;
+
+    /// Whether `self` may be dropped when it can't be queued
+    fn is_disposable(&self) -> bool;
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    sent: AtomicU64,
+    received: AtomicU64,
+    coalesced: AtomicU64,
+    dropped: AtomicU64,
+    blocked: AtomicU64,
+    max_depth: AtomicUsize,
+}
+
+struct Shared<T> {
+    capacity: usize,
+    queue: Mutex<VecDeque<T>>,
+    /// Signalled when a message is queued or the last sender is gone
+    items: Notify,
+    /// Signalled when a message is taken out of a full queue
+    space: Notify,
+    senders: AtomicUsize,
+    receiver_alive: AtomicBool,
+    counters: Counters,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, VecDeque<T>> {
+        self.queue.lock().expect("channel lock poisoned")
+    }
+}
+
+/// Point in time view of the channel counters
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ChannelStatsSnapshot {
+    pub capacity: usize,
+    /// Messages currently queued
+    pub depth: usize,
+    /// Highest depth seen since startup
+    pub max_depth: usize,
+    pub sent: u64,
+    pub received: u64,
+    /// Messages that replaced a queued message with the same key
+    pub coalesced: u64,
+    /// Messages dropped because the queue was full
+    pub dropped: u64,
+    /// Times a sender had to wait for room
+    pub blocked: u64,
+}
+
+trait StatsSource: Send + Sync {
+    fn snapshot(&self) -> ChannelStatsSnapshot;
+}
+
+impl<T: Send> StatsSource for Shared<T> {
+    fn snapshot(&self) -> ChannelStatsSnapshot {
+        let counters = &self.counters;
+        ChannelStatsSnapshot {
+            capacity: self.capacity,
+            depth: self.lock().len(),
+            max_depth: counters.max_depth.load(Ordering::Relaxed),
+            sent: counters.sent.load(Ordering::Relaxed),
+            received: counters.received.load(Ordering::Relaxed),
+            coalesced: counters.coalesced.load(Ordering::Relaxed),
+            dropped: counters.dropped.load(Ordering::Relaxed),
+            blocked: counters.blocked.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Read-only handle to the counters of a bounded channel
+#[derive(Clone)]
+pub struct ChannelStats(Arc<dyn StatsSource>);
+
+impl ChannelStats {
+    pub fn snapshot(&self) -> ChannelStatsSnapshot {
+        self.0.snapshot()
+    }
+}
+
+/// Receiver is gone, the message couldn't be delivered
+#[derive(Debug)]
+pub struct SendError<T>(pub T);
+
+pub struct BoundedSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Coalesce> BoundedSender<T> {
+    /// Queues the message, coalescing or dropping it when the queue is full.
+    /// Waits for room only when neither is possible.
+    pub async fn send(&self, message: T) -> Result<(), SendError<T>> {
+        let shared = &self.shared;
+        let counters = &shared.counters;
+        counters.sent.fetch_add(1, Ordering::Relaxed);
+
+        loop {
+            let space = shared.space.notified();
+            {
+                let mut queue = shared.lock();
+                if !shared.receiver_alive.load(Ordering::Acquire) {
+                    return Err(SendError(message));
+                }
+
+                if queue.len() < shared.capacity {
+                    queue.push_back(message);
+                    counters.max_depth.fetch_max(queue.len(), Ordering::Relaxed);
+                    shared.items.notify_one();
+                    return Ok(());
+                }
+
+                let key = message.key();
+                match queue.iter_mut().rev().find(|queued| queued.key() == key) {
+                    Some(queued) if message.supersedes(queued) => {
+                        *queued = message;
+                        counters.coalesced.fetch_add(1, Ordering::Relaxed);
+                        return Ok(());
+                    }
+                    _ if message.is_disposable() => {
+                        counters.dropped.fetch_add(1, Ordering::Relaxed);
+                        return Ok(());
+                    }
+                    _ => counters.blocked.fetch_add(1, Ordering::Relaxed),
+                };
+            }
+            space.await;
+        }
+    }
+}
+
+impl<T> Clone for BoundedSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::AcqRel);
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for BoundedSender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.items.notify_one();
+        }
+    }
+}
+
+pub struct BoundedReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> BoundedReceiver<T> {
+    /// Receives the next message, `None` once every sender is gone and the queue is drained
+    pub async fn recv(&mut self) -> Option<T> {
+        let shared = &self.shared;
+
+        loop {
+            let items = shared.items.notified();
+            {
+                let mut queue = shared.lock();
+                if let Some(message) = queue.pop_front() {
+                    shared.counters.received.fetch_add(1, Ordering::Relaxed);
+                    shared.space.notify_one();
+                    return Some(message);
+                }
+                if shared.senders.load(Ordering::Acquire) == 0 {
+                    return None;
+                }
+            }
+            items.await;
+        }
+    }
+}
+
+impl<T> Drop for BoundedReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.receiver_alive.store(false, Ordering::Release);
+        self.shared.space.notify_waiters();
+    }
+}
+
+/// Bounded counterpart of [`super::UnboundedMPSCController`], see the module docs for the overflow behaviour
+pub struct BoundedMPSCController<T> {
+    receiver: BoundedReceiver<T>,
+    owned_sender: BoundedSender<T>,
+}
+
+impl<T: Send + 'static> BoundedMPSCController<T> {
+    pub fn new(capacity: usize) -> Self {
+        let shared = Arc::new(Shared {
+            capacity: capacity.max(1),
+            queue: Mutex::new(VecDeque::with_capacity(capacity.max(1))),
+            items: Notify::new(),
+            space: Notify::new(),
+            senders: AtomicUsize::new(1),
+            receiver_alive: AtomicBool::new(true),
+            counters: Counters::default(),
+        });
+
+        Self {
+            owned_sender: BoundedSender {
+                shared: Arc::clone(&shared),
+            },
+            receiver: BoundedReceiver { shared },
+        }
+    }
+
+    pub fn get_sender(&self) -> BoundedSender<T> {
+        self.owned_sender.clone()
+    }
+
+    pub fn stats(&self) -> ChannelStats {
+        ChannelStats(Arc::clone(&self.receiver.shared) as Arc<dyn StatsSource>)
+    }
+
+    pub fn take_receiver(self) -> BoundedReceiver<T> {
+        self.receiver
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Message {
+        State(i64, &'static str),
+        Latency(i64),
+    }
+
+    impl Coalesce for Message {
+        fn key(&self) -> i64 {
+            match self {
+                Message::State(key, _) | Message::Latency(key) => *key,
+            }
+        }
+
+        fn supersedes(&self, queued: &Self) -> bool {
+            matches!(self, Message::State(..)) || matches!(queued, Message::Latency(_))
+        }
+
+        fn is_disposable(&self) -> bool {
+            matches!(self, Message::Latency(_))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bounded_coalesces_on_overflow() {
+        let controller = BoundedMPSCController::new(2);
+        let stats = controller.stats();
+        let tx = controller.get_sender();
+        let mut rx = controller.take_receiver();
+
+        tx.send(Message::State(1, "down")).await.unwrap();
+        tx.send(Message::Latency(2)).await.unwrap();
+        // full from here on
+        tx.send(Message::State(1, "up")).await.unwrap();
+        tx.send(Message::Latency(1)).await.unwrap();
+        tx.send(Message::Latency(3)).await.unwrap();
+
+        assert_eq!(rx.recv().await, Some(Message::State(1, "up")));
+        assert_eq!(rx.recv().await, Some(Message::Latency(2)));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.depth, 0);
+        assert_eq!(snapshot.max_depth, 2);
+        assert_eq!(snapshot.sent, 5);
+        assert_eq!(snapshot.received, 2);
+        assert_eq!(snapshot.coalesced, 1);
+        assert_eq!(snapshot.dropped, 2);
+    }
+
+    #[tokio::test]
+    async fn test_bounded_waits_for_room() {
+        let controller = BoundedMPSCController::new(1);
+        let tx = controller.get_sender();
+        let mut rx = controller.take_receiver();
+
+        tx.send(Message::State(1, "down")).await.unwrap();
+        // a state of another server can't be coalesced nor dropped
+        let blocked = tokio::spawn(async move {
+            tx.send(Message::State(2, "down")).await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!blocked.is_finished());
+
+        assert_eq!(rx.recv().await, Some(Message::State(1, "down")));
+        blocked.await.unwrap();
+        assert_eq!(rx.recv().await, Some(Message::State(2, "down")));
+        // every sender is gone
+        assert_eq!(rx.recv().await, None);
+    }
+}
@@ -1,10 +1,18 @@
 use tokio::sync::mpsc;
 
+mod bounded;
 mod error;
 mod server;
 
+pub use bounded::{
+    BoundedMPSCController, BoundedReceiver, BoundedSender, ChannelStats, ChannelStatsSnapshot,
+    Coalesce,
+};
 pub use error::Error;
-pub use server::{ControlMessage, ServerMessage, setup_monitoring_future};
+pub use server::{
+    ControlMessage, HeartbeatPing, HeartbeatSignal, ServerMessage, UnderMaintenance,
+    setup_monitoring_future, validate_server,
+};
 
 /// MPSC channel controller to control sending commands back to our main application from "check" threads
 pub struct UnboundedMPSCController<T> {
@@ -0,0 +1,51 @@
+//! Notification delivery, kept off the result handling path.
+//!
+//! Notifications of a server are delivered in order, a slow notifier only holds back servers
+//! sharing its lane. Queueing never waits, notifications that don't fit the lane are dropped and
+//! counted, so a stuck notifier can't hold up result handling.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tokio::task::JoinSet;
+use tracing::{error, warn};
+
+use super::lanes::Lanes;
+use crate::{model::ServerLogLine, notify::NotifyManager};
+
+pub struct Delivery {
+    lanes: Lanes<(i64, ServerLogLine)>,
+    /// Notifications dropped because their lane was full
+    dropped: AtomicU64,
+}
+
+impl Delivery {
+    pub fn spawn(notify_manager: NotifyManager, lanes: usize) -> (Self, JoinSet<()>) {
+        let (lanes, tasks) = Lanes::spawn(lanes, move |(server_id, line)| {
+            let notify_manager = notify_manager.clone();
+            async move {
+                let result = notify_manager.notify(server_id, line).await;
+                if let Err(e) = result {
+                    error!("Unable to send notification for {server_id}: {e}");
+                }
+            }
+        });
+
+        (
+            Self {
+                lanes,
+                dropped: AtomicU64::new(0),
+            },
+            tasks,
+        )
+    }
+
+    /// Queues the notification, it's delivered in the background
+    pub fn notify(&self, server_id: i64, line: ServerLogLine) {
+        if !self.lanes.try_send(server_id, (server_id, line)) {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            warn!(
+                "Notification lane of server {server_id} is full, {dropped} notifications dropped so far"
+            );
+        }
+    }
+}
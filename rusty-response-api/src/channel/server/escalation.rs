@@ -0,0 +1,108 @@
+//! Escalation of unacknowledged outages through the steps of their server's policy.
+//!
+//! Progress is stored on the incident and every step is timed from its start, so escalation
+//! picks up where it left off after a restart.
+
+use std::time::Duration;
+
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, trace};
+
+use crate::{
+    ModelManager,
+    channel::server::{maintenance::UnderMaintenance, utils},
+    model::{
+        Ctx, EscalationPolicyBmc, Incident, IncidentBmc, ServerBmc, ServerLogBmc, ServerLogLine,
+    },
+    notify::NotifyManager,
+};
+
+/// How often incidents are checked for due steps
+const TICK: Duration = Duration::from_secs(15);
+
+/// Notifies every step of the incident's escalation that's due
+async fn escalate(
+    mm: &ModelManager,
+    ctx: &Ctx,
+    notify_manager: &NotifyManager,
+    under_maintenance: &UnderMaintenance,
+    incident: Incident,
+) -> eyre::Result<()> {
+    let Some(server) = ServerBmc::get_by_id(mm, ctx, incident.server_id).await? else {
+        return Ok(());
+    };
+    let Some(policy_id) = server.escalation_policy_id else {
+        return Ok(());
+    };
+    let Some(policy) = EscalationPolicyBmc::get(mm, ctx, policy_id).await? else {
+        return Ok(());
+    };
+
+    let elapsed = OffsetDateTime::now_utc() - incident.started_at.assume_utc();
+    let due = policy.due(incident.escalation_step, elapsed);
+    if due.is_empty() {
+        return Ok(());
+    }
+
+    // like any other notification, escalations of expected or inherited outages are suppressed
+    let silenced = utils::is_silenced(&server, &*under_maintenance.read().await);
+
+    let first = ServerLogBmc::first_of_incident(mm, ctx, incident.id).await?;
+    if let (false, Some(log)) = (silenced, first) {
+        let line = ServerLogLine::new(server.redacted(), log).with_incident(Some(incident.clone()));
+        for step in due.clone() {
+            debug!(
+                "Escalating incident {} of server {} to step {}",
+                incident.id,
+                incident.server_id,
+                step + 1
+            );
+            let line = line.clone().with_escalation(step + 1);
+            for notifier_id in &policy.steps[step].notifier_ids {
+                if let Err(e) = notify_manager.notify_by_nid(*notifier_id, &line).await {
+                    error!(
+                        "Unable to escalate incident {} to notifier {notifier_id}: {e}",
+                        incident.id
+                    );
+                }
+            }
+        }
+    }
+
+    IncidentBmc::escalated(mm, ctx, incident.id, due.end as i64).await?;
+    Ok(())
+}
+
+pub async fn payload(
+    mm: ModelManager,
+    notify_manager: NotifyManager,
+    under_maintenance: UnderMaintenance,
+    cancellation_token: CancellationToken,
+) {
+    let ctx = Ctx::admin_root();
+
+    loop {
+        match IncidentBmc::escalating(&mm, &ctx).await {
+            Ok(incidents) => {
+                for incident in incidents {
+                    let id = incident.id;
+                    let result =
+                        escalate(&mm, &ctx, &notify_manager, &under_maintenance, incident).await;
+                    if let Err(e) = result {
+                        error!("Unable to escalate incident {id}: {e}");
+                    }
+                }
+            }
+            Err(e) => error!("Unable to fetch escalating incidents: {e}"),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(TICK) => {},
+            _ = cancellation_token.cancelled() => {
+                trace!("Escalation payload shut down successfully");
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Detection of servers bouncing between states.
+//!
+//! Confirmed transitions are kept in a sliding window of `flap_window` seconds per server.
+//! Once the window holds `flap_threshold` transitions the server is flapping, a single
+//! notification is sent and further transitions are only logged. The server stabilises
+//! once a whole window passes without a transition.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::model::Server;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flap {
+    /// Transition is notified as usual
+    Stable,
+    /// Server started flapping with this many transitions in the window
+    Started(usize),
+    /// Server is already flapping, the transition isn't notified
+    Ongoing,
+}
+
+#[derive(Debug)]
+struct Transitions {
+    window: Duration,
+    at: VecDeque<Instant>,
+    flapping: bool,
+}
+
+impl Transitions {
+    fn prune(&mut self, now: Instant) {
+        while self
+            .at
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= self.window)
+        {
+            self.at.pop_front();
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FlapDetector {
+    servers: HashMap<i64, Transitions>,
+}
+
+impl FlapDetector {
+    /// Records a confirmed transition of `server`
+    pub fn record(&mut self, server: &Server, now: Instant) -> Flap {
+        let Some(threshold) = server.flap_threshold else {
+            // detection was turned off, a flapping server still has to stabilise
+            return match self.servers.get(&server.id) {
+                Some(transitions) if transitions.flapping => Flap::Ongoing,
+                _ => Flap::Stable,
+            };
+        };
+
+        let transitions = self
+            .servers
+            .entry(server.id)
+            .or_insert_with(|| Transitions {
+                window: Duration::ZERO,
+                at: VecDeque::new(),
+                flapping: false,
+            });
+        transitions.window = Duration::from_secs(server.flap_window as u64);
+        transitions.at.push_back(now);
+        transitions.prune(now);
+
+        if transitions.flapping {
+            return Flap::Ongoing;
+        }
+        if transitions.at.len() >= threshold as usize {
+            transitions.flapping = true;
+            return Flap::Started(transitions.at.len());
+        }
+
+        Flap::Stable
+    }
+
+    /// Restores a server recorded as flapping, it has to stay stable for a whole window
+    pub fn resume(&mut self, server: &Server, now: Instant) {
+        self.servers.insert(
+            server.id,
+            Transitions {
+                window: Duration::from_secs(server.flap_window as u64),
+                at: VecDeque::from([now]),
+                flapping: true,
+            },
+        );
+    }
+
+    /// Ids of servers that stopped flapping, they're forgotten afterwards
+    pub fn stabilised(&mut self, now: Instant) -> Vec<i64> {
+        let mut stable = vec![];
+
+        self.servers.retain(|server_id, transitions| {
+            transitions.prune(now);
+            if transitions.at.is_empty() {
+                if transitions.flapping {
+                    stable.push(*server_id);
+                }
+                return false;
+            }
+            true
+        });
+
+        stable
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_flapping_lifecycle() {
+        let mut server = Server::mock(1, "http://localhost/");
+        server.flap_threshold = Some(3);
+        server.flap_window = 60;
+
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut detector = FlapDetector::default();
+
+        assert_eq!(detector.record(&server, at(0)), Flap::Stable);
+        assert_eq!(detector.record(&server, at(10)), Flap::Stable);
+        assert_eq!(detector.record(&server, at(20)), Flap::Started(3));
+        assert_eq!(detector.record(&server, at(30)), Flap::Ongoing);
+
+        // last transition is still within the window
+        assert!(detector.stabilised(at(80)).is_empty());
+        assert_eq!(detector.stabilised(at(90)), vec![1]);
+
+        // transitions before stabilising don't count anymore
+        assert_eq!(detector.record(&server, at(100)), Flap::Stable);
+    }
+
+    #[test]
+    fn test_flapping_slow_transitions() {
+        let mut server = Server::mock(1, "http://localhost/");
+        server.flap_threshold = Some(3);
+        server.flap_window = 60;
+
+        let start = Instant::now();
+        let mut detector = FlapDetector::default();
+
+        for minute in 0..5 {
+            let flap = detector.record(&server, start + Duration::from_secs(minute * 45));
+            assert_eq!(flap, Flap::Stable);
+        }
+
+        server.flap_threshold = None;
+        assert_eq!(detector.record(&server, start), Flap::Stable);
+    }
+}
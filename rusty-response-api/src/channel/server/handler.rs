@@ -1,96 +1,324 @@
 use axum::http;
-use tracing::{error, trace};
+use tracing::{debug, error, trace};
 
-use super::utils;
-use std::{collections::BTreeMap, sync::Arc};
+use super::{
+    CertificateInfo, ServerStatus,
+    delivery::Delivery,
+    flapping::{Flap, FlapDetector},
+    maintenance::UnderMaintenance,
+    utils::{self, LastSeen},
+};
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::sync::Mutex;
 
 use crate::{
     ModelManager,
     channel::ServerMessage,
-    model::{Ctx, Server, ServerBmc, ServerLogBmc, ServerLogCreate, ServerLogLine},
-    notify::NotifyManager,
+    model::{
+        CheckResultBmc, CheckResultCreate, Ctx, IncidentBmc, PreviousState, Server, ServerBmc,
+        ServerLogBmc, ServerLogCreate, ServerLogLine, ServerState,
+    },
 };
 
+/// State shared by handling of every server's results.
+/// Locks are only held for in-memory bookkeeping, never across database or notifier calls.
+pub struct HandlerState {
+    pub statuses: Arc<Mutex<BTreeMap<i64, LastSeen>>>,
+    pub under_maintenance: UnderMaintenance,
+    pub flaps: Mutex<FlapDetector>,
+    /// Own failures of servers recorded as down because of a parent, re-recorded once it recovers
+    pub dependency_failures: Mutex<BTreeMap<i64, DependencyFailure>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DependencyFailure {
+    status_code: http::StatusCode,
+    reason: Option<String>,
+}
+
+/// Single probe outcome to be recorded
+struct Observation {
+    state: ServerState,
+    status_code: http::StatusCode,
+    reason: Option<String>,
+    body: Vec<u8>,
+    latency: Option<Duration>,
+    certificate: Option<CertificateInfo>,
+}
+
+/// Handles a probe result, results of a single server have to be handled one at a time
 pub async fn handle_server_response(
     msg: ServerMessage,
     mm: &ModelManager,
     ctx: &Ctx,
-    notify_manager: &NotifyManager,
-    statuses: Arc<Mutex<BTreeMap<i64, (i64, String)>>>,
+    delivery: &Delivery,
+    state: &HandlerState,
 ) {
-    let mut statuses = statuses.lock().await;
-
-    match msg {
-        ServerMessage::ServerStateChanged { status, server } => match status {
-            super::ServerStatus::Unreachable {
-                reason,
-                status_code,
-                body,
-            } => {
-                handle_arm(
-                    server,
+    let (server, observation) = match msg {
+        ServerMessage::ServerStateChanged {
+            status,
+            server,
+            certificate,
+        } => {
+            let state = status.state();
+            let observation = match status {
+                ServerStatus::Unreachable {
+                    reason,
                     status_code,
-                    Some(reason),
                     body,
-                    &mut statuses,
-                    mm,
-                    notify_manager,
-                    ctx,
-                )
-                .await;
-            }
-            super::ServerStatus::Online { status_code, body } => {
-                handle_arm(
-                    server,
+                    latency,
+                }
+                | ServerStatus::Warning {
+                    reason,
+                    status_code,
+                    body,
+                    latency,
+                }
+                | ServerStatus::Degraded {
+                    reason,
+                    status_code,
+                    body,
+                    latency,
+                } => Observation {
+                    state,
+                    status_code,
+                    reason: Some(reason),
+                    body,
+                    latency: Some(latency),
+                    certificate,
+                },
+                ServerStatus::Online {
                     status_code,
-                    None,
                     body,
-                    &mut statuses,
-                    mm,
-                    notify_manager,
-                    ctx,
-                )
-                .await;
+                    latency,
+                } => Observation {
+                    state,
+                    status_code,
+                    reason: None,
+                    body,
+                    latency: Some(latency),
+                    certificate,
+                },
+            };
+            (server, observation)
+        }
+        ServerMessage::ChannelError { server, .. } => (
+            server,
+            Observation {
+                state: ServerState::Unreachable,
+                status_code: http::StatusCode::INTERNAL_SERVER_ERROR,
+                reason: Some("Error occurred during fetching".to_string()),
+                body: vec![],
+                latency: None,
+                certificate: None,
+            },
+        ),
+        ServerMessage::Checked {
+            server_id,
+            state,
+            status_code,
+            latency,
+        } => {
+            // the latest latency is flushed along with the rollups, not written per probe
+            let crc = CheckResultCreate {
+                server_id,
+                state,
+                status_code: status_code.map(|code| code.as_u16() as i64),
+                latency_ms: latency.map(|latency| latency.as_millis() as i64),
+                transition: false,
+            };
+            if let Err(e) = CheckResultBmc::insert(mm, ctx, crc).await {
+                error!("Unable to record check of server {server_id}: {e}");
             }
-        },
-        ServerMessage::ChannelError { server, .. } => {
-            handle_arm(
-                server,
-                http::StatusCode::INTERNAL_SERVER_ERROR,
-                Some("Error occurred during fetching".to_string()),
-                vec![],
-                &mut statuses,
-                mm,
-                notify_manager,
-                ctx,
-            )
-            .await;
+            return;
         }
-    }
+    };
+
+    handle_arm(server, observation, mm, delivery, state, ctx).await;
 }
 
-// TODO: Rewrite this
-#[allow(clippy::too_many_arguments)]
 async fn handle_arm(
     server: Server,
-    status_code: http::StatusCode,
-    reason: Option<String>,
-    body: Vec<u8>,
-    statuses: &mut BTreeMap<i64, (i64, String)>,
+    observation: Observation,
+    mm: &ModelManager,
+    delivery: &Delivery,
+    handler_state: &HandlerState,
+    ctx: &Ctx,
+) {
+    let server_id = server.id;
+    let recovered = observation.state != ServerState::Unreachable
+        && handler_state
+            .statuses
+            .lock()
+            .await
+            .get(&server_id)
+            .is_some_and(|prev| prev.state == ServerState::Unreachable);
+
+    record_arm(server, observation, mm, delivery, handler_state, ctx).await;
+
+    if recovered {
+        recheck_dependents(server_id, mm, delivery, handler_state, ctx).await;
+    }
+}
+
+/// Re-records children that stayed down after their parent recovered, with their own failure.
+/// Their trackers already confirmed them as unreachable, so no new result would do it
+async fn recheck_dependents(
+    parent_id: i64,
+    mm: &ModelManager,
+    delivery: &Delivery,
+    handler_state: &HandlerState,
+    ctx: &Ctx,
+) {
+    let children = match ServerBmc::children(mm, ctx, parent_id).await {
+        Ok(children) => children,
+        Err(e) => {
+            error!("Unable to fetch servers depending on {parent_id}: {e}");
+            return;
+        }
+    };
+    let stranded = utils::stranded_dependents(&*handler_state.statuses.lock().await, &children);
+
+    for child in children.into_iter().filter(|c| stranded.contains(&c.id)) {
+        let failure = handler_state
+            .dependency_failures
+            .lock()
+            .await
+            .remove(&child.id)
+            // failures recorded before a restart are only known to be caused by the parent
+            .unwrap_or_else(|| DependencyFailure {
+                status_code: http::StatusCode::INTERNAL_SERVER_ERROR,
+                reason: Some(format!(
+                    "still unreachable after server {parent_id} recovered"
+                )),
+            });
+        debug!(
+            "Server {} stayed down after server {parent_id} recovered",
+            child.id
+        );
+
+        let observation = Observation {
+            state: ServerState::Unreachable,
+            status_code: failure.status_code,
+            reason: failure.reason,
+            body: vec![],
+            latency: None,
+            certificate: None,
+        };
+        record_arm(child, observation, mm, delivery, handler_state, ctx).await;
+    }
+}
+
+async fn record_arm(
+    mut server: Server,
+    observation: Observation,
     mm: &ModelManager,
-    notify_manager: &NotifyManager,
+    delivery: &Delivery,
+    handler_state: &HandlerState,
     ctx: &Ctx,
 ) {
+    let Observation {
+        state,
+        status_code,
+        reason,
+        body,
+        latency,
+        certificate,
+    } = observation;
     let code = status_code.as_u16() as i64;
 
+    let (prev, down_parent) = {
+        let statuses = handler_state.statuses.lock().await;
+        // failures caused by an unreachable parent are recorded, but not notified
+        let down_parent = match state {
+            ServerState::Unreachable => server.parent_ids.iter().copied().find(|id| {
+                statuses
+                    .get(id)
+                    .is_some_and(|parent| parent.state == ServerState::Unreachable)
+            }),
+            _ => None,
+        };
+        (statuses.get(&server.id).cloned(), down_parent)
+    };
+    let reason = {
+        let mut failures = handler_state.dependency_failures.lock().await;
+        match down_parent {
+            Some(parent) => {
+                failures.insert(
+                    server.id,
+                    DependencyFailure {
+                        status_code,
+                        reason,
+                    },
+                );
+                Some(format!(
+                    "{}: server {parent} is unreachable",
+                    utils::DEPENDENCY_DOWN
+                ))
+            }
+            None => {
+                failures.remove(&server.id);
+                reason
+            }
+        }
+    };
+    // neither is the recovery from such a failure
+    let silent_recovery = state == ServerState::Online
+        && prev.as_ref().is_some_and(|prev| {
+            prev.state == ServerState::Unreachable
+                && prev.reason.starts_with(utils::DEPENDENCY_DOWN)
+        });
+
+    let latency_ms = latency.map(|latency| latency.as_millis() as i64);
+
     let server_id = server.id;
 
-    if utils::is_changed(statuses, server_id, code, reason.as_ref()) {
-        utils::update_cache(statuses, server_id, code, reason.clone());
-        let result =
-            ServerBmc::update_status(mm, ctx, server_id, reason.clone().unwrap_or_default(), code)
-                .await;
+    let flap = match &prev {
+        Some(prev) if prev.state == state => Flap::Stable,
+        _ => handler_state
+            .flaps
+            .lock()
+            .await
+            .record(&server, Instant::now()),
+    };
+    server.flapping = flap != Flap::Stable;
+    if let Flap::Started(_) = flap {
+        let result = ServerBmc::update_flapping(mm, ctx, server_id, true).await;
+        if let Err(e) = result {
+            error!("Unable to update server flapping: {}", e);
+        }
+    }
+
+    if let Some(latency_ms) = latency_ms {
+        server.last_latency_ms = Some(latency_ms);
+        let result = ServerBmc::update_latency(mm, ctx, server_id, latency_ms).await;
+        if let Err(e) = result {
+            error!("Unable to update server latency: {}", e);
+        }
+    }
+
+    let changed = {
+        let mut statuses = handler_state.statuses.lock().await;
+        let changed = utils::is_changed(&statuses, server_id, state, code, reason.as_ref());
+        if changed {
+            utils::update_cache(&mut statuses, server_id, state, code, reason.clone());
+        }
+        changed
+    };
+    if changed {
+        let result = ServerBmc::update_status(
+            mm,
+            ctx,
+            server_id,
+            state,
+            reason.clone().unwrap_or_default(),
+            code,
+        )
+        .await;
         if let Err(e) = result {
             error!("Unable to update server status: {}", e);
         }
@@ -98,33 +326,179 @@ async fn handle_arm(
 
     trace!(
         "Recieved message: {:?}. From: {}",
-        (status_code, &reason),
+        (state, status_code, &reason, latency),
         server_id
     );
     let lossy_str = String::from_utf8_lossy(&body).into_owned();
 
-    let lc = ServerLogCreate::new(
+    // transitions are recorded whatever the record mode is
+    let crc = CheckResultCreate {
         server_id,
-        !status_code.is_success(),
-        status_code.as_u16() as i64,
-        Some(lossy_str),
-        reason,
-    );
+        state,
+        // probes failing before an answer carry no latency
+        status_code: latency_ms.map(|_| code),
+        latency_ms,
+        transition: true,
+    };
+    if let Err(e) = CheckResultBmc::insert(mm, ctx, crc).await {
+        error!("Unable to record check of server {server_id}: {e}");
+    }
 
-    let result = ServerLogBmc::insert(mm, ctx, lc).await;
+    // transitions during planned work are logged, but left out of uptime figures
+    let in_maintenance = handler_state
+        .under_maintenance
+        .read()
+        .await
+        .contains(&server_id);
 
-    if let Err(e) = &result {
-        error!(
-            "Error occured during logging server {} response: {}",
-            server_id, e
-        );
+    let mut lc = ServerLogCreate::new(server_id, state, code, Some(lossy_str), reason)
+        .with_maintenance(in_maintenance);
+    if let Some(latency_ms) = latency_ms {
+        lc = lc.with_latency(latency_ms);
+    }
+    if let Some(cert) = certificate {
+        lc = lc.with_certificate(cert.expires_at, cert.issuer);
     }
 
-    let log_line = result.unwrap();
-    let log_line = ServerLogLine::new(server, log_line);
+    // the incident is opened first, so the line going down is already linked to it. Cascading
+    // failures aren't incidents of their own, once the parent recovers the re-check of the
+    // server opens one if it's still down
+    let mut incident = None;
+    if state == ServerState::Unreachable && down_parent.is_none() {
+        match IncidentBmc::open(mm, ctx, server_id).await {
+            Ok(opened) => incident = Some(opened),
+            Err(e) => error!("Unable to open incident of server {server_id}: {e}"),
+        }
+    }
+
+    let mut log_line = match ServerLogBmc::insert(mm, ctx, lc).await {
+        Ok(log_line) => log_line,
+        Err(e) => {
+            error!(
+                "Error occured during logging server {} response: {}",
+                server_id, e
+            );
+            return;
+        }
+    };
+
+    if state != ServerState::Unreachable {
+        match IncidentBmc::resolve(mm, ctx, server_id).await {
+            Ok(resolved) => incident = resolved,
+            Err(e) => error!("Unable to resolve incident of server {server_id}: {e}"),
+        }
+    }
+
+    // how long the server was in the state it just left, e.g the downtime of a recovery
+    let mut previous = None;
+    if let Some(prev) = prev.as_ref().filter(|prev| prev.state != state) {
+        match ServerLogBmc::entered_at(mm, ctx, server_id, prev.state, log_line.id).await {
+            Ok(Some(since)) => {
+                previous = Some(PreviousState::new(
+                    prev.state,
+                    since,
+                    log_line.created_at.assume_utc(),
+                    Some(prev.reason.clone()),
+                ))
+            }
+            Ok(None) => {}
+            Err(e) => error!("Unable to find previous state of server {server_id}: {e}"),
+        }
+    }
+
+    match flap {
+        Flap::Stable => {}
+        Flap::Started(count) => {
+            // the flapping notice replaces the transition's notification
+            let reason = format!(
+                "flapping: {count} state changes within {}s",
+                server.flap_window
+            );
+            let lc =
+                ServerLogCreate::new(server_id, ServerState::Flapping, code, None, Some(reason))
+                    .with_maintenance(in_maintenance);
+            match ServerLogBmc::insert(mm, ctx, lc).await {
+                Ok(line) => log_line = line,
+                Err(e) => error!("Unable to log flapping of server {server_id}: {e}"),
+            }
+        }
+        Flap::Ongoing => {
+            trace!("Server {server_id} is flapping, notification suppressed");
+            return;
+        }
+    }
+
+    if in_maintenance {
+        trace!("Server {server_id} is under maintenance, notification suppressed");
+        return;
+    }
+
+    if down_parent.is_some() || silent_recovery {
+        trace!("Server {server_id} depends on an unreachable server, notification suppressed");
+        return;
+    }
+
+    let log_line = ServerLogLine::new(server.redacted(), log_line)
+        .with_incident(incident)
+        .with_previous(previous);
+    delivery.notify(server_id, log_line);
+}
+
+/// Logs and notifies servers that stopped flapping
+pub async fn handle_stabilised(
+    mm: &ModelManager,
+    ctx: &Ctx,
+    delivery: &Delivery,
+    state: &HandlerState,
+) {
+    let stable = state.flaps.lock().await.stabilised(Instant::now());
+
+    for server_id in stable {
+        let result = ServerBmc::update_flapping(mm, ctx, server_id, false).await;
+        if let Err(e) = result {
+            error!("Unable to update server flapping: {}", e);
+        }
+
+        let server = match ServerBmc::get_by_id(mm, ctx, server_id).await {
+            Ok(Some(server)) => server,
+            Ok(None) => continue,
+            Err(e) => {
+                error!("Unable to fetch stabilised server {server_id}: {e}");
+                continue;
+            }
+        };
+
+        let last_seen = state
+            .statuses
+            .lock()
+            .await
+            .get(&server_id)
+            .cloned()
+            .unwrap_or_else(|| LastSeen::from_server(&server));
+        let reason = "stopped flapping".to_string();
+        let in_maintenance = state.under_maintenance.read().await.contains(&server_id);
+        let lc = ServerLogCreate::new(
+            server_id,
+            last_seen.state,
+            last_seen.code,
+            None,
+            Some(reason),
+        )
+        .with_maintenance(in_maintenance);
+
+        let log_line = match ServerLogBmc::insert(mm, ctx, lc).await {
+            Ok(log_line) => log_line,
+            Err(e) => {
+                error!("Unable to log stabilisation of server {server_id}: {e}");
+                continue;
+            }
+        };
+
+        if in_maintenance {
+            continue;
+        }
 
-    let result = notify_manager.notify(server_id, log_line).await;
-    if let Err(e) = result {
-        error!("Unable to send notification for {server_id}: {e}");
+        let log_line = ServerLogLine::new(server.redacted(), log_line);
+        delivery.notify(server_id, log_line);
     }
 }
@@ -0,0 +1,244 @@
+//! Passive monitors, jobs ping `/api/v1/heartbeat/{token}` and the server is considered
+//! unreachable when no ping arrives within `interval` plus `grace` seconds.
+
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use axum::http::StatusCode;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, trace};
+
+use crate::{
+    channel::{
+        BoundedSender,
+        server::{
+            ServerMessage, ServerStatus,
+            recording::Recorder,
+            rollup::Aggregator,
+            tracker::{StateTracker, Transition},
+        },
+    },
+    model::{Server, ServerState},
+};
+
+fn default_grace() -> u64 {
+    60
+}
+
+/// `check_options` of a `heartbeat` server, e.g `{"grace": 300}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct HeartbeatOptions {
+    /// Seconds a ping may be late before the server is considered unreachable
+    #[serde(default = "default_grace")]
+    grace: u64,
+}
+
+impl HeartbeatOptions {
+    pub fn from_server(server: &Server) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(server.check_options.clone())
+    }
+}
+
+/// Signal sent by a job, `success` and `fail` may also be sent as an exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeartbeatSignal {
+    Start,
+    Success,
+    Fail(Option<i32>),
+}
+
+impl FromStr for HeartbeatSignal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(Self::Start),
+            "success" => Ok(Self::Success),
+            "fail" => Ok(Self::Fail(None)),
+            code => match code.parse::<i32>() {
+                Ok(0) => Ok(Self::Success),
+                Ok(code) => Ok(Self::Fail(Some(code))),
+                Err(_) => Err(format!("unknown heartbeat signal `{s}`")),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HeartbeatPing {
+    pub signal: HeartbeatSignal,
+    /// Log output attached by the job
+    pub payload: Vec<u8>,
+}
+
+impl HeartbeatPing {
+    pub fn new(signal: HeartbeatSignal, payload: Vec<u8>) -> Self {
+        Self { signal, payload }
+    }
+}
+
+pub async fn payload(
+    server: Server,
+    mut pings: mpsc::UnboundedReceiver<HeartbeatPing>,
+    sender: BoundedSender<ServerMessage>,
+    aggregator: Aggregator,
+    cancellation_token: CancellationToken,
+    last_seen_state: ServerState,
+) {
+    let interval = Duration::from_secs(server.interval as u64);
+    let grace = Duration::from_secs(
+        HeartbeatOptions::from_server(&server)
+            .map(|o| o.grace)
+            .unwrap_or_else(|_| default_grace()),
+    );
+
+    let mut tracker = StateTracker::from_server(&server, last_seen_state);
+    let mut recorder = Recorder::default();
+    let mut started: Option<Instant> = None;
+    let mut deadline = tokio::time::Instant::now() + interval + grace;
+
+    loop {
+        let status = tokio::select! {
+            ping = pings.recv() => {
+                let Some(ping) = ping else {
+                    break;
+                };
+                let now = Instant::now();
+                let latency = started.map(|s| now - s).unwrap_or_default();
+
+                match ping.signal {
+                    HeartbeatSignal::Start => {
+                        // a started job has to finish within grace
+                        started = Some(now);
+                        deadline = tokio::time::Instant::now() + grace;
+                        continue;
+                    }
+                    HeartbeatSignal::Success => {
+                        started = None;
+                        deadline = tokio::time::Instant::now() + interval + grace;
+                        ServerStatus::online(StatusCode::OK, ping.payload, latency)
+                    }
+                    HeartbeatSignal::Fail(code) => {
+                        started = None;
+                        deadline = tokio::time::Instant::now() + interval + grace;
+                        let reason = match code {
+                            Some(code) => format!("job failed with exit code {code}"),
+                            None => "job reported a failure".to_string(),
+                        };
+                        ServerStatus::unreachable(
+                            reason,
+                            ping.payload,
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            latency,
+                        )
+                    }
+                }
+            }
+            _ = tokio::time::sleep_until(deadline) => {
+                let reason = match started.take() {
+                    Some(_) => format!("job didn't finish within {}s", grace.as_secs()),
+                    None => format!("no ping received within {}s", (interval + grace).as_secs()),
+                };
+                deadline = tokio::time::Instant::now() + interval;
+                ServerStatus::unreachable(reason, vec![], StatusCode::REQUEST_TIMEOUT, Duration::ZERO)
+            }
+            _ = cancellation_token.cancelled() => {
+                trace!("Heartbeat payload for server {} shut down successfully", server.id);
+                break;
+            }
+        };
+
+        let online = status.state() == ServerState::Online;
+        aggregator.record(
+            server.id,
+            status.state() == ServerState::Unreachable,
+            online.then(|| status.latency()),
+        );
+
+        let transition = tracker.observe(status.state());
+        if let Transition::Suspected { state, count } = transition {
+            debug!(
+                "Server {} suspected {:?} ({} in a row)",
+                server.id, state, count
+            );
+        }
+
+        if let Transition::Confirmed(_) = transition {
+            sender
+                .send(ServerMessage::report(status.into(), server.clone()))
+                .await
+                .ok();
+        } else if recorder.record(&server) {
+            let message = ServerMessage::Checked {
+                server_id: server.id,
+                state: status.state(),
+                status_code: Some(status.status_code()),
+                latency: online.then(|| status.latency()),
+            };
+            sender.send(message).await.ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::channel::BoundedMPSCController;
+
+    #[test]
+    fn test_heartbeat_signal() {
+        assert_eq!("start".parse(), Ok(HeartbeatSignal::Start));
+        assert_eq!("0".parse(), Ok(HeartbeatSignal::Success));
+        assert_eq!("fail".parse(), Ok(HeartbeatSignal::Fail(None)));
+        assert_eq!("137".parse(), Ok(HeartbeatSignal::Fail(Some(137))));
+        assert!("finish".parse::<HeartbeatSignal>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_missed_ping() {
+        let mut server = Server::mock(1, "heartbeat");
+        server.interval = 1;
+        server.check_options = json!({"grace": 0});
+
+        let (ping_tx, ping_rx) = mpsc::unbounded_channel();
+        let channel = BoundedMPSCController::new(16);
+        let tx = channel.get_sender();
+        let mut rx = channel.take_receiver();
+        let token = CancellationToken::new();
+        tokio::spawn(payload(
+            server,
+            ping_rx,
+            tx,
+            Aggregator::default(),
+            token.clone(),
+            ServerState::Unreachable,
+        ));
+
+        ping_tx
+            .send(HeartbeatPing::new(
+                HeartbeatSignal::Success,
+                b"done".to_vec(),
+            ))
+            .unwrap();
+        let Some(ServerMessage::ServerStateChanged { status, .. }) = rx.recv().await else {
+            panic!("expected a state change");
+        };
+        assert_eq!(status.state(), ServerState::Online);
+
+        let Some(ServerMessage::ServerStateChanged { status, .. }) = rx.recv().await else {
+            panic!("expected a state change");
+        };
+        assert!(matches!(
+            status,
+            ServerStatus::Unreachable { reason, .. } if reason == "no ping received within 1s"
+        ));
+
+        token.cancel();
+    }
+}
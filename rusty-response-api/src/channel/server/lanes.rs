@@ -0,0 +1,114 @@
+//! Fixed set of sequential workers.
+//!
+//! Messages are spread over the lanes by key, so messages sharing a key are handled one after
+//! another in the order they were sent, while different keys are handled concurrently.
+
+use std::future::Future;
+
+use tokio::{sync::mpsc, task::JoinSet};
+
+/// Messages a single lane may queue before senders have to wait
+const LANE_CAPACITY: usize = 64;
+
+pub struct Lanes<T> {
+    lanes: Vec<mpsc::Sender<T>>,
+}
+
+impl<T: Send + 'static> Lanes<T> {
+    /// Spawns `count` lanes running `handler`, they finish once the `Lanes` are dropped
+    pub fn spawn<H, F>(count: usize, handler: H) -> (Self, JoinSet<()>)
+    where
+        H: Fn(T) -> F + Clone + Send + 'static,
+        F: Future<Output = ()> + Send,
+    {
+        let mut tasks = JoinSet::new();
+        let lanes = (0..count.max(1))
+            .map(|_| {
+                let (tx, mut rx) = mpsc::channel(LANE_CAPACITY);
+                let handler = handler.clone();
+                tasks.spawn(async move {
+                    while let Some(message) = rx.recv().await {
+                        handler(message).await;
+                    }
+                });
+                tx
+            })
+            .collect();
+
+        (Self { lanes }, tasks)
+    }
+
+    fn lane(&self, key: i64) -> &mpsc::Sender<T> {
+        &self.lanes[key.rem_euclid(self.lanes.len() as i64) as usize]
+    }
+
+    /// Queues the message on the lane of `key`
+    pub async fn send(&self, key: i64, message: T) {
+        self.lane(key).send(message).await.ok();
+    }
+
+    /// Queues the message on the lane of `key` without waiting, returns `false` if the lane is full
+    pub fn try_send(&self, key: i64, message: T) -> bool {
+        match self.lane(key).try_send(message) {
+            Err(mpsc::error::TrySendError::Full(_)) => false,
+            // closed lanes are shutting down, there's nothing to wait for
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lanes_keep_order_per_key() {
+        let handled = Arc::new(Mutex::new(vec![]));
+        let (lanes, mut tasks) = Lanes::spawn(4, {
+            let handled = Arc::clone(&handled);
+            move |(key, seq): (i64, u64)| {
+                let handled = Arc::clone(&handled);
+                async move {
+                    // earlier messages take longer, reordering would show up
+                    tokio::time::sleep(Duration::from_millis(10 - seq * 3)).await;
+                    handled.lock().unwrap().push((key, seq));
+                }
+            }
+        });
+
+        for seq in 0..3 {
+            for key in 0..4 {
+                lanes.send(key, (key, seq)).await;
+            }
+        }
+        drop(lanes);
+        while tasks.join_next().await.is_some() {}
+
+        let handled = handled.lock().unwrap();
+        for key in 0..4 {
+            let seqs: Vec<u64> = handled
+                .iter()
+                .filter(|(k, _)| *k == key)
+                .map(|(_, seq)| *seq)
+                .collect();
+            assert_eq!(seqs, vec![0, 1, 2]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lanes_try_send_full() {
+        let (lanes, _tasks) = Lanes::spawn(1, |_: u64| std::future::pending::<()>());
+
+        // the handler never finishes, at most one message leaves the lane
+        let queued = (0..2 * LANE_CAPACITY as u64)
+            .take_while(|seq| lanes.try_send(0, *seq))
+            .count();
+        assert!(queued <= LANE_CAPACITY + 1);
+        assert!(!lanes.try_send(0, 0));
+    }
+}
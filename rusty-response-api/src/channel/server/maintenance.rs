@@ -0,0 +1,123 @@
+//! Servers covered by an active maintenance window are still checked and logged,
+//! but their state changes aren't notified.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use time::OffsetDateTime;
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, trace};
+
+use crate::{
+    ModelManager,
+    channel::server::{delivery::Delivery, utils::LastSeen},
+    model::{
+        Ctx, Maintenance, MaintenanceBmc, ServerBmc, ServerLogBmc, ServerLogCreate, ServerLogLine,
+        ServerState,
+    },
+};
+
+/// Ids of servers currently under maintenance
+pub type UnderMaintenance = Arc<RwLock<HashSet<i64>>>;
+
+/// How often windows are re-evaluated, schedules have a minute resolution
+const TICK: Duration = Duration::from_secs(15);
+
+/// Logs the start or the end of a window for `server_id`, notifying it if the window asks to
+async fn announce(
+    mm: &ModelManager,
+    ctx: &Ctx,
+    delivery: &Delivery,
+    server_id: i64,
+    window: &Maintenance,
+    started: bool,
+) -> eyre::Result<()> {
+    let Some(server) = ServerBmc::get_by_id(mm, ctx, server_id).await? else {
+        return Ok(());
+    };
+
+    let (state, reason, server) = if started {
+        let reason = format!("maintenance `{}` started", window.name);
+        (ServerState::Maintenance, reason, server.in_maintenance())
+    } else {
+        let reason = format!("maintenance `{}` ended", window.name);
+        (LastSeen::from_server(&server).state, reason, server)
+    };
+    let code = server.last_seen_status_code.unwrap_or(0);
+
+    let lc = ServerLogCreate::new(server_id, state, code, None, Some(reason));
+    let log = ServerLogBmc::insert(mm, ctx, lc).await?;
+
+    if window.notify {
+        delivery.notify(server_id, ServerLogLine::new(server.redacted(), log));
+    }
+
+    Ok(())
+}
+
+pub async fn payload(
+    mm: ModelManager,
+    delivery: Arc<Delivery>,
+    under_maintenance: UnderMaintenance,
+    cancellation_token: CancellationToken,
+) {
+    let ctx = Ctx::admin_root();
+    // window covering each server on the previous tick
+    let mut covered: Option<HashMap<i64, Maintenance>> = None;
+
+    loop {
+        match MaintenanceBmc::all(&mm, &ctx).await {
+            Ok(windows) => {
+                let now = OffsetDateTime::now_utc();
+                let mut current = HashMap::new();
+                for window in windows.into_iter().filter(|w| w.is_active(now)) {
+                    for server_id in &window.server_ids {
+                        current.entry(*server_id).or_insert_with(|| window.clone());
+                    }
+                }
+
+                *under_maintenance.write().await = current.keys().copied().collect();
+
+                // the first tick is only a baseline, so restarts don't announce running windows again
+                if let Some(previous) = &covered {
+                    let started = current
+                        .iter()
+                        .filter(|(id, _)| !previous.contains_key(id))
+                        .map(|(id, window)| (id, window, true));
+                    let ended = previous
+                        .iter()
+                        .filter(|(id, _)| !current.contains_key(id))
+                        .map(|(id, window)| (id, window, false));
+
+                    for (server_id, window, started) in started.chain(ended) {
+                        debug!(
+                            "Maintenance `{}` of server {} {}",
+                            window.name,
+                            server_id,
+                            if started { "started" } else { "ended" }
+                        );
+                        let result =
+                            announce(&mm, &ctx, &delivery, *server_id, window, started).await;
+                        if let Err(e) = result {
+                            error!("Unable to announce maintenance of server {server_id}: {e}");
+                        }
+                    }
+                }
+                covered = Some(current);
+            }
+            Err(e) => error!("Unable to fetch maintenance windows: {e}"),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(TICK) => {},
+            _ = cancellation_token.cancelled() => {
+                trace!("Maintenance payload shut down successfully");
+                break;
+            }
+        }
+    }
+}
@@ -1,9 +1,26 @@
+mod delivery;
+mod escalation;
+mod flapping;
 mod handler;
+mod heartbeat;
+mod lanes;
+mod maintenance;
+mod probe;
+mod recording;
+mod reminder;
+mod retention;
+mod rollup;
 mod runner;
+mod scheduler;
+mod suppressed;
+mod tracker;
 mod types;
 mod utils;
 pub use super::Error;
 
-pub use handler::handle_server_response;
+pub use handler::{handle_server_response, handle_stabilised};
+pub use heartbeat::{HeartbeatPing, HeartbeatSignal};
+pub use maintenance::UnderMaintenance;
+pub use probe::validate as validate_server;
 pub use runner::setup_monitoring_future;
-pub use types::{ControlMessage, ServerMessage, ServerStatus};
+pub use types::{CertificateInfo, ControlMessage, ProbeReport, ServerMessage, ServerStatus};
@@ -0,0 +1,238 @@
+use std::fmt;
+
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Deserializer, de};
+use serde_json::Value;
+
+/// Regex compiled once, when the options are parsed
+#[derive(Debug, Clone)]
+pub struct Pattern(Regex);
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(Self)
+            .map_err(|e| de::Error::custom(format!("invalid regex `{pattern}`: {e}")))
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.as_str())
+    }
+}
+
+/// Check performed against an HTTP response, configured in `check_options.assertions`, e.g:
+/// `[{"type": "body_contains", "value": "ok"}, {"type": "json_pointer", "pointer": "/status", "value": "up"}]`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Assertion {
+    BodyContains {
+        value: String,
+    },
+    BodyNotContains {
+        value: String,
+    },
+    BodyMatches {
+        pattern: Pattern,
+    },
+    /// Value at the [RFC 6901](https://www.rfc-editor.org/rfc/rfc6901) pointer of a JSON body
+    JsonPointer {
+        pointer: String,
+        value: Value,
+    },
+    /// Required response header, optionally with an exact value
+    Header {
+        name: String,
+        value: Option<String>,
+    },
+}
+
+impl Assertion {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Assertion::JsonPointer { pointer, .. }
+                if !pointer.is_empty() && !pointer.starts_with('/') =>
+            {
+                Err(format!(
+                    "invalid json pointer `{pointer}`, must start with `/`"
+                ))
+            }
+            Assertion::Header { name, .. } => reqwest::header::HeaderName::try_from(name.as_str())
+                .map(|_| ())
+                .map_err(|_| format!("invalid header name `{name}`")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns a human readable reason when the response doesn't satisfy the assertion
+    pub fn check(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), String> {
+        let failed = |details: String| Err(format!("assertion `{self}` failed: {details}"));
+        let text = || String::from_utf8_lossy(body);
+
+        match self {
+            Assertion::BodyContains { value } if !text().contains(value.as_str()) => {
+                failed("body does not contain the value".to_string())
+            }
+            Assertion::BodyNotContains { value } if text().contains(value.as_str()) => {
+                failed("body contains the value".to_string())
+            }
+            Assertion::BodyMatches { pattern } if !pattern.0.is_match(&text()) => {
+                failed("body does not match".to_string())
+            }
+            Assertion::JsonPointer { pointer, value } => {
+                let json: Value = match serde_json::from_slice(body) {
+                    Ok(json) => json,
+                    Err(e) => return failed(format!("body is not valid json: {e}")),
+                };
+
+                match json.pointer(pointer) {
+                    Some(actual) if actual == value => Ok(()),
+                    Some(actual) => failed(format!("got {actual}")),
+                    None => failed("pointer not found".to_string()),
+                }
+            }
+            Assertion::Header { name, value } => {
+                let Some(actual) = headers.get(name.as_str()) else {
+                    return failed("header is missing".to_string());
+                };
+
+                match value {
+                    Some(value) if actual.as_bytes() != value.as_bytes() => failed(format!(
+                        "got `{}`",
+                        String::from_utf8_lossy(actual.as_bytes())
+                    )),
+                    _ => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Assertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Assertion::BodyContains { value } => write!(f, "body contains \"{value}\""),
+            Assertion::BodyNotContains { value } => write!(f, "body does not contain \"{value}\""),
+            Assertion::BodyMatches { pattern } => write!(f, "body matches /{pattern}/"),
+            Assertion::JsonPointer { pointer, value } => write!(f, "json {pointer} == {value}"),
+            Assertion::Header { name, value: None } => write!(f, "header {name} is present"),
+            Assertion::Header {
+                name,
+                value: Some(value),
+            } => write!(f, "header {name} == \"{value}\""),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use reqwest::header::{CONTENT_TYPE, HeaderValue};
+    use serde_json::json;
+
+    use super::*;
+
+    fn assertion(value: Value) -> Assertion {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_assertion_body() {
+        let headers = HeaderMap::new();
+        let body = br#"{"status": "up", "checks": {"db": 3}}"#;
+
+        assert!(
+            assertion(json!({"type": "body_contains", "value": "\"up\""}))
+                .check(&headers, body)
+                .is_ok()
+        );
+        assert!(
+            assertion(json!({"type": "body_matches", "pattern": r#""db": \d+"#}))
+                .check(&headers, body)
+                .is_ok()
+        );
+        assert_eq!(
+            assertion(json!({"type": "body_not_contains", "value": "status"}))
+                .check(&headers, body),
+            Err(
+                "assertion `body does not contain \"status\"` failed: body contains the value"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_assertion_json_pointer() {
+        let headers = HeaderMap::new();
+        let body = br#"{"status": "up", "checks": {"db": 3}}"#;
+
+        assert!(
+            assertion(json!({"type": "json_pointer", "pointer": "/checks/db", "value": 3}))
+                .check(&headers, body)
+                .is_ok()
+        );
+        assert_eq!(
+            assertion(json!({"type": "json_pointer", "pointer": "/status", "value": "down"}))
+                .check(&headers, body),
+            Err("assertion `json /status == \"down\"` failed: got \"up\"".to_string())
+        );
+        assert!(
+            assertion(json!({"type": "json_pointer", "pointer": "/missing", "value": null}))
+                .check(&headers, body)
+                .is_err()
+        );
+        assert!(
+            assertion(json!({"type": "json_pointer", "pointer": "/status", "value": "up"}))
+                .check(&headers, b"<html>")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_assertion_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        assert!(
+            assertion(json!({"type": "header", "name": "Content-Type"}))
+                .check(&headers, b"")
+                .is_ok()
+        );
+        assert!(
+            assertion(json!({"type": "header", "name": "content-type", "value": "text/html"}))
+                .check(&headers, b"")
+                .is_err()
+        );
+        assert!(
+            assertion(json!({"type": "header", "name": "x-request-id"}))
+                .check(&headers, b"")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_assertion_validate() {
+        // patterns are compiled, so invalid ones don't parse at all
+        let invalid = json!({"type": "body_matches", "pattern": "(unclosed"});
+        assert!(serde_json::from_value::<Assertion>(invalid).is_err());
+        assert!(
+            assertion(json!({"type": "json_pointer", "pointer": "status", "value": 1}))
+                .validate()
+                .is_err()
+        );
+        assert!(
+            assertion(json!({"type": "header", "name": "bad header"}))
+                .validate()
+                .is_err()
+        );
+    }
+}
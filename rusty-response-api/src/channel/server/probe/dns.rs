@@ -0,0 +1,362 @@
+use std::{
+    collections::BTreeSet,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use axum::http::StatusCode;
+use hickory_resolver::{
+    TokioAsyncResolver,
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
+    proto::rr::{RData, RecordType},
+    system_conf,
+};
+use serde::Deserialize;
+
+use crate::{channel::server::ServerStatus, model::Server};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DnsRecordType {
+    #[default]
+    A,
+    Aaaa,
+    Cname,
+    Mx,
+    Txt,
+}
+
+impl From<DnsRecordType> for RecordType {
+    fn from(value: DnsRecordType) -> Self {
+        match value {
+            DnsRecordType::A => RecordType::A,
+            DnsRecordType::Aaaa => RecordType::AAAA,
+            DnsRecordType::Cname => RecordType::CNAME,
+            DnsRecordType::Mx => RecordType::MX,
+            DnsRecordType::Txt => RecordType::TXT,
+        }
+    }
+}
+
+/// `check_options` of a `dns` server, e.g:
+/// `{"record_type": "MX", "resolver": "1.1.1.1", "expected": ["10 mx.example.com"]}`
+///
+/// MX answers are compared as `<preference> <exchange>`, TXT answers as the joined string.
+/// With no `expected` values any non-empty answer is considered healthy.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DnsOptions {
+    #[serde(default)]
+    record_type: DnsRecordType,
+    resolver: Option<String>,
+    #[serde(default)]
+    expected: Vec<String>,
+}
+
+impl DnsOptions {
+    pub fn from_server(server: &Server) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(server.check_options.clone())
+    }
+
+    fn resolver_addr(&self) -> Result<Option<SocketAddr>, DnsFailure> {
+        let Some(resolver) = &self.resolver else {
+            return Ok(None);
+        };
+
+        if let Ok(addr) = resolver.parse::<SocketAddr>() {
+            return Ok(Some(addr));
+        }
+
+        resolver
+            .parse::<IpAddr>()
+            .map(|ip| Some(SocketAddr::new(ip, 53)))
+            .map_err(|_| DnsFailure::InvalidOptions(format!("invalid resolver: {resolver}")))
+    }
+}
+
+/// Reason of a failed DNS probe, rendered into `server_log.reason`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsFailure {
+    InvalidOptions(String),
+    NoRecords,
+    TimedOut,
+    Resolve(String),
+    Drift {
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+}
+
+impl DnsFailure {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            DnsFailure::NoRecords => StatusCode::NOT_FOUND,
+            DnsFailure::TimedOut => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+impl From<ResolveError> for DnsFailure {
+    fn from(value: ResolveError) -> Self {
+        match value.kind() {
+            ResolveErrorKind::NoRecordsFound { .. } => Self::NoRecords,
+            ResolveErrorKind::Timeout => Self::TimedOut,
+            _ => Self::Resolve(value.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for DnsFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsFailure::InvalidOptions(e) => write!(f, "invalid dns options: {e}"),
+            DnsFailure::NoRecords => write!(f, "no records found"),
+            DnsFailure::TimedOut => write!(f, "dns query timed out"),
+            DnsFailure::Resolve(e) => write!(f, "dns resolution failed: {e}"),
+            DnsFailure::Drift { expected, actual } => write!(
+                f,
+                "dns answers drifted: expected [{}], got [{}]",
+                expected.join(", "),
+                actual.join(", ")
+            ),
+        }
+    }
+}
+
+pub fn validate(server_name: &str, options: &DnsOptions) -> Result<(), String> {
+    if name(server_name).is_empty() {
+        return Err("dns name to resolve is empty".to_string());
+    }
+
+    options.resolver_addr().map(|_| ()).map_err(|e| e.to_string())
+}
+
+pub async fn check(server: &Server, timeout: Duration) -> ServerStatus {
+    let started = Instant::now();
+    let result = match DnsOptions::from_server(server) {
+        Ok(options) => resolve(&server.url, &options, timeout).await,
+        Err(e) => Err(DnsFailure::InvalidOptions(e.to_string())),
+    };
+    let latency = started.elapsed();
+
+    match result {
+        Ok(answers) => {
+            ServerStatus::online(StatusCode::OK, answers.join("\n").into_bytes(), latency)
+        }
+        Err(failure) => {
+            let body = match &failure {
+                DnsFailure::Drift { actual, .. } => actual.join("\n").into_bytes(),
+                _ => Vec::new(),
+            };
+            ServerStatus::unreachable(failure.to_string(), body, failure.status_code(), latency)
+        }
+    }
+}
+
+fn name(url: &str) -> &str {
+    let url = url.trim();
+    url.strip_prefix("dns://").unwrap_or(url).trim_end_matches('/')
+}
+
+/// Lowercases names and strips the trailing root dot, so `Mail.Example.com.` equals `mail.example.com`
+fn normalize(value: &str) -> String {
+    value.trim().trim_end_matches('.').to_lowercase()
+}
+
+async fn resolve(
+    url: &str,
+    options: &DnsOptions,
+    timeout: Duration,
+) -> Result<Vec<String>, DnsFailure> {
+    let (config, mut opts) = match options.resolver_addr()? {
+        Some(addr) => (
+            ResolverConfig::from_parts(
+                None,
+                vec![],
+                NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true),
+            ),
+            ResolverOpts::default(),
+        ),
+        None => system_conf::read_system_conf()
+            .unwrap_or_else(|_| (ResolverConfig::default(), ResolverOpts::default())),
+    };
+
+    // every probe has to hit the resolver, otherwise drifts are hidden for the whole TTL
+    opts.cache_size = 0;
+    opts.attempts = 1;
+    opts.timeout = timeout;
+    opts.use_hosts_file = false;
+
+    let resolver = TokioAsyncResolver::tokio(config, opts);
+    let record_type = RecordType::from(options.record_type);
+    let lookup = tokio::time::timeout(timeout, resolver.lookup(name(url), record_type))
+        .await
+        .map_err(|_| DnsFailure::TimedOut)??;
+
+    let actual: BTreeSet<String> = lookup
+        .record_iter()
+        .filter(|r| r.record_type() == record_type)
+        .filter_map(|r| r.data())
+        .filter_map(|data| match data {
+            RData::A(a) => Some(a.to_string()),
+            RData::AAAA(aaaa) => Some(aaaa.to_string()),
+            RData::CNAME(cname) => Some(normalize(&cname.to_string())),
+            RData::MX(mx) => Some(format!(
+                "{} {}",
+                mx.preference(),
+                normalize(&mx.exchange().to_string())
+            )),
+            RData::TXT(txt) => Some(
+                txt.txt_data()
+                    .iter()
+                    .map(|part| String::from_utf8_lossy(part))
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .collect();
+
+    if actual.is_empty() {
+        return Err(DnsFailure::NoRecords);
+    }
+
+    let actual: Vec<String> = actual.into_iter().collect();
+    if options.expected.is_empty() {
+        return Ok(actual);
+    }
+
+    let expected: BTreeSet<String> = options
+        .expected
+        .iter()
+        .map(|value| match options.record_type {
+            DnsRecordType::Txt => value.clone(),
+            _ => normalize(value),
+        })
+        .collect();
+    let expected: Vec<String> = expected.into_iter().collect();
+
+    if expected != actual {
+        return Err(DnsFailure::Drift { expected, actual });
+    }
+
+    Ok(actual)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::Ipv4Addr, str::FromStr};
+
+    use hickory_resolver::proto::{
+        op::{Message, MessageType, ResponseCode},
+        rr::{Name, Record, rdata},
+    };
+    use tokio::net::UdpSocket;
+
+    use super::*;
+
+    /// Minimal UDP resolver answering every query with `records` for the queried name,
+    /// or NXDOMAIN when there's nothing to answer with
+    async fn stub_resolver(records: Vec<RData>) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            loop {
+                let Ok((len, peer)) = socket.recv_from(&mut buf).await else {
+                    break;
+                };
+                let request = Message::from_vec(&buf[..len]).unwrap();
+                let query = request.queries()[0].clone();
+
+                let mut response = Message::new();
+                response
+                    .set_id(request.id())
+                    .set_message_type(MessageType::Response)
+                    .set_op_code(request.op_code())
+                    .set_recursion_desired(true)
+                    .set_recursion_available(true)
+                    .add_query(query.clone());
+
+                let answers: Vec<Record> = records
+                    .iter()
+                    .filter(|data| data.record_type() == query.query_type())
+                    .map(|data| Record::from_rdata(query.name().clone(), 60, data.clone()))
+                    .collect();
+
+                if answers.is_empty() {
+                    response.set_response_code(ResponseCode::NXDomain);
+                }
+                response.add_answers(answers);
+
+                socket
+                    .send_to(&response.to_vec().unwrap(), peer)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        addr
+    }
+
+    fn options(record_type: DnsRecordType, resolver: SocketAddr, expected: &[&str]) -> DnsOptions {
+        DnsOptions {
+            record_type,
+            resolver: Some(resolver.to_string()),
+            expected: expected.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dns_expected_answers() {
+        let resolver = stub_resolver(vec![
+            RData::A(rdata::A(Ipv4Addr::new(10, 0, 0, 2))),
+            RData::A(rdata::A(Ipv4Addr::new(10, 0, 0, 1))),
+        ])
+        .await;
+
+        let opts = options(DnsRecordType::A, resolver, &["10.0.0.1", "10.0.0.2"]);
+        let answers = resolve("example.com", &opts, Duration::from_secs(2)).await;
+
+        assert_eq!(answers, Ok(vec!["10.0.0.1".to_string(), "10.0.0.2".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_dns_answers_drift() {
+        let resolver = stub_resolver(vec![RData::A(rdata::A(Ipv4Addr::new(10, 0, 0, 3)))]).await;
+
+        let opts = options(DnsRecordType::A, resolver, &["10.0.0.1"]);
+        let answers = resolve("example.com", &opts, Duration::from_secs(2)).await;
+
+        assert_eq!(
+            answers,
+            Err(DnsFailure::Drift {
+                expected: vec!["10.0.0.1".to_string()],
+                actual: vec!["10.0.0.3".to_string()],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dns_mx_normalized() {
+        let exchange = Name::from_str("Mail.Example.com.").unwrap();
+        let resolver = stub_resolver(vec![RData::MX(rdata::MX::new(10, exchange))]).await;
+
+        let opts = options(DnsRecordType::Mx, resolver, &["10 mail.example.com"]);
+        let answers = resolve("dns://example.com", &opts, Duration::from_secs(2)).await;
+
+        assert_eq!(answers, Ok(vec!["10 mail.example.com".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_dns_no_records() {
+        let resolver = stub_resolver(vec![]).await;
+
+        let opts = options(DnsRecordType::Txt, resolver, &[]);
+        let answers = resolve("example.com", &opts, Duration::from_secs(2)).await;
+
+        assert_eq!(answers, Err(DnsFailure::NoRecords));
+    }
+}
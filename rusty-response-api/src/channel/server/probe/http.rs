@@ -0,0 +1,204 @@
+use std::time::{Duration, Instant};
+
+use reqwest::{
+    Method, StatusCode,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
+use serde::Deserialize;
+use tracing::error;
+
+use super::assertion::Assertion;
+use crate::{
+    channel::server::{Error, ServerStatus},
+    model::{Server, ServerHeader},
+};
+
+/// `check_options` of an `http` server
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HttpOptions {
+    /// Every assertion has to pass for the server to be considered online
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
+}
+
+impl HttpOptions {
+    pub fn from_server(server: &Server) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(server.check_options.clone())
+    }
+}
+
+pub fn validate(url: &str, options: Result<HttpOptions, serde_json::Error>) -> Result<(), String> {
+    reqwest::Url::parse(url).map_err(|e| format!("invalid url: {e}"))?;
+    let options = options.map_err(|e| format!("invalid http options: {e}"))?;
+
+    options.assertions.iter().try_for_each(Assertion::validate)
+}
+
+pub fn validate_request(method: Option<&str>, headers: &[ServerHeader]) -> Result<(), String> {
+    if let Some(method) = method {
+        Method::from_bytes(method.to_uppercase().as_bytes())
+            .map_err(|_| format!("invalid http method `{method}`"))?;
+    }
+
+    headers.iter().try_for_each(|header| {
+        HeaderName::from_bytes(header.name.as_bytes())
+            .map_err(|_| format!("invalid header name `{}`", header.name))?;
+        HeaderValue::from_str(&header.value)
+            .map(|_| ())
+            .map_err(|_| format!("invalid value of header `{}`", header.name))
+    })
+}
+
+/// Client shared by http probes. Redirects aren't followed, so `3xx` codes can be accepted and
+/// a redirect to an error page isn't reported as the status of the server
+pub fn client() -> reqwest::Client {
+    reqwest::ClientBuilder::new()
+        .user_agent(format!(
+            "{} - {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        ))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("unable to create reqwest client")
+}
+
+/// Builds the configured request, failures are reported as a reason of the unreachable status
+fn request(server: &Server, client: &reqwest::Client) -> Result<reqwest::RequestBuilder, String> {
+    let method = Method::from_bytes(server.method.as_bytes())
+        .map_err(|_| format!("invalid http method `{}`", server.method))?;
+
+    let mut headers = HeaderMap::new();
+    for header in server.headers.iter() {
+        let value = header
+            .reveal()
+            .map_err(|e| format!("unable to decrypt header `{}`: {e}", header.name))?;
+        let name = HeaderName::from_bytes(header.name.as_bytes())
+            .map_err(|_| format!("invalid header name `{}`", header.name))?;
+        let value = HeaderValue::from_str(&value)
+            .map_err(|_| format!("invalid value of header `{}`", header.name))?;
+        headers.append(name, value);
+    }
+
+    let mut request = client.request(method, &server.url).headers(headers);
+    if let Some(body) = &server.request_body {
+        request = request.body(body.clone());
+    }
+
+    Ok(request)
+}
+
+pub async fn check(
+    server: &Server,
+    options: Result<&HttpOptions, &str>,
+    client: &reqwest::Client,
+    timeout: Duration,
+) -> Result<ServerStatus, Error> {
+    let started = Instant::now();
+    let request = match request(server, client) {
+        Ok(request) => request,
+        Err(reason) => {
+            return Ok(ServerStatus::unreachable(
+                reason,
+                Vec::new(),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                started.elapsed(),
+            ));
+        }
+    };
+    let response = request.timeout(timeout).send().await?;
+
+    let status = response.status();
+    let headers = response.headers().clone();
+    let mut body = Vec::new();
+    match response.bytes().await {
+        Ok(bytes) => body.extend_from_slice(&bytes),
+        Err(e) => {
+            error!(
+                "Error during reading response body from server: {}. Error: {:#?}",
+                server.id, e
+            );
+            body.extend_from_slice(b"Unable to read body");
+        }
+    }
+    let latency = started.elapsed();
+
+    if !server.accepted_codes().contains(status.as_u16()) {
+        let reason = format!("unexpected status code {status}");
+        return Ok(ServerStatus::unreachable(reason, body, status, latency));
+    }
+
+    let options = match options {
+        Ok(options) => options,
+        Err(e) => {
+            let reason = format!("invalid http options: {e}");
+            return Ok(ServerStatus::unreachable(reason, body, status, latency));
+        }
+    };
+
+    let failed = options
+        .assertions
+        .iter()
+        .find_map(|assertion| assertion.check(&headers, &body).err());
+    if let Some(reason) = failed {
+        return Ok(ServerStatus::unreachable(reason, body, status, latency));
+    }
+
+    Ok(ServerStatus::online(status, body, latency))
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    use super::*;
+    use crate::model::ServerState;
+
+    /// Serves a single redirect to `/elsewhere`, which isn't served at all
+    async fn redirecting() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let response = "HTTP/1.1 301 Moved Permanently\r\n\
+                            Location: /elsewhere\r\n\
+                            Content-Length: 0\r\n\
+                            Connection: close\r\n\r\n";
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+        format!("http://{addr}/")
+    }
+
+    #[tokio::test]
+    async fn test_http_accepted_redirect() {
+        let mut server = Server::mock(1, &redirecting().await);
+        server.accepted_status_codes = "200-299,301".to_string();
+
+        let status = check(
+            &server,
+            Ok(&HttpOptions::default()),
+            &client(),
+            Duration::from_secs(2),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status.status_code(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(status.state(), ServerState::Online);
+    }
+
+    #[tokio::test]
+    async fn test_http_unaccepted_redirect() {
+        let server = Server::mock(1, &redirecting().await);
+
+        let status = check(
+            &server,
+            Ok(&HttpOptions::default()),
+            &client(),
+            Duration::from_secs(2),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status.status_code(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(status.state(), ServerState::Unreachable);
+    }
+}
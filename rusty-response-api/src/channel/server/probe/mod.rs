@@ -0,0 +1,136 @@
+//! Probes performed by the monitoring backend, one module per [`ServerKind`].
+//!
+//! Every probe resolves to a [`ProbeReport`], the runner only decides whether it's worth reporting.
+
+mod assertion;
+mod dns;
+mod http;
+mod tcp;
+mod tls;
+
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use serde_json::json;
+
+use super::{Error, ProbeReport, ServerStatus, heartbeat::HeartbeatOptions};
+use crate::model::{Server, ServerCreate, ServerKind, StatusCodes};
+
+pub use http::client;
+
+/// Options of a server parsed once when it's loaded, so probes don't parse them again
+#[derive(Debug)]
+pub struct ProbeOptions {
+    http: Result<http::HttpOptions, String>,
+}
+
+impl ProbeOptions {
+    pub fn from_server(server: &Server) -> Self {
+        let http = match server.kind {
+            ServerKind::Http => http::HttpOptions::from_server(server).map_err(|e| e.to_string()),
+            _ => Ok(http::HttpOptions::default()),
+        };
+
+        Self { http }
+    }
+}
+
+pub async fn check(
+    server: &Server,
+    options: &ProbeOptions,
+    client: &reqwest::Client,
+) -> Result<ProbeReport, Error> {
+    let timeout = Duration::from_secs(server.timeout as u64);
+
+    let mut report: ProbeReport = match server.kind {
+        ServerKind::Http => {
+            let options = options.http.as_ref().map_err(String::as_str);
+            http::check(server, options, client, timeout).await?.into()
+        }
+        ServerKind::Tcp => tcp::check(server, timeout).await.into(),
+        ServerKind::Dns => dns::check(server, timeout).await.into(),
+        ServerKind::Tls => tls::check(server, timeout).await,
+        ServerKind::Heartbeat => ServerStatus::unreachable(
+            "heartbeat servers are fed by pings, not probed",
+            vec![],
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Duration::ZERO,
+        )
+        .into(),
+    };
+
+    let threshold = server
+        .degraded_threshold_ms
+        .map(|ms| Duration::from_millis(ms as u64));
+    report.status = report.status.with_latency_threshold(threshold);
+
+    Ok(report)
+}
+
+/// Validates the kind specific part of the server configuration, so misconfigured servers are
+/// rejected by the API instead of being reported as unreachable forever.
+pub fn validate(sc: &ServerCreate) -> Result<(), String> {
+    if let Some(codes) = &sc.accepted_status_codes {
+        codes.parse::<StatusCodes>()?;
+    }
+
+    if matches!(sc.degraded_threshold_ms, Some(ms) if ms <= 0) {
+        return Err("degraded threshold must be positive".to_string());
+    }
+
+    if matches!(sc.failure_threshold, Some(n) if n < 1)
+        || matches!(sc.recovery_threshold, Some(n) if n < 1)
+    {
+        return Err("failure and recovery thresholds must be at least 1".to_string());
+    }
+
+    if matches!(sc.recheck_interval, Some(secs) if secs <= 0) {
+        return Err("re-check interval must be positive".to_string());
+    }
+
+    if matches!(sc.flap_threshold, Some(n) if n < 2) {
+        return Err("flap threshold must be at least 2".to_string());
+    }
+
+    if matches!(sc.flap_window, Some(secs) if secs <= 0) {
+        return Err("flap window must be positive".to_string());
+    }
+
+    if matches!(sc.record_sample, Some(n) if n < 1) {
+        return Err("record sample must be at least 1".to_string());
+    }
+
+    if matches!(sc.log_retention_days, Some(days) if days < 0) {
+        return Err("log retention can't be negative".to_string());
+    }
+
+    match sc.kind.unwrap_or_default() {
+        ServerKind::Http => {
+            let options = sc.check_options.clone().unwrap_or_else(|| json!({}));
+            http::validate(&sc.url, serde_json::from_value(options))?;
+            http::validate_request(
+                sc.method.as_deref(),
+                sc.headers.as_deref().unwrap_or_default(),
+            )
+        }
+        ServerKind::Tcp => tcp::target(&sc.url)
+            .map(|_| ())
+            .ok_or_else(|| tcp::ConnectFailure::InvalidAddress.to_string()),
+        ServerKind::Dns => {
+            let options = sc.check_options.clone().unwrap_or_else(|| json!({}));
+            let options: dns::DnsOptions =
+                serde_json::from_value(options).map_err(|e| format!("invalid dns options: {e}"))?;
+            dns::validate(&sc.url, &options)
+        }
+        ServerKind::Tls => {
+            let options = sc.check_options.clone().unwrap_or_else(|| json!({}));
+            tls::validate(&sc.url, serde_json::from_value(options))
+        }
+        ServerKind::Heartbeat => {
+            let options = sc.check_options.clone().unwrap_or_else(|| json!({}));
+            serde_json::from_value::<HeartbeatOptions>(options)
+                .map(|_| ())
+                .map_err(|e| format!("invalid heartbeat options: {e}"))
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    fmt, io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use axum::http::StatusCode;
+use tokio::net::{TcpStream, lookup_host};
+
+use crate::{channel::server::ServerStatus, model::Server};
+
+/// Reason of a failed TCP probe, rendered into `server_log.reason`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectFailure {
+    InvalidAddress,
+    Resolve(String),
+    Refused,
+    Reset,
+    TimedOut,
+    Unreachable,
+    Other(String),
+}
+
+impl ConnectFailure {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ConnectFailure::TimedOut => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+impl From<io::Error> for ConnectFailure {
+    fn from(value: io::Error) -> Self {
+        match value.kind() {
+            io::ErrorKind::ConnectionRefused => Self::Refused,
+            io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted => Self::Reset,
+            io::ErrorKind::TimedOut => Self::TimedOut,
+            io::ErrorKind::HostUnreachable | io::ErrorKind::NetworkUnreachable => {
+                Self::Unreachable
+            }
+            _ => Self::Other(value.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ConnectFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectFailure::InvalidAddress => write!(f, "invalid address, expected host:port"),
+            ConnectFailure::Resolve(e) => write!(f, "unable to resolve host: {e}"),
+            ConnectFailure::Refused => write!(f, "connection refused"),
+            ConnectFailure::Reset => write!(f, "connection reset"),
+            ConnectFailure::TimedOut => write!(f, "connection timed out"),
+            ConnectFailure::Unreachable => write!(f, "host unreachable"),
+            ConnectFailure::Other(e) => write!(f, "connection failed: {e}"),
+        }
+    }
+}
+
+/// Splits `host:port` (optionally prefixed with `tcp://`) into its parts.
+/// IPv6 hosts have to be wrapped into brackets, e.g. `[::1]:5432`.
+pub fn target(url: &str) -> Option<(&str, u16)> {
+    let url = url.trim();
+    let addr = url.strip_prefix("tcp://").unwrap_or(url).trim_end_matches('/');
+    let (host, port) = addr.rsplit_once(':')?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+
+    if host.is_empty() || host.contains(['/', ' ']) {
+        return None;
+    }
+
+    let port = port.parse::<u16>().ok().filter(|p| *p != 0)?;
+    Some((host, port))
+}
+
+pub async fn check(server: &Server, timeout: Duration) -> ServerStatus {
+    let started = Instant::now();
+    let result = connect(&server.url, timeout).await;
+    let latency = started.elapsed();
+
+    match result {
+        Ok(()) => ServerStatus::online(StatusCode::OK, Vec::new(), latency),
+        Err(failure) => ServerStatus::unreachable(
+            failure.to_string(),
+            Vec::new(),
+            failure.status_code(),
+            latency,
+        ),
+    }
+}
+
+async fn connect(url: &str, timeout: Duration) -> Result<(), ConnectFailure> {
+    let (host, port) = target(url).ok_or(ConnectFailure::InvalidAddress)?;
+
+    let attempt = async {
+        let addrs: Vec<SocketAddr> = lookup_host((host, port))
+            .await
+            .map_err(|e| ConnectFailure::Resolve(e.to_string()))?
+            .collect();
+
+        TcpStream::connect(&addrs[..]).await?;
+        Ok(())
+    };
+
+    tokio::time::timeout(timeout, attempt)
+        .await
+        .unwrap_or(Err(ConnectFailure::TimedOut))
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn test_tcp_target() {
+        assert_eq!(target("localhost:5432"), Some(("localhost", 5432)));
+        assert_eq!(target("tcp://10.0.0.1:25"), Some(("10.0.0.1", 25)));
+        assert_eq!(target("[::1]:6379"), Some(("::1", 6379)));
+        assert_eq!(target("localhost"), None);
+        assert_eq!(target("localhost:0"), None);
+        assert_eq!(target(":80"), None);
+        assert_eq!(target("http://localhost:80"), None);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_connect_online() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let result = connect(&addr.to_string(), Duration::from_secs(1)).await;
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_tcp_connect_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let result = connect(&addr.to_string(), Duration::from_secs(1)).await;
+        assert_eq!(result, Err(ConnectFailure::Refused));
+    }
+}
@@ -0,0 +1,370 @@
+use std::{
+    fmt, io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::http::StatusCode;
+use serde::Deserialize;
+use time::OffsetDateTime;
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    TlsConnector,
+    rustls::{
+        self, CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore,
+        SignatureScheme,
+        client::{
+            WebPkiServerVerifier,
+            danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        },
+        crypto::ring,
+        pki_types::{CertificateDer, ServerName, UnixTime},
+    },
+};
+
+use crate::{
+    channel::server::{CertificateInfo, ProbeReport, ServerStatus},
+    model::Server,
+};
+
+fn default_warn_days() -> i64 {
+    14
+}
+
+/// `check_options` of a `tls` server, e.g `{"warn_days": 30}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsOptions {
+    /// Server goes into the `warning` state this many days before the certificate expires
+    #[serde(default = "default_warn_days")]
+    warn_days: i64,
+}
+
+impl TlsOptions {
+    pub fn from_server(server: &Server) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(server.check_options.clone())
+    }
+}
+
+/// Reason of a failed TLS probe, rendered into `server_log.reason`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsFailure {
+    InvalidAddress,
+    InvalidOptions(String),
+    Connect(String),
+    TimedOut,
+    Expired,
+    NotYetValid,
+    HostnameMismatch,
+    UntrustedChain,
+    InvalidCertificate(String),
+    Handshake(String),
+}
+
+impl TlsFailure {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            TlsFailure::TimedOut => StatusCode::GATEWAY_TIMEOUT,
+            TlsFailure::InvalidAddress | TlsFailure::InvalidOptions(_) | TlsFailure::Connect(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            _ => StatusCode::from_u16(495).unwrap(), // SSL Certificate Error
+        }
+    }
+
+    fn from_handshake(e: io::Error) -> Self {
+        let Some(rustls::Error::InvalidCertificate(cert_err)) = e
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+        else {
+            return Self::Handshake(e.to_string());
+        };
+
+        match cert_err {
+            CertificateError::Expired | CertificateError::ExpiredContext { .. } => Self::Expired,
+            CertificateError::NotValidYet | CertificateError::NotValidYetContext { .. } => {
+                Self::NotYetValid
+            }
+            CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. } => {
+                Self::HostnameMismatch
+            }
+            CertificateError::UnknownIssuer => Self::UntrustedChain,
+            other => Self::InvalidCertificate(format!("{other:?}")),
+        }
+    }
+}
+
+impl fmt::Display for TlsFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsFailure::InvalidAddress => write!(f, "invalid address, expected host[:port]"),
+            TlsFailure::InvalidOptions(e) => write!(f, "invalid tls options: {e}"),
+            TlsFailure::Connect(e) => write!(f, "connection failed: {e}"),
+            TlsFailure::TimedOut => write!(f, "tls handshake timed out"),
+            TlsFailure::Expired => write!(f, "certificate expired"),
+            TlsFailure::NotYetValid => write!(f, "certificate is not valid yet"),
+            TlsFailure::HostnameMismatch => write!(f, "certificate is not valid for the hostname"),
+            TlsFailure::UntrustedChain => write!(f, "certificate chain is not trusted"),
+            TlsFailure::InvalidCertificate(e) => write!(f, "certificate is invalid: {e}"),
+            TlsFailure::Handshake(e) => write!(f, "tls handshake failed: {e}"),
+        }
+    }
+}
+
+/// Splits `host[:port]` (optionally prefixed with `tls://` or `https://`) into its parts,
+/// port defaults to 443
+pub fn target(url: &str) -> Option<(&str, u16)> {
+    let url = url.trim();
+    let addr = url
+        .strip_prefix("tls://")
+        .or_else(|| url.strip_prefix("https://"))
+        .unwrap_or(url);
+    let addr = addr.split('/').next()?;
+
+    let (host, port) = match addr.rsplit_once(':') {
+        Some((host, port)) if !host.ends_with(':') => (host, port.parse::<u16>().ok()?),
+        _ => (addr, 443),
+    };
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+
+    if host.is_empty() || host.contains(' ') || port == 0 {
+        return None;
+    }
+
+    Some((host, port))
+}
+
+pub fn validate(url: &str, options: Result<TlsOptions, serde_json::Error>) -> Result<(), String> {
+    target(url).ok_or_else(|| TlsFailure::InvalidAddress.to_string())?;
+    options
+        .map(|_| ())
+        .map_err(|e| TlsFailure::InvalidOptions(e.to_string()).to_string())
+}
+
+pub async fn check(server: &Server, timeout: Duration) -> ProbeReport {
+    let started = Instant::now();
+    let options = TlsOptions::from_server(server);
+    let (result, chain) = match &options {
+        Ok(_) => handshake(&server.url, timeout).await,
+        Err(e) => (Err(TlsFailure::InvalidOptions(e.to_string())), Vec::new()),
+    };
+    let latency = started.elapsed();
+
+    let certificates: Vec<CertificateInfo> = chain.iter().filter_map(parse_certificate).collect();
+    let body = certificates
+        .iter()
+        .map(|c| {
+            format!(
+                "{} (issuer: {}, expires: {})",
+                c.subject, c.issuer, c.expires_at
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .into_bytes();
+    let leaf = certificates.into_iter().next();
+
+    let status = match (result, &leaf) {
+        (Err(failure), _) => {
+            ServerStatus::unreachable(failure.to_string(), body, failure.status_code(), latency)
+        }
+        (Ok(()), None) => {
+            let failure = TlsFailure::InvalidCertificate("unable to parse".to_string());
+            ServerStatus::unreachable(failure.to_string(), body, failure.status_code(), latency)
+        }
+        (Ok(()), Some(cert)) => {
+            let warn_days = options
+                .map(|o| o.warn_days)
+                .unwrap_or_else(|_| default_warn_days());
+            let days_left = (cert.expires_at - OffsetDateTime::now_utc()).whole_days();
+
+            // the reason stays the same every day, formats count down with `cert_days_left`
+            if days_left < warn_days {
+                ServerStatus::warning(
+                    format!("certificate expires on {}", cert.expires_at.date()),
+                    body,
+                    StatusCode::OK,
+                    latency,
+                )
+            } else {
+                ServerStatus::online(StatusCode::OK, body, latency)
+            }
+        }
+    };
+
+    ProbeReport {
+        status,
+        certificate: leaf,
+    }
+}
+
+fn parse_certificate(der: &CertificateDer<'static>) -> Option<CertificateInfo> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der.as_ref()).ok()?;
+
+    Some(CertificateInfo {
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        expires_at: cert.validity().not_after.to_datetime(),
+    })
+}
+
+/// Performs a verified handshake, returns the peer chain (leaf first) even if verification failed
+async fn handshake(
+    url: &str,
+    timeout: Duration,
+) -> (Result<(), TlsFailure>, Vec<CertificateDer<'static>>) {
+    let Some((host, port)) = target(url) else {
+        return (Err(TlsFailure::InvalidAddress), Vec::new());
+    };
+    let Ok(server_name) = ServerName::try_from(host.to_string()) else {
+        return (Err(TlsFailure::InvalidAddress), Vec::new());
+    };
+
+    let verifier = match CapturingVerifier::new() {
+        Ok(v) => Arc::new(v),
+        Err(e) => return (Err(TlsFailure::Handshake(e.to_string())), Vec::new()),
+    };
+    let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map(|builder| {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(verifier.clone())
+                .with_no_client_auth()
+        });
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => return (Err(TlsFailure::Handshake(e.to_string())), Vec::new()),
+    };
+
+    let attempt = async {
+        let stream = TcpStream::connect((host, port))
+            .await
+            .map_err(|e| TlsFailure::Connect(e.to_string()))?;
+        TlsConnector::from(Arc::new(config))
+            .connect(server_name, stream)
+            .await
+            .map_err(TlsFailure::from_handshake)?;
+        Ok(())
+    };
+
+    let result = tokio::time::timeout(timeout, attempt)
+        .await
+        .unwrap_or(Err(TlsFailure::TimedOut));
+
+    (result, verifier.take_chain())
+}
+
+/// Verifies certificates against webpki roots, keeping the presented chain around,
+/// so expiry and issuer can be reported for untrusted chains as well
+#[derive(Debug)]
+struct CapturingVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    chain: Mutex<Vec<CertificateDer<'static>>>,
+}
+
+impl CapturingVerifier {
+    fn new() -> Result<Self, rustls::client::VerifierBuilderError> {
+        let roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let inner = WebPkiServerVerifier::builder_with_provider(
+            Arc::new(roots),
+            Arc::new(ring::default_provider()),
+        )
+        .build()?;
+
+        Ok(Self {
+            inner,
+            chain: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn take_chain(&self) -> Vec<CertificateDer<'static>> {
+        std::mem::take(&mut *self.chain.lock().unwrap())
+    }
+}
+
+impl ServerCertVerifier for CapturingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        *self.chain.lock().unwrap() = std::iter::once(end_entity)
+            .chain(intermediates)
+            .map(|c| c.clone().into_owned())
+            .collect();
+
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tls_target() {
+        assert_eq!(target("example.com"), Some(("example.com", 443)));
+        assert_eq!(target("example.com:8443"), Some(("example.com", 8443)));
+        assert_eq!(
+            target("https://example.com/health"),
+            Some(("example.com", 443))
+        );
+        assert_eq!(target("tls://[::1]:993"), Some(("::1", 993)));
+        assert_eq!(target("example.com:https"), None);
+        assert_eq!(target(""), None);
+    }
+
+    #[test]
+    fn test_tls_failure_from_handshake() {
+        let err = |e: CertificateError| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                rustls::Error::InvalidCertificate(e),
+            )
+        };
+
+        assert_eq!(
+            TlsFailure::from_handshake(err(CertificateError::Expired)),
+            TlsFailure::Expired
+        );
+        assert_eq!(
+            TlsFailure::from_handshake(err(CertificateError::NotValidForName)),
+            TlsFailure::HostnameMismatch
+        );
+        assert_eq!(
+            TlsFailure::from_handshake(err(CertificateError::UnknownIssuer)),
+            TlsFailure::UntrustedChain
+        );
+    }
+}
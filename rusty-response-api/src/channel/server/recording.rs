@@ -0,0 +1,44 @@
+//! Selection of checks recorded to the `check_result` table besides state transitions.
+
+use crate::model::{RecordMode, Server};
+
+/// Counts checks of a single server, so `sampled` servers record every `record_sample`th one
+#[derive(Debug, Default)]
+pub struct Recorder {
+    checks: u64,
+}
+
+impl Recorder {
+    /// Whether a check that didn't change the state is recorded
+    pub fn record(&mut self, server: &Server) -> bool {
+        match server.record_mode {
+            RecordMode::Transitions => false,
+            RecordMode::Every => true,
+            RecordMode::Sampled => {
+                let sample = server.record_sample.max(1) as u64;
+                self.checks += 1;
+                (self.checks - 1).is_multiple_of(sample)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_recorder_modes() {
+        let mut server = Server::mock(1, "http://localhost/");
+        let mut recorder = Recorder::default();
+        assert!(!(0..5).any(|_| recorder.record(&server)));
+
+        server.record_mode = RecordMode::Every;
+        assert!((0..5).all(|_| recorder.record(&server)));
+
+        server.record_mode = RecordMode::Sampled;
+        server.record_sample = 3;
+        let recorded: Vec<bool> = (0..7).map(|_| recorder.record(&server)).collect();
+        assert_eq!(recorded, vec![true, false, false, true, false, false, true]);
+    }
+}
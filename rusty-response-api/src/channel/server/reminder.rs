@@ -0,0 +1,110 @@
+//! Reminders of servers staying down, sent by notifiers with `remind_every` set.
+//!
+//! Reminders are timed from the start of the incident and counted per notifier in the database,
+//! reminders missed while the backend was down are sent as a single one. Acknowledged incidents
+//! aren't reminded of.
+
+use std::time::Duration;
+
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, trace};
+
+use crate::{
+    ModelManager,
+    channel::server::{maintenance::UnderMaintenance, utils},
+    model::{Ctx, Incident, IncidentBmc, NotifierBmc, ServerBmc, ServerLogBmc, ServerLogLine},
+    notify::NotifyManager,
+};
+
+/// How often incidents are checked for due reminders, reminders have a minute resolution
+const TICK: Duration = Duration::from_secs(30);
+
+/// Sends the reminders of the incident that are due
+async fn remind(
+    mm: &ModelManager,
+    ctx: &Ctx,
+    notify_manager: &NotifyManager,
+    under_maintenance: &UnderMaintenance,
+    incident: Incident,
+) -> eyre::Result<()> {
+    let notifiers: Vec<_> = NotifierBmc::notifiers_for(mm, ctx, incident.server_id)
+        .await?
+        .into_iter()
+        .filter(|notifier| notifier.active && notifier.remind_every.is_some())
+        .collect();
+    if notifiers.is_empty() {
+        return Ok(());
+    }
+
+    let Some(server) = ServerBmc::get_by_id(mm, ctx, incident.server_id).await? else {
+        return Ok(());
+    };
+    let Some(log) = ServerLogBmc::first_of_incident(mm, ctx, incident.id).await? else {
+        return Ok(());
+    };
+
+    let silenced = utils::is_silenced(&server, &*under_maintenance.read().await);
+    let elapsed = OffsetDateTime::now_utc() - incident.started_at.assume_utc();
+    let sent = IncidentBmc::reminders(mm, ctx, incident.id).await?;
+    let line = ServerLogLine::new(server.redacted(), log).with_incident(Some(incident.clone()));
+
+    for notifier in notifiers {
+        let due = notifier.reminders_due(elapsed);
+        if due <= sent.get(&notifier.id).copied().unwrap_or(0) {
+            continue;
+        }
+
+        // silenced reminders are counted as sent, so they don't all go out once it's over
+        if !silenced {
+            debug!(
+                "Reminding of incident {} of server {} through notifier {}, reminder {due}",
+                incident.id, incident.server_id, notifier.id
+            );
+            let line = line.clone().with_reminder(due);
+            if let Err(e) = notify_manager.notify_by_nid(notifier.id, &line).await {
+                error!(
+                    "Unable to remind of incident {} through notifier {}: {e}",
+                    incident.id, notifier.id
+                );
+                continue;
+            }
+        }
+        IncidentBmc::reminded(mm, ctx, incident.id, notifier.id, due).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn payload(
+    mm: ModelManager,
+    notify_manager: NotifyManager,
+    under_maintenance: UnderMaintenance,
+    cancellation_token: CancellationToken,
+) {
+    let ctx = Ctx::admin_root();
+
+    loop {
+        match IncidentBmc::unacknowledged(&mm, &ctx).await {
+            Ok(incidents) => {
+                for incident in incidents {
+                    let id = incident.id;
+                    let result =
+                        remind(&mm, &ctx, &notify_manager, &under_maintenance, incident).await;
+                    if let Err(e) = result {
+                        error!("Unable to remind of incident {id}: {e}");
+                    }
+                }
+            }
+            Err(e) => error!("Unable to fetch unacknowledged incidents: {e}"),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(TICK) => {},
+            _ = cancellation_token.cancelled() => {
+                trace!("Reminder payload shut down successfully");
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Background pruning of rows past their retention, see [`crate::config::Retention`].
+//!
+//! Every batch is a statement of its own followed by a pause, so the monitoring backend never
+//! waits for more than a single batch to be deleted.
+
+use std::{path::Path, time::Duration};
+
+use serde_json::Value;
+use time::OffsetDateTime;
+use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, trace, warn};
+
+use crate::{
+    ModelManager, Settings,
+    config::Retention,
+    model::{Ctx, Expiry, RetentionBmc},
+};
+
+/// Appends `rows` to `<table>.jsonl` in `dir`, one JSON object per line
+async fn archive(dir: &Path, table: &str, rows: &[Value]) -> eyre::Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+
+    let mut lines = Vec::new();
+    for row in rows {
+        serde_json::to_writer(&mut lines, row)?;
+        lines.push(b'\n');
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(format!("{table}.jsonl")))
+        .await?;
+    file.write_all(&lines).await?;
+    file.sync_data().await?;
+
+    Ok(())
+}
+
+/// Deletes every expired row of `expiry`, returns how many were deleted
+async fn prune(
+    mm: &ModelManager,
+    ctx: &Ctx,
+    retention: &Retention,
+    expiry: &Expiry,
+    cancellation_token: &CancellationToken,
+) -> eyre::Result<u64> {
+    let mut deleted = 0;
+
+    while let Some(end) =
+        RetentionBmc::batch_end(mm, ctx, expiry, retention.batch_size().max(1)).await?
+    {
+        if let Some(dir) = retention.archive_dir() {
+            let rows = RetentionBmc::rows(mm, ctx, expiry, end).await?;
+            // rows are only deleted once they're safely archived
+            archive(Path::new(dir), expiry.table.table(), &rows).await?;
+        }
+        deleted += RetentionBmc::delete(mm, ctx, expiry, end).await?;
+
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(retention.batch_pause())) => {},
+            _ = cancellation_token.cancelled() => break,
+        }
+    }
+
+    Ok(deleted)
+}
+
+async fn run(
+    mm: &ModelManager,
+    ctx: &Ctx,
+    retention: &Retention,
+    cancellation_token: &CancellationToken,
+) -> eyre::Result<()> {
+    let expiries = RetentionBmc::expiries(mm, ctx, retention, OffsetDateTime::now_utc()).await?;
+
+    if retention.dry_run() {
+        for expired in RetentionBmc::report(mm, ctx, &expiries).await? {
+            info!(
+                "Retention dry run: {} expired rows of `{}` would be pruned",
+                expired.rows,
+                expired.table.table()
+            );
+        }
+        return Ok(());
+    }
+
+    for expiry in &expiries {
+        if cancellation_token.is_cancelled() {
+            return Ok(());
+        }
+
+        match prune(mm, ctx, retention, expiry, cancellation_token).await {
+            Ok(0) => {}
+            Ok(deleted) => debug!(
+                "Pruned {deleted} rows of `{}` older than {}",
+                expiry.table.table(),
+                expiry.cutoff
+            ),
+            Err(e) => error!("Unable to prune `{}`: {e}", expiry.table.table()),
+        }
+    }
+
+    RetentionBmc::incremental_vacuum(mm, ctx, retention.vacuum_pages()).await?;
+
+    Ok(())
+}
+
+/// Databases created before incremental vacuum was enabled keep it disabled until converted,
+/// which is only done when [`Retention::convert_vacuum`] opts in
+async fn prepare_vacuum(mm: &ModelManager, ctx: &Ctx, retention: &Retention) -> eyre::Result<()> {
+    if RetentionBmc::is_incremental(mm, ctx).await? {
+        return Ok(());
+    }
+
+    if !retention.convert_vacuum() {
+        warn!(
+            "Incremental vacuum is disabled, pruned space is not released until \
+            `retention.convert_vacuum` is set once"
+        );
+        return Ok(());
+    }
+
+    info!("Switching the database to incremental vacuum, it's locked until done");
+    RetentionBmc::enable_incremental_vacuum(mm, ctx).await?;
+    info!("Incremental vacuum enabled, `retention.convert_vacuum` can be unset");
+
+    Ok(())
+}
+
+pub async fn payload(mm: ModelManager, cancellation_token: CancellationToken) {
+    let ctx = Ctx::admin_root();
+    let retention = Settings::global().retention();
+    let every = Duration::from_secs(retention.interval().max(1));
+
+    if let Err(e) = prepare_vacuum(&mm, &ctx, retention).await {
+        error!("Unable to switch the database to incremental vacuum: {e}");
+    }
+
+    loop {
+        if let Err(e) = run(&mm, &ctx, retention, &cancellation_token).await {
+            error!("Unable to apply retention: {e}");
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(every) => {},
+            _ = cancellation_token.cancelled() => {
+                trace!("Retention payload shut down successfully");
+                break;
+            }
+        }
+    }
+}
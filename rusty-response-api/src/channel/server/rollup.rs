@@ -0,0 +1,162 @@
+//! Aggregation of every probe's outcome into the `rollup` tables.
+//!
+//! Outcomes are collected in memory per server and hour and periodically merged into the
+//! database, an hour whose merge failed is kept and retried on the next flush. The latest latency
+//! of every server is flushed along, so probes don't write `server.last_latency_ms` one by one.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use time::{OffsetDateTime, PrimitiveDateTime};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, trace};
+
+use crate::{
+    ModelManager,
+    model::{Ctx, RollupBmc, RollupDelta, RollupPeriod, ServerBmc},
+};
+
+/// Cheap to clone handle probes report their outcomes to
+#[derive(Debug, Clone, Default)]
+pub struct Aggregator {
+    pending: Arc<Mutex<HashMap<(i64, PrimitiveDateTime), RollupDelta>>>,
+    /// server_id → latency in millis of its latest answered probe
+    latest: Arc<Mutex<HashMap<i64, i64>>>,
+}
+
+impl Aggregator {
+    /// Counts a probe of the server, `latency` is `None` if it got no answer
+    pub fn record(&self, server_id: i64, failed: bool, latency: Option<Duration>) {
+        self.record_at(server_id, OffsetDateTime::now_utc(), failed, latency);
+    }
+
+    fn record_at(
+        &self,
+        server_id: i64,
+        at: OffsetDateTime,
+        failed: bool,
+        latency: Option<Duration>,
+    ) {
+        let hour = RollupPeriod::Hour.start_of(at);
+        let mut pending = self.pending.lock().expect("rollup lock poisoned");
+        let delta = pending.entry((server_id, hour)).or_default();
+
+        delta.count += 1;
+        delta.failures += failed as i64;
+        if let Some(latency) = latency {
+            delta.latency.record(latency.as_millis() as u64);
+        }
+        drop(pending);
+
+        if let Some(latency) = latency {
+            let mut latest = self.latest.lock().expect("rollup lock poisoned");
+            latest.insert(server_id, latency.as_millis() as i64);
+        }
+    }
+
+    fn restore(&self, key: (i64, PrimitiveDateTime), delta: RollupDelta) {
+        let mut pending = self.pending.lock().expect("rollup lock poisoned");
+        let current = pending.entry(key).or_default();
+
+        current.count += delta.count;
+        current.failures += delta.failures;
+        current.latency.merge(&delta.latency);
+    }
+
+    /// Merges the collected outcomes into the database
+    pub async fn flush(&self, mm: &ModelManager, ctx: &Ctx) {
+        let pending = std::mem::take(&mut *self.pending.lock().expect("rollup lock poisoned"));
+
+        for ((server_id, hour), delta) in pending {
+            // a failed merge rolled back, so adding the outcomes again doesn't count them twice
+            if let Err(e) = RollupBmc::merge_hour(mm, ctx, server_id, hour, delta.clone()).await {
+                error!("Unable to roll up probes of server {server_id}: {e}");
+                self.restore((server_id, hour), delta);
+            }
+        }
+
+        let latest = std::mem::take(&mut *self.latest.lock().expect("rollup lock poisoned"));
+        if latest.is_empty() {
+            return;
+        }
+        let latencies: Vec<(i64, i64)> = latest.into_iter().collect();
+        if let Err(e) = ServerBmc::update_latencies(mm, ctx, &latencies).await {
+            error!(
+                "Unable to update latencies of {} servers: {e}",
+                latencies.len()
+            );
+            // probes since the take are newer, they win
+            let mut latest = self.latest.lock().expect("rollup lock poisoned");
+            for (server_id, latency_ms) in latencies {
+                latest.entry(server_id).or_insert(latency_ms);
+            }
+        }
+    }
+}
+
+pub async fn payload(
+    mm: ModelManager,
+    aggregator: Aggregator,
+    every: Duration,
+    cancellation_token: CancellationToken,
+) {
+    let ctx = Ctx::admin_root();
+    let mut ticks = tokio::time::interval(every);
+    ticks.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => aggregator.flush(&mm, &ctx).await,
+            _ = cancellation_token.cancelled() => {
+                // outcomes collected since the last tick would be lost otherwise
+                aggregator.flush(&mm, &ctx).await;
+                trace!("Rollup payload shut down successfully");
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn test_aggregator_buckets_by_hour() {
+        let aggregator = Aggregator::default();
+        let latency = Some(Duration::from_millis(120));
+        aggregator.record_at(1, datetime!(2026-10-17 13:05 UTC), false, latency);
+        aggregator.record_at(1, datetime!(2026-10-17 13:55 UTC), true, None);
+        aggregator.record_at(1, datetime!(2026-10-17 14:00 UTC), false, latency);
+        aggregator.record_at(2, datetime!(2026-10-17 13:30 UTC), false, latency);
+
+        let pending = aggregator.pending.lock().unwrap();
+        assert_eq!(pending.len(), 3);
+
+        let hour = &pending[&(1, datetime!(2026-10-17 13:00))];
+        assert_eq!((hour.count, hour.failures), (2, 1));
+        assert_eq!(hour.latency.count, 1);
+        assert_eq!(hour.latency.max, Some(120));
+
+        // only the latest answered probe is kept for `last_latency_ms`
+        let latest = aggregator.latest.lock().unwrap();
+        assert_eq!(latest.get(&1), Some(&120));
+        assert_eq!(latest.get(&2), Some(&120));
+    }
+
+    #[test]
+    fn test_aggregator_latest_latency() {
+        let aggregator = Aggregator::default();
+        let at = datetime!(2026-10-17 13:05 UTC);
+        aggregator.record_at(1, at, false, Some(Duration::from_millis(120)));
+        aggregator.record_at(1, at, false, Some(Duration::from_millis(80)));
+        aggregator.record_at(1, at, true, None);
+
+        assert_eq!(aggregator.latest.lock().unwrap().get(&1), Some(&80));
+    }
+}
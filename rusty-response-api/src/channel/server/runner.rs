@@ -1,210 +1,342 @@
-use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use axum::http;
-use reqwest::StatusCode;
+use time::OffsetDateTime;
 use tokio::{
     sync::{Mutex, mpsc},
     task::JoinHandle,
 };
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, trace, warn};
+use tracing::{debug, trace, warn};
 
 use crate::{
-    ModelManager,
+    ModelManager, Settings,
     channel::{
-        UnboundedMPSCController,
+        BoundedMPSCController, BoundedSender, Coalesce,
         server::{
-            ServerStatus,
+            delivery::Delivery,
+            escalation,
+            flapping::FlapDetector,
+            handler::HandlerState,
+            heartbeat::{self, HeartbeatPing},
+            lanes::Lanes,
+            maintenance::{self, UnderMaintenance},
+            probe,
+            reminder,
+            retention,
+            rollup::{self, Aggregator},
+            scheduler::{self, SchedulerHandle, SchedulerOptions},
+            suppressed,
             types::{ControlMessage, ServerMessage},
+            utils::{LastSeen, state_from_code},
         },
     },
-    model::{Ctx, Server, ServerBmc},
+    model::{
+        Ctx, Server, ServerBmc, ServerKind, ServerState, UserAction, UserActionLogBmc, UserRole,
+    },
     notify::NotifyManager,
 };
 
-async fn payload(
-    server: Server,
-    client: Arc<reqwest::Client>,
-    sender: mpsc::UnboundedSender<ServerMessage>,
-    cancellation_token: tokio_util::sync::CancellationToken,
-    last_seen_status: bool,
-) {
-    let interval = Duration::from_secs(server.interval as u64);
-    let timeout = Duration::from_secs(server.timeout as u64);
-
-    let mut local_status = last_seen_status;
-
-    loop {
-        let result = async {
-            let response = client.get(&server.url).timeout(timeout).send().await;
-
-            let response = match response {
-                Ok(resp) => resp,
-                Err(e) => {
-                    if local_status {
-                        let message = ServerMessage::error(e.into(), server.clone());
-                        sender.send(message).ok();
-                        local_status = false;
-                    }
-                    return;
-                }
-            };
-
-            let status = response.status();
-            let mut body = Vec::new();
-            match response.bytes().await {
-                Ok(bytes) => body.extend_from_slice(&bytes),
-                Err(e) => {
-                    error!(
-                        "Error during reading response body from server: {}. Error: {:#?}",
-                        server.id, e
-                    );
-                    body.extend_from_slice(b"Unable to read body");
-                }
-            }
+/// State the payload starts from, servers that were never checked are assumed to be online
+fn initial_state(server: &Server) -> ServerState {
+    match (server.last_seen_state, server.last_seen_status_code) {
+        (Some(state), _) => state,
+        (None, Some(code)) => state_from_code(server, code),
+        (None, None) => ServerState::Online,
+    }
+}
 
-            if !status.is_success() {
-                if local_status {
-                    let message = ServerMessage::unreachable(
-                        ServerStatus::Unreachable {
-                            reason: "TODO".to_string(),
-                            body,
-                            status_code: status,
-                        },
-                        server.clone(),
-                    );
-                    sender.send(message).ok();
-                    local_status = false;
-                }
+type Heartbeats = BTreeMap<i64, mpsc::UnboundedSender<HeartbeatPing>>;
+
+/// How often flapping servers are checked for stabilisation
+const FLAP_SWEEP: Duration = Duration::from_secs(30);
+
+/// Resumes the server once `until` passes, an already passed moment resumes it right away
+fn schedule_resume(
+    mm: ModelManager,
+    server_id: i64,
+    until: OffsetDateTime,
+    control_tx: mpsc::UnboundedSender<ControlMessage>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let delay = Duration::try_from(until - OffsetDateTime::now_utc()).unwrap_or_default();
+        tokio::time::sleep(delay).await;
+
+        let admin_ctx = Ctx::admin_root();
+        let server = match ServerBmc::get_by_id(&mm, &admin_ctx, server_id).await {
+            Ok(Some(server)) => server,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Unable to fetch paused server {server_id}: {e}");
                 return;
             }
+        };
+        if let Err(e) = ServerBmc::resume(&mm, &admin_ctx, server_id).await {
+            warn!("Unable to resume server {server_id}: {e}");
+            return;
+        }
+        debug!("Pause of server {server_id} expired, resuming");
 
-            if !local_status {
-                let message = ServerMessage::ServerStateChanged {
-                    status: ServerStatus::Online {
-                        status_code: status,
-                        body,
-                    },
-                    server: server.clone(),
-                };
-                sender.send(message).ok();
-                local_status = true;
-            }
+        let owner_ctx = Ctx::new(server.user_id, UserRole::User);
+        let action = UserAction::server_auto_resume(server_id);
+        if let Err(e) = UserActionLogBmc::log(&mm, &owner_ctx, action).await {
+            warn!("Unable to log automatic resume of server {server_id}: {e}");
+        }
+        control_tx
+            .send(ControlMessage::ResumeServer(server_id))
+            .ok();
+    })
+}
 
-            // DEBUG: REMOVE THIS BLOCK IN PROD
-            //            sender
-            //                .send(ServerMessage::ServerStateChanged {
-            //                    status: ServerStatus::Unreachable {
-            //                        reason: format!("Sending from: {}", server.id),
-            //                        status_code: http::StatusCode::OK,
-            //                        body: b"TEST".to_vec(),
-            //                    },
-            //                    server: server.clone(),
-            //                })
-            //                .ok();
-        };
+/// Starts monitoring `server`, `heartbeat` servers get their own task with a ping channel
+/// registered in `heartbeats`, the rest is probed by the scheduler
+fn start_monitoring(
+    server: Server,
+    scheduler: &SchedulerHandle,
+    sender: BoundedSender<ServerMessage>,
+    cancellation_token: CancellationToken,
+    last_seen_state: ServerState,
+    heartbeats: &mut Heartbeats,
+    staggered: bool,
+) -> Option<JoinHandle<()>> {
+    if server.kind == ServerKind::Heartbeat {
+        let (ping_tx, ping_rx) = mpsc::unbounded_channel();
+        heartbeats.insert(server.id, ping_tx);
+        return Some(tokio::spawn(heartbeat::payload(
+            server,
+            ping_rx,
+            sender,
+            scheduler.aggregator().clone(),
+            cancellation_token,
+            last_seen_state,
+        )));
+    }
 
-        result.await;
+    heartbeats.remove(&server.id);
+    match staggered {
+        true => scheduler.upsert_staggered(server, last_seen_state),
+        false => scheduler.upsert(server, last_seen_state),
+    }
+    None
+}
 
-        tokio::select! {
-            _ = tokio::time::sleep(interval) => {},
-            _ = cancellation_token.cancelled() => {
-                trace!("Payload for server {} shut down successfully", server.id);
-                break;
-            }
-        }
+/// Stops monitoring of the server, whichever way it's monitored
+async fn stop_monitoring(
+    server_id: i64,
+    scheduler: &SchedulerHandle,
+    handles: &Mutex<BTreeMap<i64, JoinHandle<()>>>,
+    heartbeats: &Mutex<Heartbeats>,
+) {
+    scheduler.remove(server_id);
+    heartbeats.lock().await.remove(&server_id);
+    if let Some(handle) = handles.lock().await.remove(&server_id) {
+        handle.abort();
     }
 }
 
 pub async fn setup_monitoring_future(
     mm: ModelManager,
+    control_tx: mpsc::UnboundedSender<ControlMessage>,
     control_rx: mpsc::UnboundedReceiver<ControlMessage>,
+    mpsc: BoundedMPSCController<ServerMessage>,
     notify_manager: NotifyManager,
+    under_maintenance: UnderMaintenance,
     cancellation_token: CancellationToken,
 ) {
-    let mpsc = UnboundedMPSCController::<ServerMessage>::new();
     let admin_ctx = Ctx::admin_root();
 
-    let reqwest_client = Arc::new(
-        reqwest::ClientBuilder::new()
-            .user_agent(format!(
-                "{} - {}",
-                env!("CARGO_PKG_NAME"),
-                env!("CARGO_PKG_VERSION")
-            ))
-            .build()
-            .expect("unable to create reqwest client"),
-    );
+    let reqwest_client = Arc::new(probe::client());
 
     let servers = ServerBmc::all(&mm, &admin_ctx)
         .await
         .expect("unable to fetch servers from database");
 
-    let statuses = Arc::new(Mutex::new(BTreeMap::<i64, (i64, String)>::new()));
+    let statuses = Arc::new(Mutex::new(BTreeMap::<i64, LastSeen>::new()));
+    let mut flaps = FlapDetector::default();
     let handles = Arc::new(Mutex::new(BTreeMap::<i64, JoinHandle<()>>::new()));
+    let heartbeats = Arc::new(Mutex::new(Heartbeats::new()));
+    // pending automatic resumes of paused servers
+    let pauses = Arc::new(Mutex::new(BTreeMap::<i64, JoinHandle<()>>::new()));
 
     {
         let mut status_lock = statuses.lock().await;
         for server in &servers {
-            status_lock.insert(
-                server.id,
-                (
-                    server.last_seen_status_code.unwrap_or(0),
-                    server.last_seen_reason.clone().unwrap_or_default(),
-                ),
-            );
+            status_lock.insert(server.id, LastSeen::from_server(server));
+            if server.flapping {
+                flaps.resume(server, Instant::now());
+            }
         }
     }
+    let aggregator = Aggregator::default();
+    let reqwest_arc = Arc::clone(&reqwest_client);
+    let (scheduler, scheduler_future) = scheduler::scheduler(
+        SchedulerOptions::from_settings(),
+        move |server: Arc<Server>, options: Arc<probe::ProbeOptions>| {
+            let client = Arc::clone(&reqwest_arc);
+            async move { probe::check(&server, &options, &client).await }
+        },
+        mpsc.get_sender(),
+        aggregator.clone(),
+        cancellation_token.child_token(),
+    );
+    let scheduler_task = tokio::spawn(scheduler_future);
+
     {
         let mut handles_lock = handles.lock().await;
+        let mut heartbeats_lock = heartbeats.lock().await;
+        let mut pauses_lock = pauses.lock().await;
         for server in servers {
+            if !server.is_turned_on {
+                trace!("Skipping paused server: {}", server.id);
+                if let Some(until) = server.paused_until {
+                    let handle = schedule_resume(mm.clone(), server.id, until, control_tx.clone());
+                    pauses_lock.insert(server.id, handle);
+                }
+                continue;
+            }
+
             trace!("Setting up: {:#?}", server);
-            let reqwest_arc = Arc::clone(&reqwest_client);
             let tx = mpsc.get_sender();
             let child_token = cancellation_token.child_token();
             let server_id = server.id;
-            let last_seen_status =
-                http::StatusCode::from_u16(server.last_seen_status_code.unwrap_or(200) as u16)
-                    .unwrap()
-                    .is_success();
-
-            let handle = tokio::spawn(async move {
-                payload(server, reqwest_arc, tx, child_token, last_seen_status).await
-            });
-            handles_lock.insert(server_id, handle);
+            let last_seen_state = initial_state(&server);
+
+            let handle = start_monitoring(
+                server,
+                &scheduler,
+                tx,
+                child_token,
+                last_seen_state,
+                &mut heartbeats_lock,
+                true,
+            );
+            if let Some(handle) = handle {
+                handles_lock.insert(server_id, handle);
+            }
         }
     }
     let server_tx = mpsc.get_sender();
 
-    // FUTURE 1: Handle ServerMessage
-    let statuses_clone = Arc::clone(&statuses);
+    // notifications of every payload go through the same lanes
+    let lanes = Settings::global().monitoring().handlers();
+    let (delivery, mut delivery_tasks) = Delivery::spawn(notify_manager.clone(), lanes);
+    let delivery = Arc::new(delivery);
+
+    // FUTURE 0: Track maintenance windows
+    let maintenance = tokio::spawn(maintenance::payload(
+        mm.clone(),
+        Arc::clone(&delivery),
+        Arc::clone(&under_maintenance),
+        cancellation_token.child_token(),
+    ));
+
+    // FUTURE 0: Escalate unacknowledged incidents
+    let escalations = tokio::spawn(escalation::payload(
+        mm.clone(),
+        notify_manager.clone(),
+        Arc::clone(&under_maintenance),
+        cancellation_token.child_token(),
+    ));
+
+    // FUTURE 0: Remind of servers staying down
+    let reminders = tokio::spawn(reminder::payload(
+        mm.clone(),
+        notify_manager.clone(),
+        Arc::clone(&under_maintenance),
+        cancellation_token.child_token(),
+    ));
+
+    // FUTURE 0: Report alerts suppressed by rate limits
+    let summary_interval =
+        Duration::from_secs(Settings::global().rate_limit().summary_interval().max(1));
+    let summaries = tokio::spawn(suppressed::payload(
+        notify_manager.clone(),
+        summary_interval,
+        cancellation_token.child_token(),
+    ));
+
+    // FUTURE 0: Flush probe outcomes to the rollup tables
+    let rollup_flush = Duration::from_secs(Settings::global().monitoring().rollup_flush().max(1));
+    let rollups = tokio::spawn(rollup::payload(
+        mm.clone(),
+        aggregator.clone(),
+        rollup_flush,
+        cancellation_token.child_token(),
+    ));
+
+    // FUTURE 0: Prune rows past their retention
+    let retention = tokio::spawn(retention::payload(
+        mm.clone(),
+        cancellation_token.child_token(),
+    ));
+
+    // FUTURE 1: Handle ServerMessage, every server on its lane
+    let handler_state = Arc::new(HandlerState {
+        statuses: Arc::clone(&statuses),
+        under_maintenance,
+        flaps: Mutex::new(flaps),
+        dependency_failures: Mutex::default(),
+    });
+    let (handlers, mut handler_tasks) = Lanes::spawn(lanes, {
+        let mm = mm.clone();
+        let delivery = Arc::clone(&delivery);
+        let handler_state = Arc::clone(&handler_state);
+        move |msg| {
+            let mm = mm.clone();
+            let delivery = Arc::clone(&delivery);
+            let handler_state = Arc::clone(&handler_state);
+            async move {
+                let ctx = Ctx::admin_root();
+                super::handle_server_response(msg, &mm, &ctx, &delivery, &handler_state).await;
+            }
+        }
+    });
     let mm_clone = mm.clone();
     let admin_ctx_clone = admin_ctx.clone();
     let mut server_rx = mpsc.take_receiver();
     let updates = tokio::spawn(async move {
-        while let Some(msg) = server_rx.recv().await {
-            super::handle_server_response(
-                msg,
-                &mm_clone,
-                &admin_ctx_clone,
-                &notify_manager,
-                Arc::clone(&statuses_clone),
-            )
-            .await;
+        let mut sweep = tokio::time::interval(FLAP_SWEEP);
+        loop {
+            tokio::select! {
+                msg = server_rx.recv() => {
+                    let Some(msg) = msg else {
+                        break;
+                    };
+                    handlers.send(msg.key(), msg).await;
+                }
+                _ = sweep.tick() => {
+                    super::handle_stabilised(
+                        &mm_clone,
+                        &admin_ctx_clone,
+                        &delivery,
+                        &handler_state,
+                    )
+                    .await;
+                }
+            }
         }
+
+        // let queued results and notifications drain before shutting down
+        drop(handlers);
+        while handler_tasks.join_next().await.is_some() {}
+        drop(delivery);
+        while delivery_tasks.join_next().await.is_some() {}
     });
 
     // FUTURE 2: Handle ControlMessage
     let statuses_clone = Arc::clone(&statuses);
     let handles_clone = Arc::clone(&handles);
-    let reqwest_client_clone = Arc::clone(&reqwest_client);
+    let heartbeats_clone = Arc::clone(&heartbeats);
+    let pauses_clone = Arc::clone(&pauses);
     let control = tokio::spawn(async move {
         let mut control_rx = control_rx;
         while let Some(ctrl_msg) = control_rx.recv().await {
             debug!("Received control message: {:?}", ctrl_msg);
             match ctrl_msg {
                 ControlMessage::AddServer(server) => {
-                    let reqwest_arc = Arc::clone(&reqwest_client_clone);
                     let tx = server_tx.clone();
                     let child_token = cancellation_token.child_token();
 
@@ -212,26 +344,35 @@ pub async fn setup_monitoring_future(
                         let server_id = server.id;
                         {
                             let mut status_lock = statuses_clone.lock().await;
-                            status_lock.insert(
-                                server.id,
-                                (
-                                    server.last_seen_status_code.unwrap_or(0),
-                                    server.last_seen_reason.clone().unwrap_or_default(),
-                                ),
-                            );
+                            status_lock.insert(server.id, LastSeen::from_server(&server));
                         }
 
-                        let handle = tokio::spawn(async move {
-                            payload(server, reqwest_arc, tx, child_token, true).await;
-                        });
+                        if !server.is_turned_on {
+                            continue;
+                        }
 
-                        {
+                        let handle = start_monitoring(
+                            server,
+                            &scheduler,
+                            tx,
+                            child_token,
+                            ServerState::Online,
+                            &mut *heartbeats_clone.lock().await,
+                            false,
+                        );
+
+                        if let Some(handle) = handle {
                             let mut handles_lock = handles_clone.lock().await;
                             handles_lock.insert(server_id, handle);
                         }
                     }
                 }
                 ControlMessage::RemoveServer(server_id) => {
+                    stop_monitoring(server_id, &scheduler, &handles_clone, &heartbeats_clone).await;
+                    if let Some(pause) = pauses_clone.lock().await.remove(&server_id) {
+                        pause.abort();
+                    }
+
                     {
                         let mut status_lock = statuses_clone.lock().await;
                         if status_lock.remove_entry(&server_id).is_none() {
@@ -241,21 +382,8 @@ pub async fn setup_monitoring_future(
                             );
                         }
                     }
-
-                    {
-                        let mut handles_lock = handles_clone.lock().await;
-                        let handle = handles_lock.remove_entry(&server_id);
-                        if let Some((_, handle)) = handle {
-                            handle.abort();
-                        } else {
-                            warn!(
-                                "Tried to remove non-existing entry from handles tree: {server_id}"
-                            );
-                        }
-                    }
                 }
                 ControlMessage::ModifyServer(server) => {
-                    let reqwest_arc = Arc::clone(&reqwest_client_clone);
                     let tx = server_tx.clone();
                     let child_token = cancellation_token.child_token();
                     let server_id = server.id;
@@ -272,44 +400,102 @@ pub async fn setup_monitoring_future(
                         );
                     }
 
-                    let removed_handle = {
-                        let mut handles_lock = handles_clone.lock().await;
-                        handles_lock.remove_entry(&server_id)
-                    };
-
-                    if let Some((_id, handle)) = removed_handle {
-                        handle.abort();
-                    } else {
-                        warn!(
-                            "Tried to modify non-existing server entry from handles tree: {}",
-                            server_id
-                        );
-                    }
+                    stop_monitoring(server_id, &scheduler, &handles_clone, &heartbeats_clone).await;
 
                     {
                         let mut status_lock = statuses_clone.lock().await;
-                        status_lock.insert(
-                            server.id,
-                            (
-                                server.last_seen_status_code.unwrap_or(0),
-                                server.last_seen_reason.clone().unwrap_or_default(),
-                            ),
-                        );
+                        status_lock.insert(server.id, LastSeen::from_server(&server));
                     }
 
-                    let last_seen_status =
-                        StatusCode::from_u16(server.last_seen_status_code.unwrap_or(200) as u16)
-                            .unwrap()
-                            .is_success();
-                    let handle = tokio::spawn(async move {
-                        payload(server, reqwest_arc, tx, child_token, last_seen_status).await;
-                    });
+                    if let Some(pause) = pauses_clone.lock().await.remove(&server_id) {
+                        pause.abort();
+                    }
 
-                    {
+                    if !server.is_turned_on {
+                        if let Some(until) = server.paused_until {
+                            let handle =
+                                schedule_resume(mm.clone(), server_id, until, control_tx.clone());
+                            pauses_clone.lock().await.insert(server_id, handle);
+                        }
+                        continue;
+                    }
+
+                    let last_seen_state = initial_state(&server);
+                    let handle = start_monitoring(
+                        server,
+                        &scheduler,
+                        tx,
+                        child_token,
+                        last_seen_state,
+                        &mut *heartbeats_clone.lock().await,
+                        false,
+                    );
+
+                    if let Some(handle) = handle {
                         let mut handles_lock = handles_clone.lock().await;
                         handles_lock.insert(server_id, handle);
                     }
                 }
+                ControlMessage::PauseServer { server_id, until } => {
+                    stop_monitoring(server_id, &scheduler, &handles_clone, &heartbeats_clone).await;
+
+                    let mut pauses_lock = pauses_clone.lock().await;
+                    if let Some(pause) = pauses_lock.remove(&server_id) {
+                        pause.abort();
+                    }
+                    if let Some(until) = until {
+                        let handle =
+                            schedule_resume(mm.clone(), server_id, until, control_tx.clone());
+                        pauses_lock.insert(server_id, handle);
+                    }
+                }
+                ControlMessage::ResumeServer(server_id) => {
+                    if let Some(pause) = pauses_clone.lock().await.remove(&server_id) {
+                        pause.abort();
+                    }
+
+                    let server = match ServerBmc::get_by_id(&mm, &admin_ctx, server_id).await {
+                        Ok(Some(server)) if server.is_turned_on => server,
+                        Ok(_) => {
+                            warn!("Tried to resume missing or paused server: {server_id}");
+                            continue;
+                        }
+                        Err(e) => {
+                            warn!("Unable to fetch resumed server {server_id}: {e}");
+                            continue;
+                        }
+                    };
+
+                    stop_monitoring(server_id, &scheduler, &handles_clone, &heartbeats_clone).await;
+
+                    {
+                        let mut status_lock = statuses_clone.lock().await;
+                        status_lock.insert(server_id, LastSeen::from_server(&server));
+                    }
+
+                    let last_seen_state = initial_state(&server);
+                    let handle = start_monitoring(
+                        server,
+                        &scheduler,
+                        server_tx.clone(),
+                        cancellation_token.child_token(),
+                        last_seen_state,
+                        &mut *heartbeats_clone.lock().await,
+                        false,
+                    );
+                    if let Some(handle) = handle {
+                        handles_clone.lock().await.insert(server_id, handle);
+                    }
+                }
+                ControlMessage::Heartbeat { server_id, ping } => {
+                    let heartbeats_lock = heartbeats_clone.lock().await;
+                    match heartbeats_lock.get(&server_id) {
+                        Some(ping_tx) => {
+                            ping_tx.send(ping).ok();
+                        }
+                        None => warn!("Received heartbeat for unmonitored server: {server_id}"),
+                    }
+                }
                 ControlMessage::Shutdown => {
                     debug!("Shutdown requested. Shutting down...");
 
@@ -323,12 +509,26 @@ pub async fn setup_monitoring_future(
                         handles_lock.clear();
                     }
 
+                    for (_, pause) in std::mem::take(&mut *pauses_clone.lock().await) {
+                        pause.abort();
+                    }
+
                     break;
                 }
             }
         }
     });
-    let _ = tokio::join!(updates, control);
+    let _ = tokio::join!(
+        scheduler_task,
+        maintenance,
+        escalations,
+        reminders,
+        summaries,
+        rollups,
+        retention,
+        updates,
+        control
+    );
 
     debug!("Monitoring backend has been shut down.");
 }
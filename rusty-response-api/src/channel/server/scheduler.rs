@@ -0,0 +1,706 @@
+//! Single scheduler dispatching probes of every server.
+//!
+//! Due probes are kept in a priority queue and handed to at most `workers` concurrently running
+//! probes, at most `per_host` of them against the same host. Probes follow a fixed grid of
+//! `interval`, so a slow probe doesn't push the following ones back, and first probes after
+//! startup are spread over `max_jitter` so they don't all fire at once.
+//!
+//! Results are handed to the channel by a delivery task of the server, off the scheduler loop. A
+//! server whose last result is still waiting for room in the channel skips its probes, so a full
+//! channel only holds up the servers with something to report.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    future::Future,
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore, mpsc},
+    task::JoinHandle,
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, trace};
+
+use crate::{
+    channel::{
+        BoundedSender,
+        server::{
+            ProbeReport,
+            probe::ProbeOptions,
+            recording::Recorder,
+            rollup::Aggregator,
+            tracker::{StateTracker, Transition},
+            types::ServerMessage,
+        },
+    },
+    model::{Server, ServerState},
+};
+
+type ProbeResult = Result<ProbeReport, super::Error>;
+
+#[derive(Debug, Clone)]
+pub struct SchedulerOptions {
+    pub workers: usize,
+    pub per_host: usize,
+    pub max_jitter: Duration,
+}
+
+impl SchedulerOptions {
+    pub fn from_settings() -> Self {
+        let monitoring = crate::Settings::global().monitoring();
+        Self {
+            workers: monitoring.workers().max(1),
+            per_host: monitoring.per_host().max(1),
+            max_jitter: Duration::from_secs(monitoring.max_jitter()),
+        }
+    }
+}
+
+enum Command {
+    Upsert {
+        server: Box<Server>,
+        state: ServerState,
+        staggered: bool,
+    },
+    Remove(i64),
+}
+
+/// Cheap to clone handle used to add, replace and remove monitored servers
+#[derive(Clone)]
+pub struct SchedulerHandle {
+    tx: mpsc::UnboundedSender<Command>,
+    aggregator: Aggregator,
+}
+
+impl SchedulerHandle {
+    /// Starts monitoring the server right away, replacing its previous configuration
+    pub fn upsert(&self, server: Server, state: ServerState) {
+        self.tx
+            .send(Command::Upsert {
+                server: Box::new(server),
+                state,
+                staggered: false,
+            })
+            .ok();
+    }
+
+    /// Starts monitoring the server after a jitter, used for servers loaded on startup
+    pub fn upsert_staggered(&self, server: Server, state: ServerState) {
+        self.tx
+            .send(Command::Upsert {
+                server: Box::new(server),
+                state,
+                staggered: true,
+            })
+            .ok();
+    }
+
+    pub fn remove(&self, server_id: i64) {
+        self.tx.send(Command::Remove(server_id)).ok();
+    }
+
+    /// Aggregator probes report their outcomes to, shared with monitors run outside the scheduler
+    pub fn aggregator(&self) -> &Aggregator {
+        &self.aggregator
+    }
+}
+
+struct Monitor {
+    server: Arc<Server>,
+    /// Parsed once per configuration, probes reuse them
+    options: Arc<ProbeOptions>,
+    host: Arc<str>,
+    tracker: StateTracker,
+    recorder: Recorder,
+    /// Bumped on every upsert, so results of a replaced configuration are dropped
+    generation: u64,
+    /// Slot of the grid the last probe was dispatched for
+    slot: Instant,
+    /// Task handing the last result to the channel
+    delivery: Option<JoinHandle<()>>,
+}
+
+impl Monitor {
+    /// Whether the last result is still waiting for room in the channel
+    fn is_delivering(&self) -> bool {
+        self.delivery
+            .as_ref()
+            .is_some_and(|delivery| !delivery.is_finished())
+    }
+
+    /// Hands the message to the channel after the previous one of the server
+    fn deliver(&mut self, sender: &BoundedSender<ServerMessage>, message: ServerMessage) {
+        let previous = self.delivery.take();
+        let sender = sender.clone();
+        self.delivery = Some(tokio::spawn(async move {
+            if let Some(previous) = previous {
+                previous.await.ok();
+            }
+            sender.send(message).await.ok();
+        }));
+    }
+}
+
+struct Outcome {
+    server_id: i64,
+    generation: u64,
+    host: Arc<str>,
+    result: ProbeResult,
+}
+
+/// Host probes of the server are limited by
+fn host_of(server: &Server) -> Arc<str> {
+    let host = match reqwest::Url::parse(&server.url) {
+        Ok(url) if url.host_str().is_some() => url.host_str().unwrap_or_default().to_string(),
+        // `tcp` and `tls` targets are `host:port` pairs
+        _ => match server.url.rsplit_once(':') {
+            Some((host, port)) if port.parse::<u16>().is_ok() => host.to_string(),
+            _ => server.url.clone(),
+        },
+    };
+    host.to_lowercase().into()
+}
+
+fn interval_of(server: &Server) -> Duration {
+    Duration::from_secs(server.interval.max(1) as u64)
+}
+
+/// Stable offset of the first probe, so servers keep their phase across restarts
+fn jitter(server_id: i64, interval: Duration, max_jitter: Duration) -> Duration {
+    let span = interval.min(max_jitter).as_millis() as u64;
+    if span == 0 {
+        return Duration::ZERO;
+    }
+    let hash = (server_id as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    Duration::from_millis((hash >> 16) % span)
+}
+
+/// First slot of the grid `slot + k * interval` after `now`
+fn next_slot(slot: Instant, interval: Duration, now: Instant) -> Instant {
+    let next = slot + interval;
+    if next > now {
+        return next;
+    }
+    // probes fell behind, skip the missed slots instead of bursting through them
+    let missed = (now - next).as_nanos() / interval.as_nanos() + 1;
+    next + interval * missed as u32
+}
+
+struct Scheduler<P> {
+    options: SchedulerOptions,
+    probe: Arc<P>,
+    sender: BoundedSender<ServerMessage>,
+    aggregator: Aggregator,
+    monitors: HashMap<i64, Monitor>,
+    queue: BinaryHeap<Reverse<(Instant, i64, u64)>>,
+    workers: Arc<Semaphore>,
+    hosts: HashMap<Arc<str>, Arc<Semaphore>>,
+    /// Servers waiting for a busy host, in the order they became due
+    waiting: HashMap<Arc<str>, VecDeque<(i64, u64)>>,
+    outcome_tx: mpsc::UnboundedSender<Outcome>,
+    next_generation: u64,
+}
+
+impl<P, F> Scheduler<P>
+where
+    P: Fn(Arc<Server>, Arc<ProbeOptions>) -> F + Send + Sync + 'static,
+    F: Future<Output = ProbeResult> + Send + 'static,
+{
+    fn upsert(&mut self, server: Server, state: ServerState, staggered: bool) {
+        let now = Instant::now();
+        let interval = interval_of(&server);
+        let delay = match staggered {
+            true => jitter(server.id, interval, self.options.max_jitter),
+            false => Duration::ZERO,
+        };
+
+        self.next_generation += 1;
+        // a pending result of the previous configuration still goes out first
+        let delivery = self
+            .monitors
+            .get_mut(&server.id)
+            .and_then(|monitor| monitor.delivery.take());
+        let monitor = Monitor {
+            host: host_of(&server),
+            options: Arc::new(ProbeOptions::from_server(&server)),
+            tracker: StateTracker::from_server(&server, state),
+            recorder: Recorder::default(),
+            server: Arc::new(server),
+            generation: self.next_generation,
+            slot: now + delay,
+            delivery,
+        };
+
+        self.queue.push(Reverse((
+            monitor.slot,
+            monitor.server.id,
+            monitor.generation,
+        )));
+        self.monitors.insert(monitor.server.id, monitor);
+    }
+
+    /// Dispatches every due probe, returns `false` when all workers are busy
+    fn dispatch_due(&mut self) -> bool {
+        let now = Instant::now();
+
+        while let Some(Reverse((due, server_id, generation))) = self.queue.peek().copied() {
+            if due > now {
+                break;
+            }
+
+            let Some(monitor) = self.monitors.get_mut(&server_id) else {
+                self.queue.pop();
+                continue;
+            };
+            if monitor.generation != generation {
+                self.queue.pop();
+                continue;
+            }
+            if monitor.is_delivering() {
+                trace!("Server {server_id} skips a probe, its last result isn't delivered yet");
+                self.queue.pop();
+                monitor.slot = next_slot(monitor.slot, interval_of(&monitor.server), now);
+                self.queue
+                    .push(Reverse((monitor.slot, server_id, generation)));
+                continue;
+            }
+
+            let Ok(worker) = Arc::clone(&self.workers).try_acquire_owned() else {
+                return false;
+            };
+            self.queue.pop();
+
+            let host = Arc::clone(&monitor.host);
+            let host_permit = self
+                .hosts
+                .entry(Arc::clone(&host))
+                .or_insert_with(|| Arc::new(Semaphore::new(self.options.per_host)))
+                .clone()
+                .try_acquire_owned();
+            let Ok(host_permit) = host_permit else {
+                trace!("Host {host} is busy, server {server_id} waits");
+                self.waiting
+                    .entry(host)
+                    .or_default()
+                    .push_back((server_id, generation));
+                continue;
+            };
+
+            self.spawn_probe(server_id, worker, host_permit);
+        }
+
+        true
+    }
+
+    fn spawn_probe(
+        &mut self,
+        server_id: i64,
+        worker: OwnedSemaphorePermit,
+        host_permit: OwnedSemaphorePermit,
+    ) {
+        let Some(monitor) = self.monitors.get(&server_id) else {
+            return;
+        };
+
+        let probe = Arc::clone(&self.probe);
+        let server = Arc::clone(&monitor.server);
+        let options = Arc::clone(&monitor.options);
+        let generation = monitor.generation;
+        let host = Arc::clone(&monitor.host);
+        let outcome_tx = self.outcome_tx.clone();
+
+        tokio::spawn(async move {
+            let result = probe(server, options).await;
+            drop((worker, host_permit));
+            outcome_tx
+                .send(Outcome {
+                    server_id,
+                    generation,
+                    host,
+                    result,
+                })
+                .ok();
+        });
+    }
+
+    fn complete(&mut self, outcome: Outcome) {
+        // the host has a free slot now, hand it to the longest waiting server
+        if let Some(waiting) = self.waiting.get_mut(&outcome.host) {
+            while let Some((server_id, generation)) = waiting.pop_front() {
+                // replaced or removed servers have already left the line
+                if self
+                    .monitors
+                    .get(&server_id)
+                    .is_some_and(|monitor| monitor.generation == generation)
+                {
+                    self.queue
+                        .push(Reverse((Instant::now(), server_id, generation)));
+                    break;
+                }
+            }
+            if waiting.is_empty() {
+                self.waiting.remove(&outcome.host);
+            }
+        }
+
+        let Some(monitor) = self.monitors.get_mut(&outcome.server_id) else {
+            return;
+        };
+        if monitor.generation != outcome.generation {
+            return;
+        }
+
+        let server = Arc::clone(&monitor.server);
+        let state = match &outcome.result {
+            Ok(report) => report.status.state(),
+            Err(_) => ServerState::Unreachable,
+        };
+        let latency = outcome.result.as_ref().ok().map(|r| r.status.latency());
+        self.aggregator
+            .record(server.id, state == ServerState::Unreachable, latency);
+
+        let transition = monitor.tracker.observe(state);
+        if let Transition::Suspected { state, count } = transition {
+            debug!(
+                "Server {} suspected {:?} ({} in a row)",
+                server.id, state, count
+            );
+        }
+
+        if let Transition::Confirmed(_) = transition {
+            let message = match outcome.result {
+                Ok(report) => ServerMessage::report(report, Server::clone(&server)),
+                Err(e) => ServerMessage::error(e, Server::clone(&server)),
+            };
+            monitor.deliver(&self.sender, message);
+        } else if monitor.recorder.record(&server) {
+            let report = outcome.result.as_ref().ok();
+            let message = ServerMessage::Checked {
+                server_id: server.id,
+                state,
+                status_code: report.map(|report| report.status.status_code()),
+                latency: report.map(|report| report.status.latency()),
+            };
+            monitor.deliver(&self.sender, message);
+        }
+
+        let now = Instant::now();
+        let due = match (monitor.tracker.is_suspected(), server.recheck_interval) {
+            (true, Some(secs)) => now + Duration::from_secs(secs.max(1) as u64),
+            _ => {
+                monitor.slot = next_slot(monitor.slot, interval_of(&server), now);
+                monitor.slot
+            }
+        };
+        self.queue
+            .push(Reverse((due, outcome.server_id, monitor.generation)));
+    }
+}
+
+/// Creates the scheduler, `probe` performs a single check of the server
+pub fn scheduler<P, F>(
+    options: SchedulerOptions,
+    probe: P,
+    sender: BoundedSender<ServerMessage>,
+    aggregator: Aggregator,
+    cancellation_token: CancellationToken,
+) -> (SchedulerHandle, impl Future<Output = ()>)
+where
+    P: Fn(Arc<Server>, Arc<ProbeOptions>) -> F + Send + Sync + 'static,
+    F: Future<Output = ProbeResult> + Send + 'static,
+{
+    let (tx, mut commands) = mpsc::unbounded_channel();
+    let (outcome_tx, mut outcomes) = mpsc::unbounded_channel();
+    let handle = SchedulerHandle {
+        tx,
+        aggregator: aggregator.clone(),
+    };
+
+    let mut scheduler = Scheduler {
+        workers: Arc::new(Semaphore::new(options.workers)),
+        options,
+        probe: Arc::new(probe),
+        sender,
+        aggregator,
+        monitors: HashMap::new(),
+        queue: BinaryHeap::new(),
+        hosts: HashMap::new(),
+        waiting: HashMap::new(),
+        outcome_tx,
+        next_generation: 0,
+    };
+
+    let future = async move {
+        loop {
+            let idle = scheduler.dispatch_due();
+            let next_due = scheduler.queue.peek().map(|Reverse((due, ..))| *due);
+
+            tokio::select! {
+                // with every worker busy only a finished probe can unblock the queue
+                _ = tokio::time::sleep_until(next_due.unwrap_or_else(Instant::now)), if idle && next_due.is_some() => {}
+                Some(command) = commands.recv() => match command {
+                    Command::Upsert { server, state, staggered } => scheduler.upsert(*server, state, staggered),
+                    Command::Remove(server_id) => {
+                        scheduler.monitors.remove(&server_id);
+                    }
+                },
+                Some(outcome) = outcomes.recv() => scheduler.complete(outcome),
+                _ = cancellation_token.cancelled() => {
+                    trace!("Scheduler shut down successfully");
+                    break;
+                }
+            }
+        }
+    };
+
+    (handle, future)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::channel::BoundedMPSCController;
+    use crate::channel::server::ServerStatus;
+
+    fn online() -> ProbeResult {
+        Ok(ServerStatus::online(StatusCode::OK, vec![], Duration::ZERO).into())
+    }
+
+    #[test]
+    fn test_next_slot() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(10);
+
+        // a slow probe doesn't shift the grid
+        assert_eq!(
+            next_slot(start, interval, start + Duration::from_secs(3)),
+            start + interval
+        );
+        // missed slots are skipped
+        assert_eq!(
+            next_slot(start, interval, start + Duration::from_secs(25)),
+            start + Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn test_host_of() {
+        let mut server = Server::mock(1, "https://Example.com:8443/health");
+        assert_eq!(&*host_of(&server), "example.com");
+
+        server.url = "10.0.0.1:5432".to_string();
+        assert_eq!(&*host_of(&server), "10.0.0.1");
+
+        server.url = "example.com".to_string();
+        assert_eq!(&*host_of(&server), "example.com");
+    }
+
+    #[test]
+    fn test_jitter_bounds() {
+        let interval = Duration::from_secs(30);
+        let max_jitter = Duration::from_secs(10);
+
+        for id in 0..1000 {
+            assert!(jitter(id, interval, max_jitter) < max_jitter);
+        }
+        assert_eq!(jitter(1, interval, Duration::ZERO), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_per_host_limit() {
+        let options = SchedulerOptions {
+            workers: 16,
+            per_host: 1,
+            max_jitter: Duration::ZERO,
+        };
+        let running = Arc::new(Mutex::new((0, 0)));
+        let probe = {
+            let running = Arc::clone(&running);
+            move |_server: Arc<Server>, _options| {
+                let running = Arc::clone(&running);
+                async move {
+                    {
+                        let mut running = running.lock().unwrap();
+                        running.0 += 1;
+                        running.1 = running.1.max(running.0);
+                    }
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    running.lock().unwrap().0 -= 1;
+                    online()
+                }
+            }
+        };
+
+        let channel = BoundedMPSCController::new(64);
+        let tx = channel.get_sender();
+        let _rx = channel.take_receiver();
+        let token = CancellationToken::new();
+        let (handle, future) = scheduler(options, probe, tx, Aggregator::default(), token.clone());
+        tokio::spawn(future);
+
+        for id in 0..4 {
+            handle.upsert(Server::mock(id, "http://same-host/"), ServerState::Online);
+        }
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        token.cancel();
+
+        assert_eq!(running.lock().unwrap().1, 1);
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_reports_transition() {
+        let options = SchedulerOptions {
+            workers: 4,
+            per_host: 4,
+            max_jitter: Duration::ZERO,
+        };
+        let probe = |_server: Arc<Server>, _options| async {
+            Ok(ServerStatus::unreachable(
+                "down",
+                vec![],
+                StatusCode::SERVICE_UNAVAILABLE,
+                Duration::ZERO,
+            )
+            .into())
+        };
+
+        let channel = BoundedMPSCController::new(64);
+        let tx = channel.get_sender();
+        let mut rx = channel.take_receiver();
+        let token = CancellationToken::new();
+        let (handle, future) = scheduler(options, probe, tx, Aggregator::default(), token.clone());
+        tokio::spawn(future);
+
+        handle.upsert(Server::mock(1, "http://localhost/"), ServerState::Online);
+        let message = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap();
+        assert!(matches!(
+            message,
+            Some(ServerMessage::ServerStateChanged { status, .. }) if status.state() == ServerState::Unreachable
+        ));
+
+        // removed servers aren't probed anymore
+        handle.remove(1);
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_full_channel() {
+        let options = SchedulerOptions {
+            workers: 4,
+            per_host: 4,
+            max_jitter: Duration::ZERO,
+        };
+        let probed = Arc::new(Mutex::new(Vec::new()));
+        let probe = {
+            let probed = Arc::clone(&probed);
+            move |server: Arc<Server>, _options| {
+                probed.lock().unwrap().push(server.id);
+                async {
+                    Ok(ServerStatus::unreachable(
+                        "down",
+                        vec![],
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        Duration::ZERO,
+                    )
+                    .into())
+                }
+            }
+        };
+
+        // nobody drains the channel, the second state change can't be delivered
+        let channel = BoundedMPSCController::new(1);
+        let tx = channel.get_sender();
+        let _rx = channel.take_receiver();
+        let token = CancellationToken::new();
+        let (handle, future) = scheduler(options, probe, tx, Aggregator::default(), token.clone());
+        tokio::spawn(future);
+
+        handle.upsert(Server::mock(1, "http://one/"), ServerState::Online);
+        handle.upsert(Server::mock(2, "http://two/"), ServerState::Online);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // the stuck delivery doesn't hold up other servers
+        handle.upsert(Server::mock(3, "http://three/"), ServerState::Online);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        token.cancel();
+
+        assert!(probed.lock().unwrap().contains(&3));
+    }
+
+    /// Run with `cargo test --release --lib -- --ignored bench_scheduler --nocapture`, CI runs it
+    /// on demand with the `Scheduler benchmark` workflow
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "benchmark"]
+    async fn bench_scheduler_10k_monitors() {
+        const MONITORS: i64 = 10_000;
+        const RUN: Duration = Duration::from_secs(10);
+
+        let options = SchedulerOptions {
+            workers: 512,
+            per_host: 8,
+            max_jitter: Duration::from_secs(1),
+        };
+        let probes = Arc::new(Mutex::new(HashMap::<i64, Vec<Instant>>::new()));
+        let probe = {
+            let probes = Arc::clone(&probes);
+            move |server: Arc<Server>, _options| {
+                probes
+                    .lock()
+                    .unwrap()
+                    .entry(server.id)
+                    .or_default()
+                    .push(Instant::now());
+                async {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    online()
+                }
+            }
+        };
+
+        let channel = BoundedMPSCController::new(64);
+        let tx = channel.get_sender();
+        let mut rx = channel.take_receiver();
+        tokio::spawn(async move { while rx.recv().await.is_some() {} });
+        let token = CancellationToken::new();
+        let (handle, future) = scheduler(options, probe, tx, Aggregator::default(), token.clone());
+        tokio::spawn(future);
+
+        for id in 0..MONITORS {
+            let mut server = Server::mock(id, &format!("http://host-{}/", id % 1000));
+            server.interval = 1;
+            handle.upsert_staggered(server, ServerState::Online);
+        }
+        tokio::time::sleep(RUN).await;
+        token.cancel();
+
+        let probes = probes.lock().unwrap();
+        let gaps: Vec<f64> = probes
+            .values()
+            .flat_map(|at| at.windows(2).map(|w| (w[1] - w[0]).as_secs_f64()))
+            .collect();
+        let total: usize = probes.values().map(Vec::len).sum();
+        let mean = gaps.iter().sum::<f64>() / gaps.len() as f64;
+        let worst = gaps
+            .iter()
+            .fold(0f64, |worst, gap| worst.max((gap - 1.0).abs()));
+
+        println!(
+            "{MONITORS} monitors, {total} probes in {}s ({:.0}/s), mean interval {mean:.4}s, worst deviation {:.1}ms",
+            RUN.as_secs(),
+            total as f64 / RUN.as_secs_f64(),
+            worst * 1000.0
+        );
+        assert_eq!(probes.len(), MONITORS as usize);
+        assert!((mean - 1.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,26 @@
+//! Summaries of alerts held back by the notifiers' rate limits and duplicate suppression.
+
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+use tracing::trace;
+
+use crate::notify::NotifyManager;
+
+pub async fn payload(
+    notify_manager: NotifyManager,
+    interval: Duration,
+    cancellation_token: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {},
+            _ = cancellation_token.cancelled() => {
+                trace!("Suppressed alerts payload shut down successfully");
+                break;
+            }
+        }
+
+        notify_manager.flush_suppressed().await;
+    }
+}
@@ -0,0 +1,237 @@
+//! Debouncing of probe results, so a single dropped packet doesn't reach notifiers.
+//!
+//! A new state is confirmed only after it was observed `failure_threshold` times in a row
+//! (when it's more severe than the confirmed one) or `recovery_threshold` times in a row
+//! (when it's less severe). Until then the server is only suspected.
+
+use crate::model::{Server, ServerState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// Observed state matches the confirmed one
+    Unchanged,
+    /// Observed state differs, but not enough times in a row yet
+    Suspected { state: ServerState, count: u32 },
+    /// Observed state is confirmed and has to be reported
+    Confirmed(ServerState),
+}
+
+#[derive(Debug, Clone)]
+pub struct StateTracker {
+    confirmed: ServerState,
+    candidate: Option<(ServerState, u32)>,
+    failure_threshold: u32,
+    recovery_threshold: u32,
+}
+
+fn severity(state: ServerState) -> u8 {
+    match state {
+        ServerState::Online | ServerState::Maintenance | ServerState::Flapping => 0,
+        ServerState::Warning => 1,
+        ServerState::Degraded => 2,
+        ServerState::Unreachable => 3,
+    }
+}
+
+impl StateTracker {
+    pub fn new(confirmed: ServerState, failure_threshold: u32, recovery_threshold: u32) -> Self {
+        Self {
+            confirmed,
+            candidate: None,
+            failure_threshold: failure_threshold.max(1),
+            recovery_threshold: recovery_threshold.max(1),
+        }
+    }
+
+    pub fn from_server(server: &Server, confirmed: ServerState) -> Self {
+        Self::new(
+            confirmed,
+            server.failure_threshold as u32,
+            server.recovery_threshold as u32,
+        )
+    }
+
+    /// Whether a state change is pending confirmation, used to switch to the fast re-check interval
+    pub fn is_suspected(&self) -> bool {
+        self.candidate.is_some()
+    }
+
+    pub fn observe(&mut self, state: ServerState) -> Transition {
+        if state == self.confirmed {
+            self.candidate = None;
+            return Transition::Unchanged;
+        }
+
+        let count = match self.candidate {
+            Some((candidate, count)) if candidate == state => count + 1,
+            _ => 1,
+        };
+
+        let threshold = if severity(state) > severity(self.confirmed) {
+            self.failure_threshold
+        } else {
+            self.recovery_threshold
+        };
+
+        if count >= threshold {
+            self.confirmed = state;
+            self.candidate = None;
+            return Transition::Confirmed(state);
+        }
+
+        self.candidate = Some((state, count));
+        Transition::Suspected { state, count }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicU16, Ordering},
+        },
+        time::Duration,
+    };
+
+    use axum::{Router, extract::State, http::StatusCode, routing::get};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::channel::server::probe;
+
+    /// Local HTTP server answering with whatever status is stored in the returned handle
+    async fn mock_http() -> (String, Arc<AtomicU16>) {
+        let status = Arc::new(AtomicU16::new(200));
+        let app = Router::new()
+            .route(
+                "/",
+                get(|State(status): State<Arc<AtomicU16>>| async move {
+                    StatusCode::from_u16(status.load(Ordering::SeqCst)).unwrap()
+                }),
+            )
+            .with_state(Arc::clone(&status));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{addr}/"), status)
+    }
+
+    async fn observe(tracker: &mut StateTracker, server: &Server) -> Transition {
+        let client = reqwest::Client::new();
+        let options = probe::ProbeOptions::from_server(server);
+        let state = match probe::check(server, &options, &client).await {
+            Ok(report) => report.status.state(),
+            Err(_) => ServerState::Unreachable,
+        };
+        tracker.observe(state)
+    }
+
+    #[test]
+    fn test_tracker_single_threshold() {
+        let mut tracker = StateTracker::new(ServerState::Online, 1, 1);
+
+        assert_eq!(
+            tracker.observe(ServerState::Unreachable),
+            Transition::Confirmed(ServerState::Unreachable)
+        );
+        assert_eq!(
+            tracker.observe(ServerState::Unreachable),
+            Transition::Unchanged
+        );
+        assert_eq!(
+            tracker.observe(ServerState::Online),
+            Transition::Confirmed(ServerState::Online)
+        );
+    }
+
+    #[test]
+    fn test_tracker_candidate_reset() {
+        let mut tracker = StateTracker::new(ServerState::Online, 3, 1);
+
+        tracker.observe(ServerState::Unreachable);
+        tracker.observe(ServerState::Unreachable);
+        assert!(tracker.is_suspected());
+
+        // a single good probe in between restarts the count
+        assert_eq!(tracker.observe(ServerState::Online), Transition::Unchanged);
+        assert!(!tracker.is_suspected());
+        assert_eq!(
+            tracker.observe(ServerState::Unreachable),
+            Transition::Suspected {
+                state: ServerState::Unreachable,
+                count: 1
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tracker_confirms_outage() {
+        let (url, status) = mock_http().await;
+        let mut server = Server::mock(1, &url);
+        server.timeout = 2;
+        let mut tracker = StateTracker::new(ServerState::Online, 3, 2);
+
+        assert_eq!(observe(&mut tracker, &server).await, Transition::Unchanged);
+
+        // dropped request
+        status.store(503, Ordering::SeqCst);
+        assert!(matches!(
+            observe(&mut tracker, &server).await,
+            Transition::Suspected { count: 1, .. }
+        ));
+        status.store(200, Ordering::SeqCst);
+        assert_eq!(observe(&mut tracker, &server).await, Transition::Unchanged);
+
+        // real outage
+        status.store(503, Ordering::SeqCst);
+        assert!(matches!(
+            observe(&mut tracker, &server).await,
+            Transition::Suspected { count: 1, .. }
+        ));
+        assert!(matches!(
+            observe(&mut tracker, &server).await,
+            Transition::Suspected { count: 2, .. }
+        ));
+        assert_eq!(
+            observe(&mut tracker, &server).await,
+            Transition::Confirmed(ServerState::Unreachable)
+        );
+
+        // recovery
+        status.store(200, Ordering::SeqCst);
+        assert!(matches!(
+            observe(&mut tracker, &server).await,
+            Transition::Suspected { count: 1, .. }
+        ));
+        assert_eq!(
+            observe(&mut tracker, &server).await,
+            Transition::Confirmed(ServerState::Online)
+        );
+        assert_eq!(tracker.confirmed, ServerState::Online);
+    }
+
+    #[tokio::test]
+    async fn test_tracker_refused_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let mut server = Server::mock(1, &format!("http://{addr}/"));
+        server.timeout = 1;
+        let mut tracker = StateTracker::new(ServerState::Online, 2, 1);
+
+        assert!(matches!(
+            observe(&mut tracker, &server).await,
+            Transition::Suspected { count: 1, .. }
+        ));
+        assert_eq!(
+            tokio::time::timeout(Duration::from_secs(5), observe(&mut tracker, &server))
+                .await
+                .unwrap(),
+            Transition::Confirmed(ServerState::Unreachable)
+        );
+    }
+}
@@ -1,6 +1,13 @@
+use std::time::Duration;
+
 use axum::http;
+use time::OffsetDateTime;
 
-use crate::model::Server;
+use super::heartbeat::HeartbeatPing;
+use crate::{
+    channel::Coalesce,
+    model::{Server, ServerState},
+};
 
 #[derive(Debug)]
 pub enum ServerStatus {
@@ -8,10 +15,24 @@ pub enum ServerStatus {
         reason: String,
         body: Vec<u8>,
         status_code: http::StatusCode,
+        latency: Duration,
+    },
+    Warning {
+        reason: String,
+        body: Vec<u8>,
+        status_code: http::StatusCode,
+        latency: Duration,
+    },
+    Degraded {
+        reason: String,
+        body: Vec<u8>,
+        status_code: http::StatusCode,
+        latency: Duration,
     },
     Online {
         status_code: http::StatusCode,
         body: Vec<u8>,
+        latency: Duration,
     },
 }
 
@@ -20,11 +41,124 @@ impl ServerStatus {
         reason: S,
         body: Vec<u8>,
         status_code: http::StatusCode,
+        latency: Duration,
     ) -> Self {
         Self::Unreachable {
             reason: reason.into(),
             body,
             status_code,
+            latency,
+        }
+    }
+
+    pub fn warning<S: Into<String>>(
+        reason: S,
+        body: Vec<u8>,
+        status_code: http::StatusCode,
+        latency: Duration,
+    ) -> Self {
+        Self::Warning {
+            reason: reason.into(),
+            body,
+            status_code,
+            latency,
+        }
+    }
+
+    pub fn degraded<S: Into<String>>(
+        reason: S,
+        body: Vec<u8>,
+        status_code: http::StatusCode,
+        latency: Duration,
+    ) -> Self {
+        Self::Degraded {
+            reason: reason.into(),
+            body,
+            status_code,
+            latency,
+        }
+    }
+
+    pub fn online(status_code: http::StatusCode, body: Vec<u8>, latency: Duration) -> Self {
+        Self::Online {
+            status_code,
+            body,
+            latency,
+        }
+    }
+
+    pub fn state(&self) -> ServerState {
+        match self {
+            Self::Unreachable { .. } => ServerState::Unreachable,
+            Self::Warning { .. } => ServerState::Warning,
+            Self::Degraded { .. } => ServerState::Degraded,
+            Self::Online { .. } => ServerState::Online,
+        }
+    }
+
+    pub fn status_code(&self) -> http::StatusCode {
+        match self {
+            Self::Unreachable { status_code, .. }
+            | Self::Warning { status_code, .. }
+            | Self::Degraded { status_code, .. }
+            | Self::Online { status_code, .. } => *status_code,
+        }
+    }
+
+    pub fn latency(&self) -> Duration {
+        match self {
+            Self::Unreachable { latency, .. }
+            | Self::Warning { latency, .. }
+            | Self::Degraded { latency, .. }
+            | Self::Online { latency, .. } => *latency,
+        }
+    }
+
+    /// Turns a slow online status into a degraded one, `threshold` of `None` disables the check
+    pub fn with_latency_threshold(self, threshold: Option<Duration>) -> Self {
+        match (self, threshold) {
+            (
+                Self::Online {
+                    status_code,
+                    body,
+                    latency,
+                },
+                Some(threshold),
+            ) if latency > threshold => Self::degraded(
+                format!(
+                    "latency {}ms exceeds {}ms",
+                    latency.as_millis(),
+                    threshold.as_millis()
+                ),
+                body,
+                status_code,
+                latency,
+            ),
+            (status, _) => status,
+        }
+    }
+}
+
+/// Peer certificate details captured by `tls` checks
+#[derive(Debug, Clone)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub expires_at: OffsetDateTime,
+}
+
+/// Outcome of a single probe
+#[derive(Debug)]
+pub struct ProbeReport {
+    pub status: ServerStatus,
+    pub certificate: Option<CertificateInfo>,
+}
+
+impl From<ServerStatus> for ProbeReport {
+    fn from(status: ServerStatus) -> Self {
+        Self {
+            status,
+            certificate: None,
         }
     }
 }
@@ -34,11 +168,21 @@ pub enum ServerMessage {
     ServerStateChanged {
         status: ServerStatus,
         server: Server,
+        certificate: Option<CertificateInfo>,
     },
     ChannelError {
         error: super::Error,
         server: Server,
     },
+    /// Probe didn't change the state and goes to the `check_result` table, see
+    /// [`crate::model::RecordMode`]
+    Checked {
+        server_id: i64,
+        state: ServerState,
+        /// `None` if the probe failed before getting an answer
+        status_code: Option<http::StatusCode>,
+        latency: Option<Duration>,
+    },
 }
 
 #[derive(Debug)]
@@ -46,15 +190,79 @@ pub enum ControlMessage {
     AddServer(Server),
     RemoveServer(i64),
     ModifyServer(Server),
+    /// Stops monitoring of the server, `until` resumes it automatically
+    PauseServer {
+        server_id: i64,
+        until: Option<OffsetDateTime>,
+    },
+    ResumeServer(i64),
+    /// Ping received for a `heartbeat` server
+    Heartbeat {
+        server_id: i64,
+        ping: HeartbeatPing,
+    },
     Shutdown,
 }
 
 impl ServerMessage {
     pub fn unreachable(status: ServerStatus, server: Server) -> Self {
-        Self::ServerStateChanged { status, server }
+        Self::ServerStateChanged {
+            status,
+            server,
+            certificate: None,
+        }
+    }
+
+    pub fn report(report: ProbeReport, server: Server) -> Self {
+        Self::ServerStateChanged {
+            status: report.status,
+            server,
+            certificate: report.certificate,
+        }
     }
 
     pub fn error(error: super::Error, server: Server) -> Self {
         Self::ChannelError { error, server }
     }
 }
+
+impl Coalesce for ServerMessage {
+    fn key(&self) -> i64 {
+        match self {
+            Self::ServerStateChanged { server, .. } | Self::ChannelError { server, .. } => server.id,
+            Self::Checked { server_id, .. } => *server_id,
+        }
+    }
+
+    /// A state change always wins, a plain check never hides it
+    fn supersedes(&self, queued: &Self) -> bool {
+        !self.is_disposable() || queued.is_disposable()
+    }
+
+    fn is_disposable(&self) -> bool {
+        matches!(self, Self::Checked { .. })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_latency_threshold() {
+        let online =
+            || ServerStatus::online(http::StatusCode::OK, vec![], Duration::from_millis(800));
+
+        let status = online().with_latency_threshold(Some(Duration::from_millis(500)));
+        assert_eq!(status.state(), ServerState::Degraded);
+        assert!(
+            matches!(status, ServerStatus::Degraded { reason, .. } if reason == "latency 800ms exceeds 500ms")
+        );
+
+        let status = online().with_latency_threshold(Some(Duration::from_secs(1)));
+        assert_eq!(status.state(), ServerState::Online);
+
+        let status = online().with_latency_threshold(None);
+        assert_eq!(status.state(), ServerState::Online);
+    }
+}
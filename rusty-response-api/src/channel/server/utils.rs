@@ -1,18 +1,87 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::model::{Server, ServerState};
+
+/// Reason prefix of failures caused by an unreachable parent server
+pub const DEPENDENCY_DOWN: &str = "dependency down";
+
+/// Whether notifications of the server are held back, while it's under maintenance or only down
+/// because one of its parents is
+pub fn is_silenced(server: &Server, under_maintenance: &HashSet<i64>) -> bool {
+    under_maintenance.contains(&server.id)
+        || server
+            .last_seen_reason
+            .as_deref()
+            .is_some_and(|reason| reason.starts_with(DEPENDENCY_DOWN))
+}
+
+/// Children that are only recorded as down because of a parent, while none of their parents is
+/// down anymore. They have to be re-evaluated, their tracker won't report the same state again
+pub fn stranded_dependents(statuses: &BTreeMap<i64, LastSeen>, children: &[Server]) -> Vec<i64> {
+    let is_down = |id: &i64| {
+        statuses
+            .get(id)
+            .is_some_and(|status| status.state == ServerState::Unreachable)
+    };
+
+    children
+        .iter()
+        .filter(|child| {
+            statuses.get(&child.id).is_some_and(|status| {
+                status.state == ServerState::Unreachable
+                    && status.reason.starts_with(DEPENDENCY_DOWN)
+            })
+        })
+        .filter(|child| !child.parent_ids.iter().any(is_down))
+        .map(|child| child.id)
+        .collect()
+}
+
+/// Last status written to the `server` table, used to skip redundant updates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastSeen {
+    pub state: ServerState,
+    pub code: i64,
+    pub reason: String,
+}
+
+impl LastSeen {
+    pub fn from_server(server: &Server) -> Self {
+        let code = server.last_seen_status_code.unwrap_or(0);
+
+        Self {
+            state: server
+                .last_seen_state
+                .unwrap_or_else(|| state_from_code(server, code)),
+            code,
+            reason: server.last_seen_reason.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Servers recorded before states were introduced only have a status code
+pub fn state_from_code(server: &Server, code: i64) -> ServerState {
+    if server.accepted_codes().contains(code as u16) {
+        ServerState::Online
+    } else {
+        ServerState::Unreachable
+    }
+}
 
 pub fn is_changed(
-    statuses: &BTreeMap<i64, (i64, String)>,
+    statuses: &BTreeMap<i64, LastSeen>,
     server_id: i64,
+    state: ServerState,
     code: i64,
     reason: Option<&String>,
 ) -> bool {
-    if let Some((prev_code, prev_reason)) = statuses.get(&server_id) {
-        if *prev_code != code {
+    if let Some(prev) = statuses.get(&server_id) {
+        if prev.state != state || prev.code != code {
             return true;
         }
 
         if let Some(r) = reason {
-            return r != prev_reason;
+            return *r != prev.reason;
         }
 
         false
@@ -22,15 +91,64 @@ pub fn is_changed(
 }
 
 pub fn update_cache(
-    statuses: &mut BTreeMap<i64, (i64, String)>,
+    statuses: &mut BTreeMap<i64, LastSeen>,
     server_id: i64,
+    state: ServerState,
     code: i64,
     reason: Option<String>,
 ) {
     statuses.entry(server_id).and_modify(|entry| {
-        entry.0 = code;
+        entry.state = state;
+        entry.code = code;
         if let Some(r) = reason {
-            entry.1 = r;
+            entry.reason = r;
         }
     });
 }
+
+#[cfg(test)]
+mod test {
+    use sqlx::types::Json;
+
+    use super::*;
+
+    fn status(state: ServerState, reason: &str) -> LastSeen {
+        LastSeen {
+            state,
+            code: 0,
+            reason: reason.to_string(),
+        }
+    }
+
+    fn child(id: i64, parent_ids: Vec<i64>) -> Server {
+        Server {
+            parent_ids: Json(parent_ids),
+            ..Server::mock(id, "http://localhost")
+        }
+    }
+
+    #[test]
+    fn test_stranded_dependents_after_parent_recovery() {
+        let silenced = format!("{DEPENDENCY_DOWN}: server 1 is unreachable");
+        let statuses = BTreeMap::from([
+            // parent that just recovered
+            (1, status(ServerState::Online, "")),
+            (2, status(ServerState::Unreachable, "")),
+            // children still down, recorded while the parent was
+            (10, status(ServerState::Unreachable, &silenced)),
+            (11, status(ServerState::Unreachable, &silenced)),
+            // children that recovered or are down on their own
+            (12, status(ServerState::Online, "")),
+            (13, status(ServerState::Unreachable, "connection refused")),
+        ]);
+        let children = [
+            child(10, vec![1]),
+            // another parent is still down
+            child(11, vec![1, 2]),
+            child(12, vec![1]),
+            child(13, vec![1]),
+        ];
+
+        assert_eq!(stranded_dependents(&statuses, &children), vec![10]);
+    }
+}
@@ -28,6 +28,91 @@ pub struct JWTSettings {
 #[derive(Debug, Deserialize, Default)]
 pub struct Application {
     jwt: JWTSettings,
+    /// Key used to encrypt secrets stored in the database, defaults to the JWT secret
+    #[serde(default)]
+    secret_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Monitoring {
+    /// Probes running at the same time
+    #[serde(default = "default_workers")]
+    workers: usize,
+    /// Probes running at the same time against a single host
+    #[serde(default = "default_per_host")]
+    per_host: usize,
+    /// Upper bound of the delay spreading first probes after startup, secs
+    #[serde(default = "default_max_jitter")]
+    max_jitter: u64,
+    /// Probe results waiting to be handled before they start being coalesced
+    #[serde(default = "default_queue_capacity")]
+    queue_capacity: usize,
+    /// Probe results and notifications handled at the same time, each for a different server
+    #[serde(default = "default_handlers")]
+    handlers: usize,
+    /// How often probe outcomes are flushed to the rollup tables and `last_latency_ms`, secs
+    #[serde(default = "default_rollup_flush")]
+    rollup_flush: u64,
+}
+
+/// How long rows of the growing tables are kept, `0` days keeps them forever
+#[derive(Debug, Deserialize)]
+pub struct Retention {
+    /// Days `server_log` lines are kept, servers may override it
+    #[serde(default = "default_server_log_days")]
+    server_log_days: i64,
+    /// Days `check_result` rows are kept, servers may override it
+    #[serde(default = "default_check_result_days")]
+    check_result_days: i64,
+    #[serde(default = "default_user_action_log_days")]
+    user_action_log_days: i64,
+    /// Days hourly rollups are kept, daily ones are never pruned
+    #[serde(default = "default_hourly_rollup_days")]
+    hourly_rollup_days: i64,
+    /// How often expired rows are pruned, secs
+    #[serde(default = "default_retention_interval")]
+    interval: u64,
+    /// Rows deleted by a single transaction
+    #[serde(default = "default_batch_size")]
+    batch_size: i64,
+    /// Pause between two batches so other writers get their turn, millis
+    #[serde(default = "default_batch_pause")]
+    batch_pause: u64,
+    /// Expired rows are appended to `<table>.jsonl` files in this directory before deletion
+    #[serde(default)]
+    archive_dir: Option<String>,
+    /// Only log what would be pruned
+    #[serde(default)]
+    dry_run: bool,
+    /// Free pages released by an incremental vacuum after every run
+    #[serde(default = "default_vacuum_pages")]
+    vacuum_pages: i64,
+    /// Switches a database created without incremental vacuum at startup, the full `VACUUM` this
+    /// takes locks the database until it's done, so it's left to the operator to opt in
+    #[serde(default)]
+    convert_vacuum: bool,
+}
+
+/// Limits of messages sent by notifiers, so a misbehaving server can't get them throttled
+#[derive(Debug, Deserialize)]
+pub struct RateLimit {
+    /// Messages a single notifier can send in a burst
+    #[serde(default = "default_notifier_burst")]
+    notifier_burst: u32,
+    /// Messages a single notifier can send per minute once its burst is spent
+    #[serde(default = "default_notifier_per_minute")]
+    notifier_per_minute: u32,
+    /// Same as `notifier_burst`, shared by notifiers using the same provider credentials
+    #[serde(default = "default_credential_burst")]
+    credential_burst: u32,
+    #[serde(default = "default_credential_per_minute")]
+    credential_per_minute: u32,
+    /// Identical messages of a notifier within this window are only sent once, secs
+    #[serde(default = "default_dedup_window")]
+    dedup_window: u64,
+    /// How often notifiers report how many alerts they suppressed, secs
+    #[serde(default = "default_summary_interval")]
+    summary_interval: u64,
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -37,6 +122,12 @@ pub struct Settings {
     app: Application,
     #[serde(default = "Database::default")]
     database: Database,
+    #[serde(default = "Monitoring::default")]
+    monitoring: Monitoring,
+    #[serde(default = "Retention::default")]
+    retention: Retention,
+    #[serde(default = "RateLimit::default")]
+    rate_limit: RateLimit,
 }
 
 impl Settings {
@@ -98,6 +189,50 @@ impl Default for Network {
     }
 }
 
+impl Default for Monitoring {
+    fn default() -> Self {
+        Self {
+            workers: default_workers(),
+            per_host: default_per_host(),
+            max_jitter: default_max_jitter(),
+            queue_capacity: default_queue_capacity(),
+            handlers: default_handlers(),
+            rollup_flush: default_rollup_flush(),
+        }
+    }
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            server_log_days: default_server_log_days(),
+            check_result_days: default_check_result_days(),
+            user_action_log_days: default_user_action_log_days(),
+            hourly_rollup_days: default_hourly_rollup_days(),
+            interval: default_retention_interval(),
+            batch_size: default_batch_size(),
+            batch_pause: default_batch_pause(),
+            archive_dir: None,
+            dry_run: false,
+            vacuum_pages: default_vacuum_pages(),
+            convert_vacuum: false,
+        }
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            notifier_burst: default_notifier_burst(),
+            notifier_per_minute: default_notifier_per_minute(),
+            credential_burst: default_credential_burst(),
+            credential_per_minute: default_credential_per_minute(),
+            dedup_window: default_dedup_window(),
+            summary_interval: default_summary_interval(),
+        }
+    }
+}
+
 impl Default for Database {
     fn default() -> Self {
         Self {
@@ -121,6 +256,142 @@ impl Settings {
     pub fn database(&self) -> &Database {
         &self.database
     }
+
+    #[inline]
+    pub fn monitoring(&self) -> &Monitoring {
+        &self.monitoring
+    }
+
+    #[inline]
+    pub fn retention(&self) -> &Retention {
+        &self.retention
+    }
+
+    #[inline]
+    pub fn rate_limit(&self) -> &RateLimit {
+        &self.rate_limit
+    }
+}
+
+impl Monitoring {
+    #[inline]
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    #[inline]
+    pub fn per_host(&self) -> usize {
+        self.per_host
+    }
+
+    #[inline]
+    pub fn max_jitter(&self) -> u64 {
+        self.max_jitter
+    }
+
+    #[inline]
+    pub fn queue_capacity(&self) -> usize {
+        self.queue_capacity
+    }
+
+    #[inline]
+    pub fn handlers(&self) -> usize {
+        self.handlers
+    }
+
+    #[inline]
+    pub fn rollup_flush(&self) -> u64 {
+        self.rollup_flush
+    }
+}
+
+impl Retention {
+    #[inline]
+    pub fn server_log_days(&self) -> i64 {
+        self.server_log_days
+    }
+
+    #[inline]
+    pub fn check_result_days(&self) -> i64 {
+        self.check_result_days
+    }
+
+    #[inline]
+    pub fn user_action_log_days(&self) -> i64 {
+        self.user_action_log_days
+    }
+
+    #[inline]
+    pub fn hourly_rollup_days(&self) -> i64 {
+        self.hourly_rollup_days
+    }
+
+    #[inline]
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    #[inline]
+    pub fn batch_size(&self) -> i64 {
+        self.batch_size
+    }
+
+    #[inline]
+    pub fn batch_pause(&self) -> u64 {
+        self.batch_pause
+    }
+
+    #[inline]
+    pub fn archive_dir(&self) -> Option<&str> {
+        self.archive_dir.as_deref()
+    }
+
+    #[inline]
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    #[inline]
+    pub fn vacuum_pages(&self) -> i64 {
+        self.vacuum_pages
+    }
+
+    #[inline]
+    pub fn convert_vacuum(&self) -> bool {
+        self.convert_vacuum
+    }
+}
+
+impl RateLimit {
+    #[inline]
+    pub fn notifier_burst(&self) -> u32 {
+        self.notifier_burst
+    }
+
+    #[inline]
+    pub fn notifier_per_minute(&self) -> u32 {
+        self.notifier_per_minute
+    }
+
+    #[inline]
+    pub fn credential_burst(&self) -> u32 {
+        self.credential_burst
+    }
+
+    #[inline]
+    pub fn credential_per_minute(&self) -> u32 {
+        self.credential_per_minute
+    }
+
+    #[inline]
+    pub fn dedup_window(&self) -> u64 {
+        self.dedup_window
+    }
+
+    #[inline]
+    pub fn summary_interval(&self) -> u64 {
+        self.summary_interval
+    }
 }
 
 impl Database {
@@ -159,6 +430,13 @@ impl Application {
     pub fn jwt(&self) -> &JWTSettings {
         &self.jwt
     }
+
+    #[inline]
+    pub fn secret_key(&self) -> &str {
+        self.secret_key
+            .as_deref()
+            .unwrap_or_else(|| self.jwt.jwt_secret())
+    }
 }
 
 fn default_host() -> String {
@@ -169,6 +447,86 @@ fn default_port() -> u16 {
     5000
 }
 
+fn default_workers() -> usize {
+    256
+}
+
+fn default_per_host() -> usize {
+    8
+}
+
+fn default_max_jitter() -> u64 {
+    60
+}
+
+fn default_queue_capacity() -> usize {
+    1024
+}
+
+fn default_handlers() -> usize {
+    16
+}
+
+fn default_rollup_flush() -> u64 {
+    60
+}
+
+fn default_server_log_days() -> i64 {
+    90
+}
+
+fn default_check_result_days() -> i64 {
+    30
+}
+
+fn default_user_action_log_days() -> i64 {
+    365
+}
+
+fn default_hourly_rollup_days() -> i64 {
+    90
+}
+
+fn default_retention_interval() -> u64 {
+    3600
+}
+
+fn default_batch_size() -> i64 {
+    1000
+}
+
+fn default_batch_pause() -> u64 {
+    50
+}
+
+fn default_vacuum_pages() -> i64 {
+    1000
+}
+
+fn default_notifier_burst() -> u32 {
+    10
+}
+
+fn default_notifier_per_minute() -> u32 {
+    6
+}
+
+fn default_credential_burst() -> u32 {
+    20
+}
+
+fn default_credential_per_minute() -> u32 {
+    20
+}
+
+fn default_dedup_window() -> u64 {
+    300
+}
+
+fn default_summary_interval() -> u64 {
+    60
+}
+
 fn default_expire_time() -> i64 {
     3600
 }
@@ -4,6 +4,7 @@ pub type Result<T> = std::result::Result<T, CryptError>;
 pub enum CryptError {
     JwtError(jsonwebtoken::errors::Error),
     BcryptError(bcrypt::BcryptError),
+    SecretError(&'static str),
 }
 
 impl std::fmt::Display for CryptError {
@@ -1,7 +1,9 @@
 mod error;
 mod password;
+mod secret;
 mod token;
 pub use error::{CryptError, Result};
 
 pub use password::BcryptController;
+pub use secret::SecretController;
 pub use token::JWTController;
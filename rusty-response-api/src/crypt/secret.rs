@@ -0,0 +1,96 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use ring::{
+    aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    digest::{SHA256, digest},
+    rand::{SecureRandom, SystemRandom},
+};
+
+use super::{CryptError, Result};
+
+/// Reversible encryption for secrets that have to be sent to third parties as is,
+/// e.g `Authorization` headers of monitored servers.
+///
+/// AES-256-GCM with a key derived from `key`, output is base64 of `nonce || ciphertext`.
+pub struct SecretController;
+
+impl SecretController {
+    pub fn encrypt<K: AsRef<[u8]>>(s: &str, key: K) -> Result<String> {
+        let key = Self::key(key)?;
+
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| CryptError::SecretError("unable to generate nonce"))?;
+
+        let mut in_out = s.as_bytes().to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut in_out,
+        )
+        .map_err(|_| CryptError::SecretError("unable to encrypt secret"))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&in_out);
+        Ok(STANDARD.encode(sealed))
+    }
+
+    pub fn decrypt<K: AsRef<[u8]>>(s: &str, key: K) -> Result<String> {
+        let key = Self::key(key)?;
+        let sealed = STANDARD
+            .decode(s)
+            .map_err(|_| CryptError::SecretError("secret is not valid base64"))?;
+        if sealed.len() < NONCE_LEN {
+            return Err(CryptError::SecretError("secret is too short"));
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| CryptError::SecretError("invalid nonce"))?;
+        let mut in_out = ciphertext.to_vec();
+        let plain = key
+            .open_in_place(nonce, Aad::empty(), &mut in_out)
+            .map_err(|_| CryptError::SecretError("unable to decrypt secret"))?;
+
+        String::from_utf8(plain.to_vec())
+            .map_err(|_| CryptError::SecretError("secret is not valid utf-8"))
+    }
+
+    /// Random hex token, e.g for URLs that act as credentials
+    pub fn generate_token() -> Result<String> {
+        let mut bytes = [0u8; 16];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| CryptError::SecretError("unable to generate token"))?;
+
+        Ok(bytes.iter().map(|b| format!("{b:02x}")).collect())
+    }
+
+    fn key<K: AsRef<[u8]>>(key: K) -> Result<LessSafeKey> {
+        let hashed = digest(&SHA256, key.as_ref());
+        let key = UnboundKey::new(&AES_256_GCM, hashed.as_ref())
+            .map_err(|_| CryptError::SecretError("invalid key"))?;
+        Ok(LessSafeKey::new(key))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_secret_roundtrip() {
+        let sealed = SecretController::encrypt("Bearer foobar", "key").unwrap();
+        assert_ne!(sealed, "Bearer foobar");
+        assert_eq!(
+            SecretController::decrypt(&sealed, "key").unwrap(),
+            "Bearer foobar"
+        );
+    }
+
+    #[test]
+    fn test_secret_wrong_key() {
+        let sealed = SecretController::encrypt("Bearer foobar", "key").unwrap();
+        assert!(SecretController::decrypt(&sealed, "other").is_err());
+    }
+}
@@ -30,8 +30,7 @@ pub mod test {
 
     fn generate_token() -> String {
         let claims = UserClaims::new("TEST", time::UtcDateTime::now().unix_timestamp());
-        let token = JWTController::generate_token(claims, "foo").expect("Unable to generate token");
-        token
+        JWTController::generate_token(claims, "foo").expect("Unable to generate token")
     }
 
     #[test]
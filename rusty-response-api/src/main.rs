@@ -38,12 +38,14 @@ async fn run(mm: ModelManager) -> Result<()> {
     let child_token = cancel_token.child_token();
     let admin_ctx = Ctx::admin_root();
     let config = Settings::global();
+    let server_mpsc = channel::BoundedMPSCController::new(config.monitoring().queue_capacity());
 
     let state = web::app_state(
         &mm,
         config.app().jwt().jwt_secret(),
         &admin_ctx,
         control_tx.clone(),
+        server_mpsc.stats(),
     )
     .await?;
 
@@ -53,11 +55,19 @@ async fn run(mm: ModelManager) -> Result<()> {
 
     info!("Server started at {}", addr);
 
+    let shutdown_tx = control_tx.clone();
     let axum_handle = axum::serve(listener, app)
-        .with_graceful_shutdown(web::shutdown_signal(cancel_token.clone(), control_tx));
-
-    let servers_handle =
-        channel::setup_monitoring_future(mm, control_rx, state.notify_manager.clone(), child_token);
+        .with_graceful_shutdown(web::shutdown_signal(cancel_token.clone(), shutdown_tx));
+
+    let servers_handle = channel::setup_monitoring_future(
+        mm,
+        control_tx,
+        control_rx,
+        server_mpsc,
+        state.notify_manager.clone(),
+        state.under_maintenance.clone(),
+        child_token,
+    );
 
     let _ = tokio::join!(axum_handle, servers_handle); // wait for both to finish
 
@@ -0,0 +1,95 @@
+use super::Result;
+use serde::Serialize;
+use sqlx::{FromRow, Row, Sqlite};
+use time::PrimitiveDateTime;
+
+use crate::{
+    ModelManager,
+    model::{Ctx, Page, ServerState},
+};
+
+/// Result of a single check, unlike [`super::ServerLog`] it's written for more than transitions
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct CheckResult {
+    pub id: i64,
+    pub server_id: i64,
+    pub state: ServerState,
+    pub status_code: Option<i64>,
+    pub latency_ms: Option<i64>,
+    pub transition: bool,
+    pub checked_at: PrimitiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckResultCreate {
+    pub server_id: i64,
+    pub state: ServerState,
+    pub status_code: Option<i64>,
+    pub latency_ms: Option<i64>,
+    pub transition: bool,
+}
+
+pub struct CheckResultBmc;
+
+impl CheckResultBmc {
+    pub async fn insert(mm: &ModelManager, _ctx: &Ctx, crc: CheckResultCreate) -> Result<i64> {
+        let row = sqlx::query(
+            "INSERT INTO check_result (server_id, state, status_code, latency_ms, transition) VALUES (?,?,?,?,?) RETURNING id",
+        )
+        .bind(crc.server_id)
+        .bind(crc.state)
+        .bind(crc.status_code)
+        .bind(crc.latency_ms)
+        .bind(crc.transition)
+        .fetch_one(&mm.pool)
+        .await?;
+
+        let id = row.try_get("id")?;
+        Ok(id)
+    }
+}
+
+// Listing API
+impl CheckResultBmc {
+    pub async fn count(mm: &ModelManager, _ctx: &Ctx, server_id: i64) -> Result<i64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM check_result WHERE server_id = ?")
+            .bind(server_id)
+            .fetch_one(&mm.pool)
+            .await?;
+        let count = row.try_get("count")?;
+        Ok(count)
+    }
+
+    /// Latest results first
+    pub async fn list(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        server_id: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<CheckResult>> {
+        let results = sqlx::query_as::<Sqlite, CheckResult>(
+            "SELECT * FROM check_result WHERE server_id = ? ORDER BY checked_at DESC, id DESC LIMIT ? OFFSET ?",
+        )
+        .bind(server_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&mm.pool)
+        .await?;
+
+        Ok(results)
+    }
+
+    pub async fn page(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        server_id: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Page<CheckResult>> {
+        let items = Self::list(mm, ctx, server_id, offset, limit).await?;
+        let count = Self::count(mm, ctx, server_id).await?;
+
+        Ok(Page::new(items, count, limit, offset))
+    }
+}
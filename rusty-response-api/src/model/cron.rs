@@ -0,0 +1,246 @@
+use std::{fmt, str::FromStr};
+
+use time::{Date, Duration, PrimitiveDateTime, Time};
+
+/// Cron expression of five fields `minute hour day-of-month month day-of-week`,
+/// e.g `30 2 * * 0` for every Sunday at 02:30. Fields accept `*`, lists, ranges and steps
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // standard cron matches either day field when both are restricted
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronSchedule {
+    /// Whether an occurrence starts at `local`, seconds are ignored
+    pub fn matches(&self, local: PrimitiveDateTime) -> bool {
+        bit(self.minutes, local.minute())
+            && bit(self.hours, local.hour())
+            && self.matches_date(local.date())
+    }
+
+    fn matches_date(&self, date: Date) -> bool {
+        let day = bit(self.days, date.day());
+        let weekday = bit(self.weekdays, date.weekday().number_days_from_sunday());
+        let day_matches = match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        };
+
+        bit(self.months, date.month() as u8) && day_matches
+    }
+
+    /// Latest occurrence at or before `local`, not earlier than `earliest`. Seconds are ignored
+    pub fn latest(
+        &self,
+        local: PrimitiveDateTime,
+        earliest: PrimitiveDateTime,
+    ) -> Option<PrimitiveDateTime> {
+        let mut date = local.date();
+        let (mut max_hour, mut max_minute) = (local.hour(), local.minute());
+
+        while date >= earliest.date() {
+            if self.matches_date(date) {
+                let mut hour = highest(self.hours, max_hour);
+                while let Some(h) = hour {
+                    let limit = if h == max_hour { max_minute } else { 59 };
+                    if let Some(m) = highest(self.minutes, limit) {
+                        let occurrence =
+                            PrimitiveDateTime::new(date, Time::from_hms(h, m, 0).ok()?);
+                        return (occurrence >= earliest).then_some(occurrence);
+                    }
+                    hour = h.checked_sub(1).and_then(|h| highest(self.hours, h));
+                }
+            }
+
+            date = date.previous_day()?;
+            (max_hour, max_minute) = (23, 59);
+        }
+
+        None
+    }
+
+    /// Occurrences at or before `local`, latest first, not earlier than `earliest`
+    pub fn occurrences_before(
+        &self,
+        local: PrimitiveDateTime,
+        earliest: PrimitiveDateTime,
+    ) -> impl Iterator<Item = PrimitiveDateTime> + '_ {
+        std::iter::successors(self.latest(local, earliest), move |previous| {
+            self.latest(*previous - Duration::MINUTE, earliest)
+        })
+    }
+}
+
+fn bit(mask: u64, value: u8) -> bool {
+    mask & (1 << value) != 0
+}
+
+/// Highest value allowed by the mask that's at most `max`
+fn highest(mask: u64, max: u8) -> Option<u8> {
+    let allowed = mask & (u64::MAX >> (63 - max));
+    (allowed != 0).then(|| 63 - allowed.leading_zeros() as u8)
+}
+
+/// Parses a single field into a bit mask of allowed values
+fn parse_field(field: &str, min: u8, max: u8) -> Result<u64, String> {
+    let invalid = || format!("invalid cron field `{field}`");
+    let parse = |value: &str| {
+        value
+            .parse::<u8>()
+            .ok()
+            .filter(|value| (min..=max).contains(value))
+            .ok_or_else(invalid)
+    };
+
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u8>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (parse(start)?, parse(end)?),
+                // `5/15` means from 5 up to the maximum
+                None if step > 1 => (parse(range)?, max),
+                None => (parse(range)?, parse(range)?),
+            },
+        };
+        if start > end {
+            return Err(invalid());
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!(
+                "cron expression needs 5 fields, got {}",
+                fields.len()
+            ));
+        };
+
+        let mut weekday_mask = parse_field(weekdays, 0, 7)?;
+        // both 0 and 7 are Sunday
+        if weekday_mask & (1 << 7) != 0 {
+            weekday_mask |= 1;
+        }
+
+        Ok(Self {
+            source: fields.join(" "),
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: weekday_mask,
+            days_restricted: days != "*",
+            weekdays_restricted: weekdays != "*",
+        })
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn test_cron_matches() {
+        let schedule: CronSchedule = "30 2 * * 0".parse().unwrap();
+        // 2026-10-18 is a Sunday
+        assert!(schedule.matches(datetime!(2026-10-18 02:30)));
+        assert!(!schedule.matches(datetime!(2026-10-18 02:31)));
+        assert!(!schedule.matches(datetime!(2026-10-19 02:30)));
+
+        let schedule: CronSchedule = "*/15 9-17 * * 1-5".parse().unwrap();
+        assert!(schedule.matches(datetime!(2026-10-19 09:45)));
+        assert!(!schedule.matches(datetime!(2026-10-19 09:50)));
+        assert!(!schedule.matches(datetime!(2026-10-18 09:45)));
+
+        // day of month and day of week are alternatives when both are set
+        let schedule: CronSchedule = "0 0 1 * 7".parse().unwrap();
+        assert!(schedule.matches(datetime!(2026-10-01 00:00)));
+        assert!(schedule.matches(datetime!(2026-10-18 00:00)));
+        assert!(!schedule.matches(datetime!(2026-10-19 00:00)));
+        assert_eq!(schedule.to_string(), "0 0 1 * 7");
+    }
+
+    #[test]
+    fn test_cron_latest() {
+        let schedule: CronSchedule = "30 2 * * 0".parse().unwrap();
+        let earliest = datetime!(2026-10-01 00:00);
+        assert_eq!(
+            schedule.latest(datetime!(2026-10-21 12:00), earliest),
+            Some(datetime!(2026-10-18 02:30))
+        );
+        assert_eq!(
+            schedule.latest(datetime!(2026-10-18 02:30), earliest),
+            Some(datetime!(2026-10-18 02:30))
+        );
+        assert_eq!(
+            schedule.latest(datetime!(2026-10-18 02:29), earliest),
+            Some(datetime!(2026-10-11 02:30))
+        );
+        assert_eq!(schedule.latest(datetime!(2026-10-03 00:00), earliest), None);
+
+        let schedule: CronSchedule = "*/15 9-17 * * 1-5".parse().unwrap();
+        assert_eq!(
+            schedule.latest(datetime!(2026-10-19 09:14), earliest),
+            Some(datetime!(2026-10-19 09:00))
+        );
+        assert_eq!(
+            schedule.latest(datetime!(2026-10-19 08:59), earliest),
+            Some(datetime!(2026-10-16 17:45))
+        );
+
+        let occurrences: Vec<_> = schedule
+            .occurrences_before(datetime!(2026-10-19 09:31), datetime!(2026-10-19 09:00))
+            .collect();
+        assert_eq!(
+            occurrences,
+            [
+                datetime!(2026-10-19 09:30),
+                datetime!(2026-10-19 09:15),
+                datetime!(2026-10-19 09:00)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cron_invalid() {
+        assert!("* * * *".parse::<CronSchedule>().is_err());
+        assert!("60 * * * *".parse::<CronSchedule>().is_err());
+        assert!("* * 0 * *".parse::<CronSchedule>().is_err());
+        assert!("*/0 * * * *".parse::<CronSchedule>().is_err());
+        assert!("5-1 * * * *".parse::<CronSchedule>().is_err());
+    }
+}
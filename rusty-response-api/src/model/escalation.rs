@@ -0,0 +1,230 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row, Sqlite, types::Json};
+use time::{Duration, PrimitiveDateTime};
+use utoipa::ToSchema;
+
+use super::{Ctx, ModelManager, Result};
+use crate::model::Page;
+
+/// Notifiers reached once an outage stays unacknowledged for `after` minutes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct EscalationStep {
+    pub after: i64,
+    pub notifier_ids: Vec<i64>,
+}
+
+/// Steps notifying more notifiers the longer an outage of its servers stays unacknowledged,
+/// e.g set A right away, set B after 10 minutes and set C after 30
+#[derive(Debug, Clone, FromRow, Serialize, ToSchema)]
+pub struct EscalationPolicy {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    #[schema(value_type = Vec<EscalationStep>)]
+    pub steps: Json<Vec<EscalationStep>>,
+    pub created_at: PrimitiveDateTime,
+    pub updated_at: PrimitiveDateTime,
+}
+
+impl EscalationPolicy {
+    /// Steps due `elapsed` into an outage, skipping the first `notified` steps that were sent already
+    pub fn due(&self, notified: i64, elapsed: Duration) -> Range<usize> {
+        let start = (notified.max(0) as usize).min(self.steps.len());
+        let end = self
+            .steps
+            .iter()
+            .take_while(|step| elapsed >= Duration::minutes(step.after))
+            .count();
+
+        start..end.max(start)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct EscalationPolicyCreate {
+    pub name: String,
+    pub steps: Vec<EscalationStep>,
+}
+
+impl EscalationPolicyCreate {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.steps.is_empty() {
+            return Err("escalation policy needs at least one step".to_string());
+        }
+
+        let mut previous = 0;
+        for (i, step) in self.steps.iter().enumerate() {
+            if step.notifier_ids.is_empty() {
+                return Err(format!("step {} has no notifiers", i + 1));
+            }
+            if step.after < previous {
+                return Err(format!(
+                    "step {} starts before the previous one, steps have to be ordered by `after`",
+                    i + 1
+                ));
+            }
+            previous = step.after;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct EscalationPolicyBmc;
+
+/// Database interactions
+impl EscalationPolicyBmc {
+    pub async fn insert(mm: &ModelManager, ctx: &Ctx, pc: EscalationPolicyCreate) -> Result<i64> {
+        let row = sqlx::query(
+            "INSERT INTO escalation_policy (user_id, name, steps) VALUES (?,?,?) RETURNING id",
+        )
+        .bind(ctx.user_id)
+        .bind(&pc.name)
+        .bind(Json(&pc.steps))
+        .fetch_one(&mm.pool)
+        .await?;
+
+        let id = row.try_get("id")?;
+        Ok(id)
+    }
+
+    pub async fn update(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        id: i64,
+        pc: EscalationPolicyCreate,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE escalation_policy SET name = ?, steps = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(&pc.name)
+        .bind(Json(&pc.steps))
+        .bind(id)
+        .execute(&mm.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get(mm: &ModelManager, _ctx: &Ctx, id: i64) -> Result<Option<EscalationPolicy>> {
+        let policy = sqlx::query_as::<Sqlite, EscalationPolicy>(
+            "SELECT * FROM escalation_policy WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&mm.pool)
+        .await?;
+
+        Ok(policy)
+    }
+
+    pub async fn delete(mm: &ModelManager, _ctx: &Ctx, id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM escalation_policy WHERE id = ?")
+            .bind(id)
+            .execute(&mm.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+// Listing API
+impl EscalationPolicyBmc {
+    pub async fn count(mm: &ModelManager, ctx: &Ctx) -> Result<i64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM escalation_policy WHERE user_id = ?")
+            .bind(ctx.user_id)
+            .fetch_one(&mm.pool)
+            .await?;
+
+        let count = row.try_get("count")?;
+        Ok(count)
+    }
+
+    pub async fn list(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<EscalationPolicy>> {
+        let policies = sqlx::query_as::<Sqlite, EscalationPolicy>(
+            "SELECT * FROM escalation_policy WHERE user_id = ? LIMIT ? OFFSET ?",
+        )
+        .bind(ctx.user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&mm.pool)
+        .await?;
+
+        Ok(policies)
+    }
+
+    pub async fn page(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Page<EscalationPolicy>> {
+        let items = Self::list(mm, ctx, offset, limit).await?;
+        let count = Self::count(mm, ctx).await?;
+
+        Ok(Page::new(items, count, limit, offset))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn step(after: i64, notifier_id: i64) -> EscalationStep {
+        EscalationStep {
+            after,
+            notifier_ids: vec![notifier_id],
+        }
+    }
+
+    fn policy(steps: Vec<EscalationStep>) -> EscalationPolicy {
+        EscalationPolicy {
+            id: 1,
+            user_id: 1,
+            name: "on-call".to_string(),
+            steps: Json(steps),
+            created_at: datetime!(2026-01-01 00:00),
+            updated_at: datetime!(2026-01-01 00:00),
+        }
+    }
+
+    #[test]
+    fn test_escalation_due_steps() {
+        let policy = policy(vec![step(0, 1), step(10, 2), step(30, 3)]);
+
+        assert_eq!(policy.due(0, Duration::seconds(5)), 0..1);
+        assert_eq!(policy.due(1, Duration::minutes(9)), 1..1);
+        assert_eq!(policy.due(1, Duration::minutes(10)), 1..2);
+        // a restart after a long downtime sends every missed step at once
+        assert_eq!(policy.due(1, Duration::hours(2)), 1..3);
+        assert_eq!(policy.due(3, Duration::hours(2)), 3..3);
+    }
+
+    #[test]
+    fn test_escalation_policy_validate() {
+        let create = |steps| EscalationPolicyCreate {
+            name: "on-call".to_string(),
+            steps,
+        };
+
+        assert!(create(vec![step(0, 1), step(10, 2)]).validate().is_ok());
+        assert!(create(vec![]).validate().is_err());
+        assert!(create(vec![step(10, 1), step(5, 2)]).validate().is_err());
+        assert!(
+            create(vec![EscalationStep {
+                after: 0,
+                notifier_ids: vec![]
+            }])
+            .validate()
+            .is_err()
+        );
+    }
+}
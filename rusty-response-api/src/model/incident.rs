@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row, Sqlite};
+use time::PrimitiveDateTime;
+use utoipa::ToSchema;
+
+use super::{Ctx, ModelManager, Result};
+use crate::model::Page;
+
+/// Columns of an incident, `duration` runs until now while it's open
+const SELECT_INCIDENT: &str = "SELECT incident.*, \
+    CAST(strftime('%s', COALESCE(resolved_at, CURRENT_TIMESTAMP)) AS INTEGER) - CAST(strftime('%s', started_at) AS INTEGER) AS duration \
+    FROM incident";
+
+/// Outage of a server, log lines written while it's open are linked to it by `incident_id`
+#[derive(Debug, Clone, FromRow, Serialize, ToSchema)]
+pub struct Incident {
+    pub id: i64,
+    pub server_id: i64,
+    pub started_at: PrimitiveDateTime,
+    pub resolved_at: Option<PrimitiveDateTime>,
+    /// Seconds from the start to the resolution, or to now while the incident is open
+    pub duration: i64,
+    pub root_cause: Option<String>,
+    pub acknowledged_by: Option<i64>,
+    pub acknowledged_at: Option<PrimitiveDateTime>,
+    /// Steps of the server's escalation policy notified so far
+    pub escalation_step: i64,
+    pub created_at: PrimitiveDateTime,
+    pub updated_at: PrimitiveDateTime,
+}
+
+impl Incident {
+    pub fn is_open(&self) -> bool {
+        self.resolved_at.is_none()
+    }
+
+    /// Acknowledged incidents aren't reminded of anymore
+    pub fn is_acknowledged(&self) -> bool {
+        self.acknowledged_at.is_some()
+    }
+}
+
+/// Incident opened by hand, e.g for an outage the probes can't see
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct IncidentCreate {
+    pub server_id: i64,
+    pub root_cause: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct IncidentUpdate {
+    pub root_cause: Option<String>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, ToSchema)]
+pub struct IncidentNote {
+    pub id: i64,
+    pub incident_id: i64,
+    pub user_id: i64,
+    pub body: String,
+    pub created_at: PrimitiveDateTime,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct IncidentNoteCreate {
+    pub body: String,
+}
+
+/// Narrows down listed incidents
+#[derive(Debug, Clone, Default)]
+pub struct IncidentFilter {
+    pub server_id: Option<i64>,
+    /// `true` for open incidents only, `false` for resolved ones only
+    pub open: Option<bool>,
+}
+
+pub struct IncidentBmc;
+
+/// Lifecycle, driven by state changes of the server
+impl IncidentBmc {
+    /// Opens an incident of the server unless one is open already, returns the open one
+    pub async fn open(mm: &ModelManager, ctx: &Ctx, server_id: i64) -> Result<Incident> {
+        sqlx::query("INSERT OR IGNORE INTO incident (server_id) VALUES (?)")
+            .bind(server_id)
+            .execute(&mm.pool)
+            .await?;
+
+        let incident = Self::get_open(mm, ctx, server_id).await?;
+        incident.ok_or(sqlx::Error::RowNotFound.into())
+    }
+
+    pub async fn get_open(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        server_id: i64,
+    ) -> Result<Option<Incident>> {
+        let incident = sqlx::query_as::<Sqlite, Incident>(&format!(
+            "{SELECT_INCIDENT} WHERE server_id = ? AND resolved_at IS NULL"
+        ))
+        .bind(server_id)
+        .fetch_optional(&mm.pool)
+        .await?;
+
+        Ok(incident)
+    }
+
+    /// Resolves the open incident of the server, returns it if there was one
+    pub async fn resolve(mm: &ModelManager, ctx: &Ctx, server_id: i64) -> Result<Option<Incident>> {
+        let Some(incident) = Self::get_open(mm, ctx, server_id).await? else {
+            return Ok(None);
+        };
+
+        sqlx::query(
+            "UPDATE incident SET resolved_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = ? AND resolved_at IS NULL",
+        )
+        .bind(incident.id)
+        .execute(&mm.pool)
+        .await?;
+
+        Self::get(mm, ctx, incident.id).await
+    }
+
+    /// Open and unacknowledged incidents of servers with an escalation policy
+    pub async fn escalating(mm: &ModelManager, _ctx: &Ctx) -> Result<Vec<Incident>> {
+        let incidents = sqlx::query_as::<Sqlite, Incident>(&format!(
+            "{SELECT_INCIDENT} WHERE resolved_at IS NULL AND acknowledged_at IS NULL \
+            AND server_id IN (SELECT id FROM server WHERE escalation_policy_id IS NOT NULL)"
+        ))
+        .fetch_all(&mm.pool)
+        .await?;
+
+        Ok(incidents)
+    }
+
+    /// Open incidents nobody took care of yet
+    pub async fn unacknowledged(mm: &ModelManager, _ctx: &Ctx) -> Result<Vec<Incident>> {
+        let incidents = sqlx::query_as::<Sqlite, Incident>(&format!(
+            "{SELECT_INCIDENT} WHERE resolved_at IS NULL AND acknowledged_at IS NULL"
+        ))
+        .fetch_all(&mm.pool)
+        .await?;
+
+        Ok(incidents)
+    }
+
+    /// Records the first `step` steps of the escalation as notified
+    pub async fn escalated(mm: &ModelManager, _ctx: &Ctx, id: i64, step: i64) -> Result<()> {
+        sqlx::query("UPDATE incident SET escalation_step = ? WHERE id = ? AND escalation_step < ?")
+            .bind(step)
+            .bind(id)
+            .bind(step)
+            .execute(&mm.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Database interactions
+impl IncidentBmc {
+    /// Opens an incident by hand, `None` if the server already has an open one
+    pub async fn insert(mm: &ModelManager, _ctx: &Ctx, ic: IncidentCreate) -> Result<Option<i64>> {
+        let row = sqlx::query(
+            "INSERT OR IGNORE INTO incident (server_id, root_cause) VALUES (?, ?) RETURNING id",
+        )
+        .bind(ic.server_id)
+        .bind(ic.root_cause)
+        .fetch_optional(&mm.pool)
+        .await?;
+
+        row.map(|row| row.try_get("id"))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    pub async fn get(mm: &ModelManager, _ctx: &Ctx, id: i64) -> Result<Option<Incident>> {
+        let incident =
+            sqlx::query_as::<Sqlite, Incident>(&format!("{SELECT_INCIDENT} WHERE id = ?"))
+                .bind(id)
+                .fetch_optional(&mm.pool)
+                .await?;
+
+        Ok(incident)
+    }
+
+    pub async fn update(mm: &ModelManager, _ctx: &Ctx, id: i64, iu: IncidentUpdate) -> Result<()> {
+        sqlx::query(
+            "UPDATE incident SET root_cause = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(iu.root_cause)
+        .bind(id)
+        .execute(&mm.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Marks the incident as taken care of by the user, the first acknowledgement is kept
+    pub async fn acknowledge(mm: &ModelManager, ctx: &Ctx, id: i64) -> Result<()> {
+        sqlx::query(
+            "UPDATE incident SET acknowledged_by = ?, acknowledged_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP \
+            WHERE id = ? AND acknowledged_at IS NULL",
+        )
+        .bind(ctx.user_id)
+        .bind(id)
+        .execute(&mm.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete(mm: &ModelManager, _ctx: &Ctx, id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM incident WHERE id = ?")
+            .bind(id)
+            .execute(&mm.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Reminders
+impl IncidentBmc {
+    /// Reminders sent for the incident so far, by notifier id
+    pub async fn reminders(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        incident_id: i64,
+    ) -> Result<HashMap<i64, i64>> {
+        let rows = sqlx::query_as::<Sqlite, (i64, i64)>(
+            "SELECT notifier_id, count FROM incident_reminder WHERE incident_id = ?",
+        )
+        .bind(incident_id)
+        .fetch_all(&mm.pool)
+        .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    pub async fn reminded(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        incident_id: i64,
+        notifier_id: i64,
+        count: i64,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO incident_reminder (incident_id, notifier_id, count) VALUES (?, ?, ?) \
+            ON CONFLICT (incident_id, notifier_id) DO UPDATE SET count = excluded.count",
+        )
+        .bind(incident_id)
+        .bind(notifier_id)
+        .bind(count)
+        .execute(&mm.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Notes
+impl IncidentBmc {
+    pub async fn add_note(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        incident_id: i64,
+        nc: IncidentNoteCreate,
+    ) -> Result<IncidentNote> {
+        let note = sqlx::query_as::<Sqlite, IncidentNote>(
+            "INSERT INTO incident_note (incident_id, user_id, body) VALUES (?, ?, ?) RETURNING *",
+        )
+        .bind(incident_id)
+        .bind(ctx.user_id)
+        .bind(nc.body)
+        .fetch_one(&mm.pool)
+        .await?;
+
+        Ok(note)
+    }
+
+    /// Notes of the incident, oldest first
+    pub async fn notes(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        incident_id: i64,
+    ) -> Result<Vec<IncidentNote>> {
+        let notes = sqlx::query_as::<Sqlite, IncidentNote>(
+            "SELECT * FROM incident_note WHERE incident_id = ? ORDER BY created_at, id",
+        )
+        .bind(incident_id)
+        .fetch_all(&mm.pool)
+        .await?;
+
+        Ok(notes)
+    }
+}
+
+// Listing API
+impl IncidentBmc {
+    const FILTER: &str = "WHERE server_id IN (SELECT id FROM server WHERE user_id = ?) \
+        AND (? IS NULL OR server_id = ?) \
+        AND (? IS NULL OR (resolved_at IS NULL) = ?)";
+
+    pub async fn count(mm: &ModelManager, ctx: &Ctx, filter: &IncidentFilter) -> Result<i64> {
+        let row = sqlx::query(&format!(
+            "SELECT COUNT(*) as count FROM incident {}",
+            Self::FILTER
+        ))
+        .bind(ctx.user_id)
+        .bind(filter.server_id)
+        .bind(filter.server_id)
+        .bind(filter.open)
+        .bind(filter.open)
+        .fetch_one(&mm.pool)
+        .await?;
+
+        let count = row.try_get("count")?;
+        Ok(count)
+    }
+
+    /// Incidents of the user's servers, latest first
+    pub async fn list(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        filter: &IncidentFilter,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<Incident>> {
+        let incidents = sqlx::query_as::<Sqlite, Incident>(&format!(
+            "{SELECT_INCIDENT} {} ORDER BY started_at DESC, id DESC LIMIT ? OFFSET ?",
+            Self::FILTER
+        ))
+        .bind(ctx.user_id)
+        .bind(filter.server_id)
+        .bind(filter.server_id)
+        .bind(filter.open)
+        .bind(filter.open)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&mm.pool)
+        .await?;
+
+        Ok(incidents)
+    }
+
+    pub async fn page(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        filter: &IncidentFilter,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Page<Incident>> {
+        let items = Self::list(mm, ctx, filter, offset, limit).await?;
+        let count = Self::count(mm, ctx, filter).await?;
+
+        Ok(Page::new(items, count, limit, offset))
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row, Sqlite};
+use time::{Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use time_tz::{OffsetDateTimeExt, OffsetResult, PrimitiveDateTimeExt, timezones};
+use utoipa::ToSchema;
+
+use super::{Ctx, ModelManager, Result};
+use crate::model::{CronSchedule, Page};
+
+/// Longest recurring window, in minutes
+const MAX_DURATION: i64 = 7 * 24 * 60;
+
+/// Occurrences are looked up on the local wall clock, this covers offset changes around them
+const OFFSET_MARGIN: Duration = Duration::hours(3);
+
+/// Window during which checks of its servers still run, but nothing is notified.
+///
+/// One-off windows last from `starts_at` to `ends_at`. Recurring ones start whenever `schedule`
+/// matches the wall clock of `timezone` and last `duration` minutes
+#[derive(Debug, Clone, FromRow, Serialize, ToSchema)]
+pub struct Maintenance {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub starts_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub ends_at: Option<OffsetDateTime>,
+    pub schedule: Option<String>,
+    pub duration: Option<i64>,
+    pub timezone: String,
+    pub notify: bool,
+    #[sqlx(skip)]
+    pub server_ids: Vec<i64>,
+    pub created_at: PrimitiveDateTime,
+    pub updated_at: PrimitiveDateTime,
+}
+
+impl Maintenance {
+    pub fn is_active(&self, now: OffsetDateTime) -> bool {
+        let in_bounds = |moment: OffsetDateTime| {
+            self.starts_at <= moment && self.ends_at.is_none_or(|end| moment < end)
+        };
+
+        let Some(schedule) = &self.schedule else {
+            return in_bounds(now);
+        };
+        let (Ok(schedule), Some(tz)) = (
+            schedule.parse::<CronSchedule>(),
+            timezones::get_by_name(&self.timezone),
+        ) else {
+            return false;
+        };
+
+        // look for an occurrence started within the last `duration` minutes
+        let duration = self.duration.unwrap_or(0).min(MAX_DURATION);
+        if duration <= 0 {
+            return false;
+        }
+        let minute = now
+            - Duration::seconds(now.second() as i64)
+            - Duration::nanoseconds(now.nanosecond() as i64);
+        let earliest = minute - Duration::minutes(duration - 1);
+        let latest = match self.ends_at {
+            Some(end) => minute.min(end),
+            None => minute,
+        };
+        let local = |moment: OffsetDateTime| {
+            let local = moment.to_timezone(tz);
+            PrimitiveDateTime::new(local.date(), local.time())
+        };
+
+        schedule
+            .occurrences_before(
+                local(latest) + OFFSET_MARGIN,
+                local(earliest.max(self.starts_at)) - OFFSET_MARGIN,
+            )
+            .any(|occurrence| {
+                // a wall clock time can happen twice when clocks go back, or not at all
+                let starts = match occurrence.assume_timezone(tz) {
+                    OffsetResult::Some(start) => vec![start],
+                    OffsetResult::Ambiguous(first, second) => vec![first, second],
+                    OffsetResult::None => vec![],
+                };
+                starts
+                    .into_iter()
+                    .any(|start| earliest <= start && start <= minute && in_bounds(start))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct MaintenanceCreate {
+    pub name: String,
+    pub server_ids: Vec<i64>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub starts_at: OffsetDateTime,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub ends_at: Option<OffsetDateTime>,
+    pub schedule: Option<String>, // e.g `0 3 * * 0` for every Sunday at 03:00
+    pub duration: Option<i64>,
+    pub timezone: Option<String>, // e.g `Europe/Berlin`, defaults to UTC
+    pub notify: Option<bool>,
+}
+
+impl MaintenanceCreate {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.server_ids.is_empty() {
+            return Err("maintenance needs at least one server".to_string());
+        }
+
+        if let Some(timezone) = &self.timezone
+            && timezones::get_by_name(timezone).is_none()
+        {
+            return Err(format!("unknown timezone `{timezone}`"));
+        }
+
+        if let Some(ends_at) = self.ends_at
+            && ends_at <= self.starts_at
+        {
+            return Err("ends_at has to be after starts_at".to_string());
+        }
+
+        match &self.schedule {
+            Some(schedule) => {
+                schedule.parse::<CronSchedule>()?;
+                match self.duration {
+                    Some(duration) if (1..=MAX_DURATION).contains(&duration) => Ok(()),
+                    _ => Err(format!(
+                        "recurring maintenance needs a duration of 1 to {MAX_DURATION} minutes"
+                    )),
+                }
+            }
+            None if self.ends_at.is_none() => Err("one-off maintenance needs ends_at".to_string()),
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct MaintenanceBmc;
+
+/// Database interactions
+impl MaintenanceBmc {
+    pub async fn insert(mm: &ModelManager, ctx: &Ctx, mc: MaintenanceCreate) -> Result<i64> {
+        let mut tx = mm.pool.begin().await?;
+
+        let row = sqlx::query(
+            "INSERT INTO maintenance (user_id, name, starts_at, ends_at, schedule, duration, timezone, notify) VALUES (?,?,?,?,?,?,?,?) RETURNING id",
+        )
+        .bind(ctx.user_id)
+        .bind(&mc.name)
+        .bind(mc.starts_at.to_offset(UtcOffset::UTC))
+        .bind(mc.ends_at.map(|end| end.to_offset(UtcOffset::UTC)))
+        .bind(&mc.schedule)
+        .bind(mc.schedule.as_ref().and(mc.duration))
+        .bind(mc.timezone.as_deref().unwrap_or("UTC"))
+        .bind(mc.notify.unwrap_or(false))
+        .fetch_one(&mut *tx)
+        .await?;
+        let id: i64 = row.try_get("id")?;
+
+        for server_id in mc.server_ids.iter().collect::<HashSet<_>>() {
+            sqlx::query("INSERT INTO maintenance_server (maintenance_id, server_id) VALUES (?,?)")
+                .bind(id)
+                .bind(server_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    pub async fn update(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        id: i64,
+        mc: MaintenanceCreate,
+    ) -> Result<()> {
+        let now = time::UtcDateTime::now();
+        let updated_at = PrimitiveDateTime::new(now.date(), now.time());
+        let mut tx = mm.pool.begin().await?;
+
+        sqlx::query(
+            "UPDATE maintenance SET name = ?, starts_at = ?, ends_at = ?, schedule = ?, duration = ?, timezone = ?, notify = ?, updated_at = ? WHERE id = ?",
+        )
+        .bind(&mc.name)
+        .bind(mc.starts_at.to_offset(UtcOffset::UTC))
+        .bind(mc.ends_at.map(|end| end.to_offset(UtcOffset::UTC)))
+        .bind(&mc.schedule)
+        .bind(mc.schedule.as_ref().and(mc.duration))
+        .bind(mc.timezone.as_deref().unwrap_or("UTC"))
+        .bind(mc.notify.unwrap_or(false))
+        .bind(updated_at)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM maintenance_server WHERE maintenance_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        for server_id in mc.server_ids.iter().collect::<HashSet<_>>() {
+            sqlx::query("INSERT INTO maintenance_server (maintenance_id, server_id) VALUES (?,?)")
+                .bind(id)
+                .bind(server_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get(mm: &ModelManager, _ctx: &Ctx, id: i64) -> Result<Option<Maintenance>> {
+        let result =
+            sqlx::query_as::<Sqlite, Maintenance>("SELECT * FROM maintenance WHERE id = ?")
+                .bind(id)
+                .fetch_one(&mm.pool)
+                .await;
+
+        if let Err(sqlx::Error::RowNotFound) = result {
+            return Ok(None);
+        }
+
+        let mut maintenance = result?;
+        maintenance.server_ids = Self::server_ids(mm, id).await?;
+        Ok(Some(maintenance))
+    }
+
+    pub async fn delete(mm: &ModelManager, _ctx: &Ctx, id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM maintenance WHERE id = ?")
+            .bind(id)
+            .execute(&mm.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn all(mm: &ModelManager, _ctx: &Ctx) -> Result<Vec<Maintenance>> {
+        let windows = sqlx::query_as::<Sqlite, Maintenance>("SELECT * FROM maintenance")
+            .fetch_all(&mm.pool)
+            .await?;
+
+        Self::with_server_ids(mm, windows).await
+    }
+
+    async fn server_ids(mm: &ModelManager, id: i64) -> Result<Vec<i64>> {
+        let rows = sqlx::query(
+            "SELECT server_id FROM maintenance_server WHERE maintenance_id = ? ORDER BY server_id",
+        )
+        .bind(id)
+        .fetch_all(&mm.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| row.try_get("server_id").map_err(Into::into))
+            .collect()
+    }
+
+    async fn with_server_ids(
+        mm: &ModelManager,
+        mut windows: Vec<Maintenance>,
+    ) -> Result<Vec<Maintenance>> {
+        for window in &mut windows {
+            window.server_ids = Self::server_ids(mm, window.id).await?;
+        }
+
+        Ok(windows)
+    }
+}
+
+// Listing API
+impl MaintenanceBmc {
+    pub async fn count(mm: &ModelManager, ctx: &Ctx) -> Result<i64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM maintenance WHERE user_id = ?")
+            .bind(ctx.user_id)
+            .fetch_one(&mm.pool)
+            .await?;
+
+        let count = row.try_get("count")?;
+        Ok(count)
+    }
+
+    pub async fn list(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<Maintenance>> {
+        let windows = sqlx::query_as::<Sqlite, Maintenance>(
+            "SELECT * FROM maintenance WHERE user_id = ? LIMIT ? OFFSET ?",
+        )
+        .bind(ctx.user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&mm.pool)
+        .await?;
+
+        Self::with_server_ids(mm, windows).await
+    }
+
+    pub async fn page(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Page<Maintenance>> {
+        let items = Self::list(mm, ctx, offset, limit).await?;
+        let count = Self::count(mm, ctx).await?;
+
+        Ok(Page::new(items, count, limit, offset))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn window(schedule: Option<&str>, timezone: &str) -> Maintenance {
+        Maintenance {
+            id: 1,
+            user_id: 1,
+            name: "window".to_string(),
+            starts_at: datetime!(2026-01-01 00:00 UTC),
+            ends_at: None,
+            schedule: schedule.map(str::to_string),
+            duration: Some(60),
+            timezone: timezone.to_string(),
+            notify: false,
+            server_ids: vec![1],
+            created_at: datetime!(2026-01-01 00:00),
+            updated_at: datetime!(2026-01-01 00:00),
+        }
+    }
+
+    #[test]
+    fn test_maintenance_one_off() {
+        let mut window = window(None, "UTC");
+        window.ends_at = Some(datetime!(2026-01-01 02:00 UTC));
+
+        assert!(window.is_active(datetime!(2026-01-01 01:59:59 UTC)));
+        assert!(!window.is_active(datetime!(2026-01-01 02:00 UTC)));
+        assert!(!window.is_active(datetime!(2025-12-31 23:59 UTC)));
+    }
+
+    #[test]
+    fn test_maintenance_recurring_timezone() {
+        // every day 03:00-04:00 in Berlin, which is UTC+2 in summer and UTC+1 in winter
+        let window = window(Some("0 3 * * *"), "Europe/Berlin");
+
+        assert!(window.is_active(datetime!(2026-07-01 01:00 UTC)));
+        assert!(window.is_active(datetime!(2026-07-01 01:59:30 UTC)));
+        assert!(!window.is_active(datetime!(2026-07-01 02:00 UTC)));
+        assert!(!window.is_active(datetime!(2026-07-01 03:00 UTC)));
+
+        assert!(window.is_active(datetime!(2026-12-01 02:30 UTC)));
+        assert!(!window.is_active(datetime!(2026-12-01 01:30 UTC)));
+    }
+
+    #[test]
+    fn test_maintenance_recurring_long() {
+        // every Monday at midnight for a whole week, so always on once it started
+        let mut window = window(Some("0 0 * * 1"), "UTC");
+        window.duration = Some(MAX_DURATION);
+
+        assert!(!window.is_active(datetime!(2026-01-04 12:00 UTC)));
+        assert!(window.is_active(datetime!(2026-01-05 00:00 UTC)));
+        assert!(window.is_active(datetime!(2026-01-11 23:59 UTC)));
+        assert!(window.is_active(datetime!(2026-01-12 00:00 UTC)));
+
+        window.ends_at = Some(datetime!(2026-01-08 00:00 UTC));
+        assert!(window.is_active(datetime!(2026-01-09 00:00 UTC)));
+        assert!(!window.is_active(datetime!(2026-01-12 00:00 UTC)));
+    }
+
+    #[test]
+    fn test_maintenance_recurring_skipped_time() {
+        // 02:30 doesn't happen in Berlin the day clocks go forward
+        let window = window(Some("30 2 * * *"), "Europe/Berlin");
+
+        assert!(window.is_active(datetime!(2026-03-28 01:45 UTC)));
+        assert!(!window.is_active(datetime!(2026-03-29 00:45 UTC)));
+        assert!(!window.is_active(datetime!(2026-03-29 01:45 UTC)));
+        assert!(window.is_active(datetime!(2026-03-30 00:45 UTC)));
+    }
+
+    #[test]
+    fn test_maintenance_validate() {
+        let mc = MaintenanceCreate {
+            name: "backup".to_string(),
+            server_ids: vec![1],
+            starts_at: datetime!(2026-01-01 00:00 UTC),
+            ends_at: None,
+            schedule: Some("0 3 * * *".to_string()),
+            duration: Some(30),
+            timezone: Some("Europe/Berlin".to_string()),
+            notify: None,
+        };
+        assert!(mc.validate().is_ok());
+
+        assert!(
+            MaintenanceCreate {
+                duration: None,
+                ..mc.clone()
+            }
+            .validate()
+            .is_err()
+        );
+        assert!(
+            MaintenanceCreate {
+                schedule: None,
+                ..mc.clone()
+            }
+            .validate()
+            .is_err()
+        );
+        assert!(
+            MaintenanceCreate {
+                timezone: Some("Mars/Olympus".to_string()),
+                ..mc.clone()
+            }
+            .validate()
+            .is_err()
+        );
+        assert!(
+            MaintenanceCreate {
+                server_ids: vec![],
+                ..mc
+            }
+            .validate()
+            .is_err()
+        );
+    }
+}
@@ -1,22 +1,48 @@
+mod check_result;
+mod cron;
 mod error;
+mod escalation;
+mod incident;
+mod maintenance;
 mod notifier;
+mod retention;
+mod rollup;
 mod server;
 mod server_log;
+mod status_codes;
+mod uptime;
 mod user;
 mod user_action;
 
 mod utils;
 pub use utils::Page;
 
-pub use notifier::{Notifier, NotifierBmc, NotifierCreate};
-pub use server::{Server, ServerBmc, ServerCreate};
-pub use server_log::{ServerLog, ServerLogBmc, ServerLogCreate, ServerLogLine};
+pub use check_result::{CheckResult, CheckResultBmc, CheckResultCreate};
+pub use cron::CronSchedule;
+pub use escalation::{
+    EscalationPolicy, EscalationPolicyBmc, EscalationPolicyCreate, EscalationStep,
+};
+pub use incident::{
+    Incident, IncidentBmc, IncidentCreate, IncidentFilter, IncidentNote, IncidentNoteCreate,
+    IncidentUpdate,
+};
+pub use maintenance::{Maintenance, MaintenanceBmc, MaintenanceCreate};
+pub use notifier::{DEFAULT_REMIND_LIMIT, Notifier, NotifierBmc, NotifierCreate};
+pub use retention::{ExpiredRows, Expiry, RetainedTable, RetentionBmc, expiries};
+pub use rollup::{LatencyHistogram, Rollup, RollupBmc, RollupDelta, RollupPeriod};
+pub use server::{
+    RecordMode, Server, ServerBmc, ServerCreate, ServerHeader, ServerKind, ServerState,
+    dependency_cycle,
+};
+pub use server_log::{PreviousState, ServerLog, ServerLogBmc, ServerLogCreate, ServerLogLine};
+pub use status_codes::StatusCodes;
+pub use uptime::{MAX_WINDOW, Uptime, parse_window};
 pub use user::{User, UserBmc, UserClaims, UserCreate, UserRole};
 pub use user_action::{UserAction, UserActionLog, UserActionLogBmc, UserActionLogCreate};
 
 pub use error::{ModelError, Result};
 
-use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::sqlite::{SqliteAutoVacuum, SqliteConnectOptions};
 use sqlx::{Pool, Sqlite};
 use std::path::Path;
 
@@ -38,8 +64,10 @@ impl ModelManager {
                 .to_path_buf();
         }
 
+        // only takes effect on new databases, existing ones need a `VACUUM` to switch
         let pool_opt = SqliteConnectOptions::new()
             .create_if_missing(true)
+            .auto_vacuum(SqliteAutoVacuum::Incremental)
             .thread_name(|x| format!("SqliteDB_{:x}", x))
             .filename(final_path);
 
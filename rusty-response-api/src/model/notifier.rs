@@ -4,7 +4,7 @@ use super::Result;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::{FromRow, Row, Sqlite};
-use time::PrimitiveDateTime;
+use time::{Duration, PrimitiveDateTime};
 
 #[derive(Debug, Clone, FromRow, Serialize)]
 pub struct Notifier {
@@ -15,10 +15,25 @@ pub struct Notifier {
     pub credentials: Value,
     pub format: String,
     pub active: bool,
+    /// Minutes between reminders while the server stays down, `None` for no reminders
+    pub remind_every: Option<i64>,
+    /// Reminders sent per incident at most
+    pub remind_limit: i64,
     pub created_at: PrimitiveDateTime,
     pub updated_at: PrimitiveDateTime,
 }
 
+impl Notifier {
+    /// Reminders due `elapsed` into an outage, capped by `remind_limit`
+    pub fn reminders_due(&self, elapsed: Duration) -> i64 {
+        let Some(every) = self.remind_every.filter(|every| *every > 0) else {
+            return 0;
+        };
+
+        (elapsed.whole_minutes() / every).clamp(0, self.remind_limit.max(0))
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct NotifierCreate {
     pub server_id: i64,
@@ -26,6 +41,8 @@ pub struct NotifierCreate {
     pub credentials: Value, // JSON object from request body
     pub format: String,
     pub active: Option<bool>,
+    pub remind_every: Option<i64>, // e.g every 30 minutes
+    pub remind_limit: Option<i64>, // up to 12 times
 }
 
 impl NotifierCreate {
@@ -42,10 +59,26 @@ impl NotifierCreate {
             credentials: serde_json::from_str(&credentials)?,
             format,
             active,
+            remind_every: None,
+            remind_limit: None,
         })
     }
+
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.remind_every.is_some_and(|every| every < 1) {
+            return Err("remind_every has to be at least 1 minute".to_string());
+        }
+        if self.remind_limit.is_some_and(|limit| limit < 0) {
+            return Err("remind_limit can't be negative".to_string());
+        }
+
+        Ok(())
+    }
 }
 
+/// Reminders sent per incident when the notifier doesn't say otherwise
+pub const DEFAULT_REMIND_LIMIT: i64 = 12;
+
 pub struct NotifierBmc;
 
 impl NotifierBmc {
@@ -87,12 +120,14 @@ impl NotifierBmc {
     ) -> Result<PrimitiveDateTime> {
         let now = time::UtcDateTime::now();
         let updated_at = PrimitiveDateTime::new(now.date(), now.time());
-        let row = sqlx::query("UPDATE notifier SET server_id = ?, provider = ?, credentials = ?, format = ?, active = ?, updated_at = ? WHERE id = ? RETURNING updated_at")
+        let row = sqlx::query("UPDATE notifier SET server_id = ?, provider = ?, credentials = ?, format = ?, active = ?, remind_every = ?, remind_limit = ?, updated_at = ? WHERE id = ? RETURNING updated_at")
             .bind(nfc.server_id)
             .bind(&nfc.provider)
             .bind(&nfc.credentials)
             .bind(&nfc.format)
             .bind(nfc.active)
+            .bind(nfc.remind_every)
+            .bind(nfc.remind_limit.unwrap_or(DEFAULT_REMIND_LIMIT))
             .bind(updated_at)
             .bind(notifier_id)
             .fetch_one(&mm.pool)
@@ -120,9 +155,11 @@ impl NotifierBmc {
         let credentials = nc.credentials;
         let format = nc.format;
         let active = nc.active.unwrap_or(false);
+        let remind_every = nc.remind_every;
+        let remind_limit = nc.remind_limit.unwrap_or(DEFAULT_REMIND_LIMIT);
 
         let row = sqlx::query(
-            "INSERT INTO notifier (user_id, server_id, provider, credentials, format, active) VALUES (?,?,?,?,?,?) RETURNING id, created_at, updated_at;"
+            "INSERT INTO notifier (user_id, server_id, provider, credentials, format, active, remind_every, remind_limit) VALUES (?,?,?,?,?,?,?,?) RETURNING id, created_at, updated_at;"
         )
         .bind(user_id)
         .bind(server_id)
@@ -130,6 +167,8 @@ impl NotifierBmc {
         .bind(&credentials)
         .bind(&format)
         .bind(active)
+        .bind(remind_every)
+        .bind(remind_limit)
         .fetch_one(&mm.pool)
         .await?;
 
@@ -145,6 +184,8 @@ impl NotifierBmc {
             credentials,
             active,
             format,
+            remind_every,
+            remind_limit,
             created_at,
             updated_at,
         };
@@ -279,3 +320,39 @@ impl NotifierBmc {
         Ok(Page::new(items, count, limit, offset))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn notifier(remind_every: Option<i64>, remind_limit: i64) -> Notifier {
+        Notifier {
+            id: 1,
+            user_id: 1,
+            server_id: 1,
+            provider: "telegram".to_string(),
+            credentials: json!({}),
+            format: "{{server.name}}".to_string(),
+            active: true,
+            remind_every,
+            remind_limit,
+            created_at: datetime!(2026-01-01 00:00),
+            updated_at: datetime!(2026-01-01 00:00),
+        }
+    }
+
+    #[test]
+    fn test_reminders_due() {
+        let every_30 = notifier(Some(30), 3);
+        assert_eq!(every_30.reminders_due(Duration::minutes(29)), 0);
+        assert_eq!(every_30.reminders_due(Duration::minutes(30)), 1);
+        assert_eq!(every_30.reminders_due(Duration::minutes(95)), 3);
+        // capped by the limit however long the outage lasts
+        assert_eq!(every_30.reminders_due(Duration::days(2)), 3);
+
+        assert_eq!(notifier(None, 3).reminders_due(Duration::days(2)), 0);
+    }
+}
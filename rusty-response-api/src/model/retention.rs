@@ -0,0 +1,359 @@
+//! Pruning of rows older than their retention.
+//!
+//! Expired rows are deleted in batches bounded by `rowid`, which every pruned table has. Rows
+//! only ever expire, so a batch selected for archiving is exactly the one deleted afterwards.
+
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{QueryBuilder, Sqlite};
+use time::{Duration, OffsetDateTime};
+use utoipa::ToSchema;
+
+use super::Result;
+use crate::{
+    ModelManager,
+    config::Retention,
+    model::{CheckResult, Ctx, Rollup, ServerLog, UserActionLog},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RetainedTable {
+    ServerLog,
+    CheckResult,
+    UserActionLog,
+    HourlyRollup,
+}
+
+impl RetainedTable {
+    pub fn table(self) -> &'static str {
+        match self {
+            Self::ServerLog => "server_log",
+            Self::CheckResult => "check_result",
+            Self::UserActionLog => "user_action_log",
+            Self::HourlyRollup => "rollup",
+        }
+    }
+
+    fn created_at(self) -> &'static str {
+        match self {
+            Self::ServerLog | Self::UserActionLog => "created_at",
+            Self::CheckResult => "checked_at",
+            Self::HourlyRollup => "bucket_start",
+        }
+    }
+
+    /// Rows of the table the retention applies to
+    fn scope(self) -> &'static str {
+        match self {
+            Self::HourlyRollup => "period = 'hour'",
+            _ => "1",
+        }
+    }
+}
+
+/// Rows of `table` older than `cutoff`, only those of `server_id` if it's set. The newest
+/// `server_log` line of a server never expires
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Expiry {
+    pub table: RetainedTable,
+    pub server_id: Option<i64>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub cutoff: OffsetDateTime,
+}
+
+impl Expiry {
+    pub fn new(table: RetainedTable, server_id: Option<i64>, cutoff: OffsetDateTime) -> Self {
+        Self {
+            table,
+            server_id,
+            cutoff,
+        }
+    }
+
+    fn push_filter(&self, qb: &mut QueryBuilder<'_, Sqlite>) {
+        qb.push(" WHERE ")
+            .push(self.table.scope())
+            .push(" AND ")
+            .push(self.table.created_at())
+            .push(" < datetime(")
+            .push_bind(self.cutoff)
+            .push(")");
+        if let Some(server_id) = self.server_id {
+            qb.push(" AND server_id = ").push_bind(server_id);
+        }
+
+        // the newest line holds the current state of the server, however old it is. Both look
+        // it up through the `server_id` index instead of grouping the whole table
+        if self.table == RetainedTable::ServerLog {
+            match self.server_id {
+                Some(server_id) => qb
+                    .push(" AND id < (SELECT MAX(id) FROM server_log WHERE server_id = ")
+                    .push_bind(server_id)
+                    .push(")"),
+                None => qb.push(
+                    " AND id < (SELECT MAX(latest.id) FROM server_log AS latest \
+                    WHERE latest.server_id = server_log.server_id)",
+                ),
+            };
+        }
+    }
+}
+
+/// Expiries of every retained table, `servers` pairs ids with their `log_retention_days`
+pub fn expiries(
+    retention: &Retention,
+    servers: &[(i64, Option<i64>)],
+    now: OffsetDateTime,
+) -> Vec<Expiry> {
+    // 0 days keeps the rows forever
+    let cutoff = |days: i64| (days > 0).then(|| now - Duration::days(days));
+    let mut expiries = vec![];
+
+    for (server_id, days) in servers {
+        let logs = [
+            (RetainedTable::ServerLog, retention.server_log_days()),
+            (RetainedTable::CheckResult, retention.check_result_days()),
+        ];
+        for (table, default) in logs {
+            if let Some(cutoff) = cutoff(days.unwrap_or(default)) {
+                expiries.push(Expiry::new(table, Some(*server_id), cutoff));
+            }
+        }
+    }
+
+    let global = [
+        (
+            RetainedTable::UserActionLog,
+            retention.user_action_log_days(),
+        ),
+        (RetainedTable::HourlyRollup, retention.hourly_rollup_days()),
+    ];
+    for (table, days) in global {
+        if let Some(cutoff) = cutoff(days) {
+            expiries.push(Expiry::new(table, None, cutoff));
+        }
+    }
+
+    expiries
+}
+
+/// Rows of a table that are expired and would be pruned by the next run
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ExpiredRows {
+    pub table: RetainedTable,
+    pub rows: i64,
+}
+
+pub struct RetentionBmc;
+
+impl RetentionBmc {
+    /// Expiries of every retained table as of `now`
+    pub async fn expiries(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        retention: &Retention,
+        now: OffsetDateTime,
+    ) -> Result<Vec<Expiry>> {
+        let servers = sqlx::query_as::<Sqlite, (i64, Option<i64>)>(
+            "SELECT id, log_retention_days FROM server",
+        )
+        .fetch_all(&mm.pool)
+        .await?;
+
+        Ok(expiries(retention, &servers, now))
+    }
+
+    /// Expired rows per table, nothing is deleted
+    pub async fn report(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        expiries: &[Expiry],
+    ) -> Result<Vec<ExpiredRows>> {
+        let mut report: Vec<ExpiredRows> = vec![];
+        for expiry in expiries {
+            let rows = Self::count(mm, ctx, expiry).await?;
+            match report
+                .iter_mut()
+                .find(|expired| expired.table == expiry.table)
+            {
+                Some(expired) => expired.rows += rows,
+                None => report.push(ExpiredRows {
+                    table: expiry.table,
+                    rows,
+                }),
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+impl RetentionBmc {
+    pub async fn count(mm: &ModelManager, _ctx: &Ctx, expiry: &Expiry) -> Result<i64> {
+        let mut qb = QueryBuilder::new(format!("SELECT COUNT(*) FROM {}", expiry.table.table()));
+        expiry.push_filter(&mut qb);
+
+        let count = qb.build_query_scalar().fetch_one(&mm.pool).await?;
+        Ok(count)
+    }
+
+    /// Last `rowid` of the next batch of at most `limit` expired rows, `None` if none is left
+    pub async fn batch_end(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        expiry: &Expiry,
+        limit: i64,
+    ) -> Result<Option<i64>> {
+        let mut qb = QueryBuilder::new(format!(
+            "SELECT MAX(rowid) FROM (SELECT rowid FROM {}",
+            expiry.table.table()
+        ));
+        expiry.push_filter(&mut qb);
+        qb.push(" ORDER BY rowid LIMIT ").push_bind(limit).push(")");
+
+        let end = qb.build_query_scalar().fetch_one(&mm.pool).await?;
+        Ok(end)
+    }
+
+    /// Expired rows up to `rowid` of `end`, oldest first
+    pub async fn rows(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        expiry: &Expiry,
+        end: i64,
+    ) -> Result<Vec<Value>> {
+        let mut qb = QueryBuilder::new(format!("SELECT * FROM {}", expiry.table.table()));
+        expiry.push_filter(&mut qb);
+        qb.push(" AND rowid <= ")
+            .push_bind(end)
+            .push(" ORDER BY rowid");
+
+        let rows = match expiry.table {
+            RetainedTable::ServerLog => {
+                to_values(qb.build_query_as::<ServerLog>().fetch_all(&mm.pool).await?)
+            }
+            RetainedTable::CheckResult => to_values(
+                qb.build_query_as::<CheckResult>()
+                    .fetch_all(&mm.pool)
+                    .await?,
+            ),
+            RetainedTable::UserActionLog => to_values(
+                qb.build_query_as::<UserActionLog>()
+                    .fetch_all(&mm.pool)
+                    .await?,
+            ),
+            RetainedTable::HourlyRollup => {
+                to_values(qb.build_query_as::<Rollup>().fetch_all(&mm.pool).await?)
+            }
+        }?;
+
+        Ok(rows)
+    }
+
+    /// Deletes expired rows up to `rowid` of `end`, returns how many were deleted
+    pub async fn delete(mm: &ModelManager, _ctx: &Ctx, expiry: &Expiry, end: i64) -> Result<u64> {
+        let mut qb = QueryBuilder::new(format!("DELETE FROM {}", expiry.table.table()));
+        expiry.push_filter(&mut qb);
+        qb.push(" AND rowid <= ").push_bind(end);
+
+        let result = qb.build().execute(&mm.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Whether the database uses incremental vacuum
+    pub async fn is_incremental(mm: &ModelManager, _ctx: &Ctx) -> Result<bool> {
+        // 2 stands for `INCREMENTAL`
+        let mode: i64 = sqlx::query_scalar("PRAGMA auto_vacuum")
+            .fetch_one(&mm.pool)
+            .await?;
+        Ok(mode == 2)
+    }
+
+    /// Switches the database to incremental vacuum, rebuilding it with a full `VACUUM`
+    pub async fn enable_incremental_vacuum(mm: &ModelManager, _ctx: &Ctx) -> Result<()> {
+        // the mode is only applied by a `VACUUM` on the same connection
+        let mut conn = mm.pool.acquire().await?;
+        sqlx::query("PRAGMA auto_vacuum = INCREMENTAL")
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("VACUUM").execute(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Releases up to `pages` free pages, a no-op unless the database uses incremental vacuum
+    pub async fn incremental_vacuum(mm: &ModelManager, ctx: &Ctx, pages: i64) -> Result<()> {
+        if !Self::is_incremental(mm, ctx).await? {
+            return Ok(());
+        }
+
+        sqlx::query(&format!("PRAGMA incremental_vacuum({})", pages.max(0)))
+            .execute(&mm.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+fn to_values<T: Serialize>(rows: Vec<T>) -> Result<Vec<Value>> {
+    let values = rows
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<std::result::Result<_, _>>()?;
+    Ok(values)
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn test_expiries_overrides() {
+        let retention = Retention::default();
+        let now = datetime!(2026-10-17 12:00 UTC);
+        // default, overridden and kept forever
+        let servers = [(1, None), (2, Some(7)), (3, Some(0))];
+
+        let expiries = expiries(&retention, &servers, now);
+        let cutoff = |table, server_id| {
+            expiries
+                .iter()
+                .find(|e| e.table == table && e.server_id == server_id)
+                .map(|e| e.cutoff)
+        };
+
+        assert_eq!(
+            cutoff(RetainedTable::ServerLog, Some(1)),
+            Some(now - Duration::days(retention.server_log_days()))
+        );
+        assert_eq!(
+            cutoff(RetainedTable::CheckResult, Some(2)),
+            Some(now - Duration::days(7))
+        );
+        assert_eq!(cutoff(RetainedTable::ServerLog, Some(3)), None);
+        assert_eq!(
+            cutoff(RetainedTable::UserActionLog, None),
+            Some(now - Duration::days(retention.user_action_log_days()))
+        );
+    }
+
+    #[test]
+    fn test_server_log_keeps_newest_line() {
+        let cutoff = datetime!(2026-10-17 12:00 UTC);
+        let sql = |server_id| {
+            let mut qb = QueryBuilder::new("DELETE FROM server_log");
+            Expiry::new(RetainedTable::ServerLog, server_id, cutoff).push_filter(&mut qb);
+            qb.sql().to_string()
+        };
+
+        assert!(sql(Some(1)).ends_with(
+            "AND server_id = ? AND id < (SELECT MAX(id) FROM server_log WHERE server_id = ?)"
+        ));
+        assert!(sql(None).contains("WHERE latest.server_id = server_log.server_id"));
+
+        let mut qb = QueryBuilder::new("DELETE FROM check_result");
+        Expiry::new(RetainedTable::CheckResult, Some(1), cutoff).push_filter(&mut qb);
+        assert!(!qb.sql().contains("MAX(id)"));
+    }
+}
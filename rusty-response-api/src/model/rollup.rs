@@ -0,0 +1,290 @@
+//! Hourly and daily aggregates of probe outcomes.
+//!
+//! Hourly rows are merged with the outcomes collected since the last flush, daily rows are
+//! rebuilt from the hourly ones, so neither is counted twice whatever restarts happen in between.
+//! Latencies are kept in a histogram with logarithmic buckets, percentiles are accurate to
+//! [`GROWTH`], and histograms of any two periods can be merged.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Sqlite, types::Json};
+use time::{OffsetDateTime, PrimitiveDateTime, Time};
+use utoipa::ToSchema;
+
+use super::Result;
+use crate::{ModelManager, model::Ctx};
+
+/// Ratio between upper bounds of neighbouring histogram buckets
+pub const GROWTH: f64 = 1.05;
+
+/// Latency distribution in milliseconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatencyHistogram {
+    pub count: u64,
+    pub sum: u64,
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+    /// Samples per bucket, bucket `i` holds latencies up to `GROWTH^i`
+    buckets: BTreeMap<u32, u64>,
+}
+
+impl LatencyHistogram {
+    fn bucket(ms: u64) -> u32 {
+        if ms <= 1 {
+            return 0;
+        }
+        ((ms as f64).ln() / GROWTH.ln()).ceil() as u32
+    }
+
+    pub fn record(&mut self, ms: u64) {
+        self.count += 1;
+        self.sum += ms;
+        self.min = Some(self.min.map_or(ms, |min| min.min(ms)));
+        self.max = Some(self.max.map_or(ms, |max| max.max(ms)));
+        *self.buckets.entry(Self::bucket(ms)).or_default() += 1;
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.into_iter().chain(other.min).min();
+        self.max = self.max.into_iter().chain(other.max).max();
+        for (bucket, count) in &other.buckets {
+            *self.buckets.entry(*bucket).or_default() += count;
+        }
+    }
+
+    pub fn avg(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+
+    /// Latency `quantile` of the samples are at most, e.g `0.95` for p95
+    pub fn quantile(&self, quantile: f64) -> Option<u64> {
+        let (min, max) = (self.min?, self.max?);
+        let rank = ((quantile * self.count as f64).ceil() as u64).max(1);
+
+        let mut seen = 0;
+        for (bucket, count) in &self.buckets {
+            seen += count;
+            if seen >= rank {
+                let upper = GROWTH.powi(*bucket as i32).round() as u64;
+                return Some(upper.clamp(min, max));
+            }
+        }
+        Some(max)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum RollupPeriod {
+    Hour,
+    Day,
+}
+
+impl RollupPeriod {
+    /// Start of the period `at` falls in, in UTC
+    pub fn start_of(self, at: OffsetDateTime) -> PrimitiveDateTime {
+        let at = at.to_offset(time::UtcOffset::UTC);
+        let time = match self {
+            Self::Hour => Time::from_hms(at.hour(), 0, 0).expect("valid hour"),
+            Self::Day => Time::MIDNIGHT,
+        };
+        PrimitiveDateTime::new(at.date(), time)
+    }
+}
+
+/// Outcomes collected for a single server and period
+#[derive(Debug, Clone, Default)]
+pub struct RollupDelta {
+    pub count: i64,
+    pub failures: i64,
+    pub latency: LatencyHistogram,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, ToSchema)]
+pub struct Rollup {
+    pub server_id: i64,
+    pub period: RollupPeriod,
+    pub bucket_start: PrimitiveDateTime,
+    /// Probes in the bucket
+    pub count: i64,
+    /// Probes that found the server unreachable
+    pub failures: i64,
+    pub latency_min: Option<i64>,
+    pub latency_avg: Option<f64>,
+    pub latency_max: Option<i64>,
+    pub latency_p50: Option<i64>,
+    pub latency_p95: Option<i64>,
+    pub latency_p99: Option<i64>,
+    #[serde(skip)]
+    #[schema(ignore)]
+    pub histogram: Json<LatencyHistogram>,
+}
+
+pub struct RollupBmc;
+
+impl RollupBmc {
+    async fn upsert<'e, E>(
+        executor: E,
+        server_id: i64,
+        period: RollupPeriod,
+        bucket_start: PrimitiveDateTime,
+        delta: &RollupDelta,
+    ) -> Result<()>
+    where
+        E: sqlx::Executor<'e, Database = Sqlite>,
+    {
+        let latency = &delta.latency;
+        let quantile = |q| latency.quantile(q).map(|ms| ms as i64);
+
+        sqlx::query(
+            "INSERT INTO rollup (server_id, period, bucket_start, count, failures, latency_min, latency_avg, latency_max, latency_p50, latency_p95, latency_p99, histogram) \
+            VALUES (?, ?, datetime(?), ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+            ON CONFLICT (server_id, period, bucket_start) DO UPDATE SET count = excluded.count, failures = excluded.failures, latency_min = excluded.latency_min, latency_avg = excluded.latency_avg, latency_max = excluded.latency_max, \
+            latency_p50 = excluded.latency_p50, latency_p95 = excluded.latency_p95, latency_p99 = excluded.latency_p99, histogram = excluded.histogram",
+        )
+        .bind(server_id)
+        .bind(period)
+        .bind(bucket_start)
+        .bind(delta.count)
+        .bind(delta.failures)
+        .bind(latency.min.map(|ms| ms as i64))
+        .bind(latency.avg())
+        .bind(latency.max.map(|ms| ms as i64))
+        .bind(quantile(0.5))
+        .bind(quantile(0.95))
+        .bind(quantile(0.99))
+        .bind(Json(latency))
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Adds `delta` to the hourly bucket and rebuilds the daily one containing it
+    pub async fn merge_hour(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        server_id: i64,
+        hour_start: PrimitiveDateTime,
+        delta: RollupDelta,
+    ) -> Result<()> {
+        let mut tx = mm.pool.begin().await?;
+
+        let stored = sqlx::query_as::<Sqlite, (i64, i64, Json<LatencyHistogram>)>(
+            "SELECT count, failures, histogram FROM rollup WHERE server_id = ? AND period = 'hour' AND bucket_start = datetime(?)",
+        )
+        .bind(server_id)
+        .bind(hour_start)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let mut hour = delta;
+        if let Some((count, failures, Json(latency))) = stored {
+            hour.count += count;
+            hour.failures += failures;
+            hour.latency.merge(&latency);
+        }
+        Self::upsert(&mut *tx, server_id, RollupPeriod::Hour, hour_start, &hour).await?;
+
+        let day_start = PrimitiveDateTime::new(hour_start.date(), Time::MIDNIGHT);
+        let hours = sqlx::query_as::<Sqlite, (i64, i64, Json<LatencyHistogram>)>(
+            "SELECT count, failures, histogram FROM rollup WHERE server_id = ? AND period = 'hour' AND bucket_start >= datetime(?) AND bucket_start < datetime(?, '+1 day')",
+        )
+        .bind(server_id)
+        .bind(day_start)
+        .bind(day_start)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut day = RollupDelta::default();
+        for (count, failures, Json(latency)) in hours {
+            day.count += count;
+            day.failures += failures;
+            day.latency.merge(&latency);
+        }
+        Self::upsert(&mut *tx, server_id, RollupPeriod::Day, day_start, &day).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Buckets of the server starting within `from..to`, oldest first
+    pub async fn list(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        server_id: i64,
+        period: RollupPeriod,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> Result<Vec<Rollup>> {
+        let rollups = sqlx::query_as::<Sqlite, Rollup>(
+            "SELECT * FROM rollup WHERE server_id = ? AND period = ? AND bucket_start >= datetime(?) AND bucket_start < datetime(?) ORDER BY bucket_start",
+        )
+        .bind(server_id)
+        .bind(period)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&mm.pool)
+        .await?;
+
+        Ok(rollups)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_histogram_quantiles() {
+        let mut histogram = LatencyHistogram::default();
+        for ms in 1..=1000 {
+            histogram.record(ms);
+        }
+
+        assert_eq!(histogram.avg(), Some(500.5));
+        for (quantile, exact) in [(0.5, 500.0), (0.95, 950.0), (0.99, 990.0)] {
+            let estimate = histogram.quantile(quantile).unwrap() as f64;
+            assert!((estimate - exact).abs() / exact <= GROWTH - 1.0);
+        }
+        assert_eq!(histogram.quantile(1.0), Some(1000));
+        assert_eq!(LatencyHistogram::default().quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_histogram_merge() {
+        let mut first = LatencyHistogram::default();
+        let mut second = LatencyHistogram::default();
+        let mut both = LatencyHistogram::default();
+
+        for ms in [3, 40, 500] {
+            first.record(ms);
+            both.record(ms);
+        }
+        for ms in [7, 40, 9000] {
+            second.record(ms);
+            both.record(ms);
+        }
+
+        first.merge(&second);
+        assert_eq!(first, both);
+    }
+
+    #[test]
+    fn test_period_start() {
+        let at = time::macros::datetime!(2026-10-17 13:45:12 +02:00);
+        assert_eq!(
+            RollupPeriod::Hour.start_of(at),
+            time::macros::datetime!(2026-10-17 11:00:00)
+        );
+        assert_eq!(
+            RollupPeriod::Day.start_of(at),
+            time::macros::datetime!(2026-10-17 00:00:00)
+        );
+    }
+}
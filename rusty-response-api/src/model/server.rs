@@ -1,14 +1,99 @@
 use eyre::Result;
+use std::collections::{HashMap, HashSet};
+
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 use sqlx::prelude::FromRow;
+use sqlx::types::Json;
 use sqlx::{Row, Sqlite};
-use time::PrimitiveDateTime;
+use time::{OffsetDateTime, PrimitiveDateTime};
 use utoipa::ToSchema;
 
-use crate::model::Page;
+use crate::{
+    Settings,
+    crypt::SecretController,
+    model::{Page, StatusCodes},
+};
 
 use super::{Ctx, ModelManager};
 
+/// Kind of check performed against the server.
+///
+/// For `http` the `url` is requested as is, for `tcp` it holds a `host:port` pair
+/// (optionally prefixed with `tcp://`) a connection is opened to, for `dns` it's the name to resolve,
+/// for `tls` it's the `host[:port]` whose certificate is verified, for `heartbeat` it's informational only,
+/// the server is fed by pings to `/api/v1/heartbeat/{heartbeat_token}`.
+/// Kind specific settings live in `check_options`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum ServerKind {
+    #[default]
+    Http,
+    Tcp,
+    Dns,
+    Tls,
+    Heartbeat,
+}
+
+/// State of the server as seen by the monitoring backend.
+///
+/// `warning` means the server is reachable, but something needs attention soon,
+/// e.g a certificate that is about to expire, `degraded` that it answers slower than its threshold.
+/// `maintenance` is never probed, it's shown while a maintenance window covers the server,
+/// `flapping` is only logged when the server starts bouncing between states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum ServerState {
+    Online,
+    Warning,
+    Degraded,
+    Unreachable,
+    Maintenance,
+    Flapping,
+}
+
+/// Which checks of the server are written to the `check_result` table.
+///
+/// State transitions are always recorded, `every` adds every other check as well,
+/// `sampled` one out of `record_sample` checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum RecordMode {
+    #[default]
+    Transitions,
+    Every,
+    Sampled,
+}
+
+/// Placeholder returned by the API instead of secret header values
+pub const REDACTED: &str = "********";
+
+/// Header sent with `http` checks.
+///
+/// Values of `secret` headers are stored encrypted and never returned by the API,
+/// sending [`REDACTED`] back on update keeps the stored value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ServerHeader {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub secret: bool,
+}
+
+impl ServerHeader {
+    /// Plain value of the header, ready to be sent
+    pub fn reveal(&self) -> crate::crypt::Result<String> {
+        if !self.secret {
+            return Ok(self.value.clone());
+        }
+
+        SecretController::decrypt(&self.value, Settings::global().app().secret_key())
+    }
+}
+
 #[derive(Debug, Clone, FromRow, Serialize, ToSchema)]
 pub struct Server {
     pub id: i64,
@@ -16,26 +101,138 @@ pub struct Server {
 
     pub name: String,
     pub url: String,
+    pub kind: ServerKind,
+    pub check_options: Value,
+
+    pub method: String,
+    #[schema(value_type = Vec<ServerHeader>)]
+    pub headers: Json<Vec<ServerHeader>>,
+    pub request_body: Option<String>,
+    pub accepted_status_codes: String,
 
     pub timeout: i64,
     pub interval: i64,
+    pub failure_threshold: i64,
+    pub recovery_threshold: i64,
+    pub recheck_interval: Option<i64>,
+    pub flap_threshold: Option<i64>,
+    pub flap_window: i64,
+    pub record_mode: RecordMode,
+    pub record_sample: i64,
+    /// Overrides the global retention of the server's logs and check results, in days
+    pub log_retention_days: Option<i64>,
+    /// Policy escalating unacknowledged outages of the server
+    pub escalation_policy_id: Option<i64>,
+    pub heartbeat_token: Option<String>,
+    #[schema(value_type = Vec<i64>)]
+    pub parent_ids: Json<Vec<i64>>,
 
     pub last_seen_status_code: Option<i64>,
     pub last_seen_reason: Option<String>,
+    pub last_seen_state: Option<ServerState>,
+    pub last_latency_ms: Option<i64>,
+    pub degraded_threshold_ms: Option<i64>,
 
+    pub flapping: bool,
     pub is_turned_on: bool,
+    /// Set for servers paused with a deadline, they're resumed automatically afterwards
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub paused_until: Option<OffsetDateTime>,
 
     pub created_at: time::PrimitiveDateTime,
     pub updated_at: time::PrimitiveDateTime,
 }
 
+#[cfg(test)]
+impl Server {
+    /// Online `http` server with defaults of a freshly created one
+    pub fn mock(id: i64, url: &str) -> Self {
+        let now = time::UtcDateTime::now();
+        let now = PrimitiveDateTime::new(now.date(), now.time());
+
+        Self {
+            id,
+            user_id: 0,
+            name: format!("server-{id}"),
+            url: url.to_string(),
+            kind: ServerKind::Http,
+            check_options: json!({}),
+            method: "GET".to_string(),
+            headers: Json(vec![]),
+            request_body: None,
+            accepted_status_codes: StatusCodes::default().to_string(),
+            timeout: 10,
+            interval: 60,
+            failure_threshold: 1,
+            recovery_threshold: 1,
+            recheck_interval: None,
+            flap_threshold: None,
+            flap_window: 600,
+            record_mode: RecordMode::Transitions,
+            record_sample: 10,
+            log_retention_days: None,
+            escalation_policy_id: None,
+            heartbeat_token: None,
+            parent_ids: Json(vec![]),
+            last_seen_status_code: None,
+            last_seen_reason: None,
+            last_seen_state: None,
+            last_latency_ms: None,
+            degraded_threshold_ms: None,
+            flapping: false,
+            is_turned_on: true,
+            paused_until: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+impl Server {
+    /// Parsed `accepted_status_codes`, falls back to `200-299` if stored value is invalid
+    pub fn accepted_codes(&self) -> StatusCodes {
+        self.accepted_status_codes.parse().unwrap_or_default()
+    }
+
+    /// Shows the server as under maintenance, probed states are still recorded in its logs
+    pub fn in_maintenance(mut self) -> Self {
+        self.last_seen_state = Some(ServerState::Maintenance);
+        self
+    }
+
+    /// Hides values of secret headers, use before handing the server out of the backend
+    pub fn redacted(mut self) -> Self {
+        for header in self.headers.iter_mut().filter(|h| h.secret) {
+            header.value = REDACTED.to_string();
+        }
+        self
+    }
+}
+
 #[derive(Deserialize, Debug, Clone, ToSchema)]
 pub struct ServerCreate {
     pub name: String,
     pub url: String,
+    pub kind: Option<ServerKind>,
+    pub check_options: Option<Value>, // JSON object from request body
+    pub method: Option<String>,
+    pub headers: Option<Vec<ServerHeader>>,
+    pub request_body: Option<String>,
+    pub accepted_status_codes: Option<String>, // e.g `200-299,401`
+    pub degraded_threshold_ms: Option<i64>,
     pub timeout: Option<i64>,
     pub interval: Option<i64>,
-    pub is_turned_on: Option<bool>,
+    pub failure_threshold: Option<i64>,
+    pub recovery_threshold: Option<i64>,
+    pub recheck_interval: Option<i64>,
+    pub flap_threshold: Option<i64>, // e.g 5 state changes
+    pub flap_window: Option<i64>,    // within 600 seconds
+    pub record_mode: Option<RecordMode>,
+    pub record_sample: Option<i64>, // e.g 1 out of 10 checks
+    pub log_retention_days: Option<i64>, // 0 keeps logs forever
+    pub escalation_policy_id: Option<i64>,
+    pub parent_ids: Option<Vec<i64>>, // servers this one depends on, e.g its router
+    pub is_turned_on: Option<bool>, // on by default, left as is on update
 }
 
 impl ServerCreate {
@@ -49,13 +246,101 @@ impl ServerCreate {
         Self {
             name: name.into(),
             url: url.into(),
+            kind: None,
+            check_options: None,
+            method: None,
+            headers: None,
+            request_body: None,
+            accepted_status_codes: None,
+            degraded_threshold_ms: None,
             timeout,
             interval,
+            failure_threshold: None,
+            recovery_threshold: None,
+            recheck_interval: None,
+            flap_threshold: None,
+            flap_window: None,
+            record_mode: None,
+            record_sample: None,
+            log_retention_days: None,
+            escalation_policy_id: None,
+            parent_ids: None,
             is_turned_on,
         }
     }
 }
 
+/// Encrypts values of secret headers, redacted values are taken from `stored` headers
+fn seal_headers(headers: Vec<ServerHeader>, stored: &[ServerHeader]) -> Result<Vec<ServerHeader>> {
+    let key = Settings::global().app().secret_key();
+
+    headers
+        .into_iter()
+        .map(|mut header| {
+            if !header.secret {
+                return Ok(header);
+            }
+
+            let previous = stored
+                .iter()
+                .find(|h| h.secret && h.name.eq_ignore_ascii_case(&header.name));
+            header.value = match previous {
+                Some(previous) if header.value == REDACTED => previous.value.clone(),
+                _ => SecretController::encrypt(&header.value, key)?,
+            };
+            Ok(header)
+        })
+        .collect()
+}
+
+/// Deduplicated parents, in the submitted order
+fn parent_ids(parents: Option<Vec<i64>>) -> Json<Vec<i64>> {
+    let mut seen = HashSet::new();
+    let parents = parents
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|id| seen.insert(*id))
+        .collect();
+    Json(parents)
+}
+
+/// Finds a dependency cycle through `server_id`, `parents` maps every server to its parent ids.
+///
+/// Returned path starts and ends with `server_id`, e.g `[1, 2, 1]`
+pub fn dependency_cycle(parents: &HashMap<i64, Vec<i64>>, server_id: i64) -> Option<Vec<i64>> {
+    fn visit(
+        parents: &HashMap<i64, Vec<i64>>,
+        target: i64,
+        current: i64,
+        path: &mut Vec<i64>,
+        visited: &mut HashSet<i64>,
+    ) -> bool {
+        for parent in parents.get(&current).into_iter().flatten() {
+            path.push(*parent);
+            if *parent == target {
+                return true;
+            }
+            if visited.insert(*parent) && visit(parents, target, *parent, path, visited) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+
+    let mut path = vec![server_id];
+    let mut visited = HashSet::new();
+    visit(parents, server_id, server_id, &mut path, &mut visited).then_some(path)
+}
+
+/// Normalizes the submitted codes, e.g ` 200-299, 401` into `200-299,401`
+fn accepted_status_codes(codes: Option<String>) -> String {
+    codes
+        .and_then(|codes| codes.parse::<StatusCodes>().ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
 pub struct ServerBmc;
 
 impl ServerBmc {
@@ -63,19 +348,58 @@ impl ServerBmc {
         let user_id = ctx.user_id;
         let name = sc.name;
         let url = sc.url;
+        let kind = sc.kind.unwrap_or_default();
+        let check_options = sc.check_options.unwrap_or_else(|| json!({}));
+        let method = sc.method.unwrap_or_else(|| "GET".to_string()).to_uppercase();
+        let headers = Json(seal_headers(sc.headers.unwrap_or_default(), &[])?);
+        let request_body = sc.request_body;
+        let accepted_status_codes = accepted_status_codes(sc.accepted_status_codes);
+        let degraded_threshold_ms = sc.degraded_threshold_ms;
         let timeout = sc.timeout.unwrap_or(10);
         let interval = sc.interval.unwrap_or(60);
-        let is_turned_on = sc.is_turned_on.unwrap_or(false);
+        let failure_threshold = sc.failure_threshold.unwrap_or(1);
+        let recovery_threshold = sc.recovery_threshold.unwrap_or(1);
+        let recheck_interval = sc.recheck_interval;
+        let flap_threshold = sc.flap_threshold;
+        let flap_window = sc.flap_window.unwrap_or(600);
+        let record_mode = sc.record_mode.unwrap_or_default();
+        let record_sample = sc.record_sample.unwrap_or(10);
+        let log_retention_days = sc.log_retention_days;
+        let escalation_policy_id = sc.escalation_policy_id;
+        let heartbeat_token = match kind {
+            ServerKind::Heartbeat => Some(SecretController::generate_token()?),
+            _ => None,
+        };
+        let parent_ids = parent_ids(sc.parent_ids);
+        let is_turned_on = sc.is_turned_on.unwrap_or(true);
 
         let row = sqlx::query(
-            "INSERT INTO server (user_id, name, url, timeout, interval, is_turned_on) \
-            VALUES (?, ?, ?, ?, ?, ?) RETURNING id, created_at, updated_at",
+            "INSERT INTO server (user_id, name, url, kind, check_options, method, headers, request_body, accepted_status_codes, degraded_threshold_ms, timeout, interval, failure_threshold, recovery_threshold, recheck_interval, flap_threshold, flap_window, record_mode, record_sample, log_retention_days, escalation_policy_id, heartbeat_token, parent_ids, is_turned_on) \
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) RETURNING id, created_at, updated_at",
         )
         .bind(user_id)
         .bind(&name)
         .bind(&url)
+        .bind(kind)
+        .bind(&check_options)
+        .bind(&method)
+        .bind(&headers)
+        .bind(&request_body)
+        .bind(&accepted_status_codes)
+        .bind(degraded_threshold_ms)
         .bind(timeout)
         .bind(interval)
+        .bind(failure_threshold)
+        .bind(recovery_threshold)
+        .bind(recheck_interval)
+        .bind(flap_threshold)
+        .bind(flap_window)
+        .bind(record_mode)
+        .bind(record_sample)
+        .bind(log_retention_days)
+        .bind(escalation_policy_id)
+        .bind(&heartbeat_token)
+        .bind(&parent_ids)
         .bind(is_turned_on)
         .fetch_one(&mm.pool)
         .await?;
@@ -89,11 +413,33 @@ impl ServerBmc {
             user_id,
             name,
             url,
+            kind,
+            check_options,
+            method,
+            headers,
+            request_body,
+            accepted_status_codes,
             timeout,
             interval,
+            failure_threshold,
+            recovery_threshold,
+            recheck_interval,
+            flap_threshold,
+            flap_window,
+            record_mode,
+            record_sample,
+            log_retention_days,
+            escalation_policy_id,
+            heartbeat_token,
+            parent_ids,
             last_seen_reason: None,
             last_seen_status_code: None,
+            last_seen_state: None,
+            last_latency_ms: None,
+            degraded_threshold_ms,
+            flapping: false,
             is_turned_on,
+            paused_until: None,
             created_at,
             updated_at,
         })
@@ -103,12 +449,14 @@ impl ServerBmc {
         mm: &ModelManager,
         _ctx: &Ctx,
         id: i64,
+        state: ServerState,
         reason: S,
         code: i64,
     ) -> Result<()> {
         sqlx::query(
-            "UPDATE server SET last_seen_reason = ?, last_seen_status_code = ? WHERE id = ?",
+            "UPDATE server SET last_seen_state = ?, last_seen_reason = ?, last_seen_status_code = ? WHERE id = ?",
         )
+        .bind(state)
         .bind(reason.into())
         .bind(code)
         .bind(id)
@@ -118,28 +466,153 @@ impl ServerBmc {
         Ok(())
     }
 
-    pub async fn update_server(
+    pub async fn update_flapping(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        id: i64,
+        flapping: bool,
+    ) -> Result<()> {
+        sqlx::query("UPDATE server SET flapping = ? WHERE id = ?")
+            .bind(flapping)
+            .bind(id)
+            .execute(&mm.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_latency(
         mm: &ModelManager,
         _ctx: &Ctx,
         id: i64,
+        latency_ms: i64,
+    ) -> Result<()> {
+        sqlx::query("UPDATE server SET last_latency_ms = ? WHERE id = ?")
+            .bind(latency_ms)
+            .bind(id)
+            .execute(&mm.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Stores the latest latency of several servers at once, `latencies` pairs ids with millis
+    pub async fn update_latencies(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        latencies: &[(i64, i64)],
+    ) -> Result<()> {
+        let mut tx = mm.pool.begin().await?;
+
+        for (id, latency_ms) in latencies {
+            sqlx::query("UPDATE server SET last_latency_ms = ? WHERE id = ?")
+                .bind(latency_ms)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Turns monitoring of the server off, `until` schedules an automatic resume
+    pub async fn pause(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        id: i64,
+        until: Option<OffsetDateTime>,
+    ) -> Result<()> {
+        sqlx::query("UPDATE server SET is_turned_on = 0, paused_until = ? WHERE id = ?")
+            .bind(until)
+            .bind(id)
+            .execute(&mm.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn resume(mm: &ModelManager, _ctx: &Ctx, id: i64) -> Result<()> {
+        sqlx::query("UPDATE server SET is_turned_on = 1, paused_until = NULL WHERE id = ?")
+            .bind(id)
+            .execute(&mm.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_server(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        found: &Server,
         sc: ServerCreate,
     ) -> Result<PrimitiveDateTime> {
+        let id = found.id;
         let name = sc.name;
         let url = sc.url;
+        let kind = sc.kind.unwrap_or_default();
+        let check_options = sc.check_options.unwrap_or_else(|| json!({}));
+        let method = sc.method.unwrap_or_else(|| "GET".to_string()).to_uppercase();
+        let headers = Json(seal_headers(sc.headers.unwrap_or_default(), &found.headers)?);
+        let request_body = sc.request_body;
+        let accepted_status_codes = accepted_status_codes(sc.accepted_status_codes);
+        let degraded_threshold_ms = sc.degraded_threshold_ms;
         let timeout = sc.timeout.unwrap_or(10);
         let interval = sc.interval.unwrap_or(60);
-        let is_turned_on = sc.is_turned_on.unwrap_or(false);
+        let failure_threshold = sc.failure_threshold.unwrap_or(1);
+        let recovery_threshold = sc.recovery_threshold.unwrap_or(1);
+        let recheck_interval = sc.recheck_interval;
+        let flap_threshold = sc.flap_threshold;
+        let flap_window = sc.flap_window.unwrap_or(600);
+        let record_mode = sc.record_mode.unwrap_or_default();
+        let record_sample = sc.record_sample.unwrap_or(10);
+        let log_retention_days = sc.log_retention_days;
+        let escalation_policy_id = sc.escalation_policy_id;
+        let heartbeat_token = match (kind, &found.heartbeat_token) {
+            (ServerKind::Heartbeat, Some(token)) => Some(token.clone()),
+            (ServerKind::Heartbeat, None) => Some(SecretController::generate_token()?),
+            _ => None,
+        };
+        let parent_ids = parent_ids(sc.parent_ids);
+        // leaving the field out keeps the server as it is, pause it through the dedicated route
+        let is_turned_on = sc.is_turned_on.unwrap_or(found.is_turned_on);
+        // turning the server on by hand cancels a pending automatic resume
+        let paused_until = if is_turned_on {
+            None
+        } else {
+            found.paused_until
+        };
         let now = time::UtcDateTime::now();
         let updated_at = PrimitiveDateTime::new(now.date(), now.time());
 
         sqlx::query(
-            "UPDATE server SET name = ?, url = ?, timeout = ?, interval = ?, is_turned_on = ?, updated_at = ? WHERE id = ?"
+            "UPDATE server SET name = ?, url = ?, kind = ?, check_options = ?, method = ?, headers = ?, request_body = ?, accepted_status_codes = ?, degraded_threshold_ms = ?, timeout = ?, interval = ?, failure_threshold = ?, recovery_threshold = ?, recheck_interval = ?, flap_threshold = ?, flap_window = ?, record_mode = ?, record_sample = ?, log_retention_days = ?, escalation_policy_id = ?, heartbeat_token = ?, parent_ids = ?, is_turned_on = ?, paused_until = ?, updated_at = ? WHERE id = ?"
         )
         .bind(&name)
         .bind(&url)
+        .bind(kind)
+        .bind(&check_options)
+        .bind(&method)
+        .bind(&headers)
+        .bind(&request_body)
+        .bind(&accepted_status_codes)
+        .bind(degraded_threshold_ms)
         .bind(timeout)
         .bind(interval)
+        .bind(failure_threshold)
+        .bind(recovery_threshold)
+        .bind(recheck_interval)
+        .bind(flap_threshold)
+        .bind(flap_window)
+        .bind(record_mode)
+        .bind(record_sample)
+        .bind(log_retention_days)
+        .bind(escalation_policy_id)
+        .bind(&heartbeat_token)
+        .bind(&parent_ids)
         .bind(is_turned_on)
+        .bind(paused_until)
         .bind(updated_at)
         .bind(id)
         .execute(&mm.pool)
@@ -163,6 +636,25 @@ impl ServerBmc {
         Ok(Some(result))
     }
 
+    pub async fn get_by_heartbeat_token(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        token: &str,
+    ) -> Result<Option<Server>> {
+        let result =
+            sqlx::query_as::<Sqlite, Server>("SELECT * FROM server WHERE heartbeat_token = ?")
+                .bind(token)
+                .fetch_one(&mm.pool)
+                .await;
+
+        if let Err(sqlx::Error::RowNotFound) = result {
+            return Ok(None);
+        }
+
+        let result = result?;
+        Ok(Some(result))
+    }
+
     pub async fn get_by_id(mm: &ModelManager, _ctx: &Ctx, id: i64) -> Result<Option<Server>> {
         let result = sqlx::query_as::<Sqlite, Server>("SELECT * FROM server WHERE id = ?")
             .bind(id)
@@ -178,11 +670,25 @@ impl ServerBmc {
     }
 
     pub async fn delete(mm: &ModelManager, _ctx: &Ctx, id: i64) -> Result<()> {
+        let mut tx = mm.pool.begin().await?;
+
         sqlx::query("DELETE FROM server WHERE id = ?")
             .bind(id)
-            .execute(&mm.pool)
+            .execute(&mut *tx)
             .await?;
 
+        // children no longer depend on the removed server
+        sqlx::query(
+            "UPDATE server SET parent_ids = (SELECT json_group_array(value) FROM json_each(server.parent_ids) WHERE value != ?) \
+            WHERE EXISTS (SELECT 1 FROM json_each(server.parent_ids) WHERE value = ?)",
+        )
+        .bind(id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
         Ok(())
     }
 }
@@ -200,6 +706,17 @@ impl ServerBmc {
         Ok(result)
     }
 
+    /// Servers depending on `parent_id`
+    pub async fn children(mm: &ModelManager, _ctx: &Ctx, parent_id: i64) -> Result<Vec<Server>> {
+        let result = sqlx::query_as(
+            "SELECT * FROM server WHERE EXISTS (SELECT 1 FROM json_each(server.parent_ids) WHERE value = ?)",
+        )
+        .bind(parent_id)
+        .fetch_all(&mm.pool)
+        .await?;
+        Ok(result)
+    }
+
     pub async fn count(
         mm: &ModelManager,
         ctx: &Ctx,
@@ -241,3 +758,24 @@ impl ServerBmc {
         Ok(Page::new(items, count, limit, offset))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dependency_cycle() {
+        // 3 -> 2 -> 1, 4 -> 1
+        let mut parents = HashMap::from([(1, vec![]), (2, vec![1]), (3, vec![2]), (4, vec![1])]);
+        assert_eq!(dependency_cycle(&parents, 3), None);
+
+        parents.insert(1, vec![4, 3]);
+        assert_eq!(dependency_cycle(&parents, 1), Some(vec![1, 4, 1]));
+
+        parents.insert(1, vec![3]);
+        assert_eq!(dependency_cycle(&parents, 1), Some(vec![1, 3, 2, 1]));
+
+        parents.insert(1, vec![1]);
+        assert_eq!(dependency_cycle(&parents, 1), Some(vec![1, 1]));
+    }
+}
@@ -1,22 +1,123 @@
 use super::Result;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Row, Sqlite};
-use time::PrimitiveDateTime;
+use time::{OffsetDateTime, PrimitiveDateTime};
 
 use crate::{
     ModelManager,
-    model::{Ctx, Page, Server},
+    model::{Ctx, Incident, Page, Server, ServerState},
 };
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ServerLogLine {
     pub server: Server,
     pub log: ServerLog,
+    /// Incident the line belongs to, formats can refer to `incident.id` and `incident.duration`
+    pub incident: Option<Incident>,
+    /// Step of the escalation policy the line is sent for, starting at 1
+    pub escalation: Option<usize>,
+    /// Number of the reminder the line is sent as while the server stays down, starting at 1
+    pub reminder_count: Option<i64>,
+    /// State the server left, set on lines of state changes
+    pub previous: Option<PreviousState>,
+    /// Whole days until `log.cert_expires_at`, counted when the line is sent
+    pub cert_days_left: Option<i64>,
 }
 
 impl ServerLogLine {
     pub fn new(server: Server, log: ServerLog) -> Self {
-        Self { server, log }
+        let cert_days_left = log
+            .cert_expires_at
+            .map(|expires_at| (expires_at - OffsetDateTime::now_utc()).whole_days());
+
+        Self {
+            server,
+            log,
+            incident: None,
+            escalation: None,
+            reminder_count: None,
+            previous: None,
+            cert_days_left,
+        }
+    }
+
+    pub fn with_incident(mut self, incident: Option<Incident>) -> Self {
+        self.incident = incident;
+        self
+    }
+
+    pub fn with_escalation(mut self, step: usize) -> Self {
+        self.escalation = Some(step);
+        self
+    }
+
+    pub fn with_reminder(mut self, count: i64) -> Self {
+        self.reminder_count = Some(count);
+        self
+    }
+
+    pub fn with_previous(mut self, previous: Option<PreviousState>) -> Self {
+        self.previous = previous;
+        self
+    }
+}
+
+/// State a server was in before a transition, formats can write e.g
+/// `back up after {{previous.duration_human}} (was: {{previous.reason}})`
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviousState {
+    pub state: ServerState,
+    /// Moment the server entered the state
+    #[serde(with = "time::serde::rfc3339")]
+    pub since: OffsetDateTime,
+    /// Seconds the server spent in the state
+    pub duration: i64,
+    /// `duration` for humans, e.g `14m 32s`
+    pub duration_human: String,
+    /// Reason the server was last failing for
+    pub reason: Option<String>,
+}
+
+impl PreviousState {
+    pub fn new(
+        state: ServerState,
+        since: OffsetDateTime,
+        until: OffsetDateTime,
+        reason: Option<String>,
+    ) -> Self {
+        let duration = (until - since).whole_seconds().max(0);
+
+        Self {
+            state,
+            since,
+            duration,
+            duration_human: humanize_duration(duration),
+            reason: reason.filter(|reason| !reason.is_empty()),
+        }
+    }
+}
+
+/// Formats `secs` with its two most significant units, e.g `2d 3h`, `14m 32s` or `45s`
+pub fn humanize_duration(secs: i64) -> String {
+    let units = [("d", 86_400), ("h", 3_600), ("m", 60), ("s", 1)];
+    let secs = secs.max(0);
+
+    let parts: Vec<String> = units
+        .iter()
+        .skip_while(|(_, size)| secs < *size && *size > 1)
+        .take(2)
+        .scan(secs, |left, (unit, size)| {
+            let value = *left / size;
+            *left %= size;
+            Some((value, unit))
+        })
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{value}{unit}"))
+        .collect();
+
+    match parts.is_empty() {
+        true => "0s".to_string(),
+        false => parts.join(" "),
     }
 }
 
@@ -24,77 +125,202 @@ impl ServerLogLine {
 pub struct ServerLog {
     pub id: i64,
     pub server_id: i64,
+    pub state: ServerState,
     pub failed: bool,
     pub status_code: i64,
     pub body: Option<String>,
     pub reason: Option<String>,
+    pub latency_ms: Option<i64>,
+    // RFC3339 instead of the default tuple, so formats can print it as is
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub cert_expires_at: Option<OffsetDateTime>,
+    pub cert_issuer: Option<String>,
+    pub incident_id: Option<i64>,
+    /// Logged while the server was under maintenance, uptime figures leave such lines out
+    pub in_maintenance: bool,
     pub created_at: PrimitiveDateTime,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ServerLogCreate {
     pub server_id: i64,
+    pub state: ServerState,
     pub failed: bool,
     pub status_code: i64,
     pub body: Option<String>,
     pub reason: Option<String>,
+    pub latency_ms: Option<i64>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub cert_expires_at: Option<OffsetDateTime>,
+    pub cert_issuer: Option<String>,
+    pub in_maintenance: bool,
 }
 
 impl ServerLogCreate {
     pub fn new(
         server_id: i64,
-        failed: bool,
+        state: ServerState,
         status_code: i64,
         body: Option<String>,
         reason: Option<String>,
     ) -> Self {
         Self {
             server_id,
-            failed,
+            state,
+            failed: state == ServerState::Unreachable,
             status_code,
             body,
             reason,
+            latency_ms: None,
+            cert_expires_at: None,
+            cert_issuer: None,
+            in_maintenance: false,
         }
     }
+
+    pub fn with_maintenance(mut self, in_maintenance: bool) -> Self {
+        self.in_maintenance = in_maintenance;
+        self
+    }
+
+    pub fn with_latency(mut self, latency_ms: i64) -> Self {
+        self.latency_ms = Some(latency_ms);
+        self
+    }
+
+    pub fn with_certificate<S: Into<String>>(mut self, expires_at: OffsetDateTime, issuer: S) -> Self {
+        self.cert_expires_at = Some(expires_at);
+        self.cert_issuer = Some(issuer.into());
+        self
+    }
 }
 
 pub struct ServerLogBmc;
 
 impl ServerLogBmc {
+    /// Inserts the line, linking it to the server's open incident if there is one
     pub async fn insert(mm: &ModelManager, _ctx: &Ctx, slc: ServerLogCreate) -> Result<ServerLog> {
         let server_id = slc.server_id;
+        let state = slc.state;
         let failed = slc.failed;
         let status_code = slc.status_code;
         let body = slc.body;
         let reason = slc.reason;
+        let latency_ms = slc.latency_ms;
+        let cert_expires_at = slc.cert_expires_at;
+        let cert_issuer = slc.cert_issuer;
+        let in_maintenance = slc.in_maintenance;
 
         let row = sqlx::query(
-            "INSERT INTO server_log (server_id, failed, status_code, body, reason) VALUES (?,?,?,?,?) RETURNING id, created_at",
+            "INSERT INTO server_log (server_id, state, failed, status_code, body, reason, latency_ms, cert_expires_at, cert_issuer, in_maintenance, incident_id) \
+            VALUES (?,?,?,?,?,?,?,?,?,?,(SELECT id FROM incident WHERE server_id = ? AND resolved_at IS NULL)) \
+            RETURNING id, incident_id, created_at",
         )
         .bind(server_id)
+        .bind(state)
         .bind(failed)
         .bind(status_code)
         .bind(body.clone())
         .bind(reason.clone())
+        .bind(latency_ms)
+        .bind(cert_expires_at)
+        .bind(cert_issuer.clone())
+        .bind(in_maintenance)
+        .bind(server_id)
         .fetch_one(&mm.pool)
         .await?;
 
         let id = row.try_get("id")?;
+        let incident_id = row.try_get("incident_id")?;
         let created_at = row.try_get("created_at")?;
 
         let log = ServerLog {
             id,
             server_id,
+            state,
             failed,
             status_code,
             body,
             reason,
+            latency_ms,
+            cert_expires_at,
+            cert_issuer,
+            incident_id,
+            in_maintenance,
             created_at,
         };
 
         Ok(log)
     }
 
+    /// Moment the server entered `state` it was still in when `before_id` was logged,
+    /// lines of states that aren't probed don't interrupt it
+    pub async fn entered_at(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        server_id: i64,
+        state: ServerState,
+        before_id: i64,
+    ) -> Result<Option<OffsetDateTime>> {
+        let since = sqlx::query_scalar::<Sqlite, Option<PrimitiveDateTime>>(
+            "SELECT MIN(created_at) FROM server_log WHERE server_id = ? AND id < ? AND state = ? \
+            AND id > COALESCE((SELECT MAX(id) FROM server_log WHERE server_id = ? AND id < ? AND state != ? \
+            AND state NOT IN ('maintenance', 'flapping')), 0)",
+        )
+        .bind(server_id)
+        .bind(before_id)
+        .bind(state)
+        .bind(server_id)
+        .bind(before_id)
+        .bind(state)
+        .fetch_one(&mm.pool)
+        .await?;
+
+        Ok(since.map(PrimitiveDateTime::assume_utc))
+    }
+
+    /// State of the server at `at` and whether it was under maintenance, according to the last
+    /// line logged before it, `flapping` lines keep the state that preceded them
+    pub async fn state_at(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        server_id: i64,
+        at: OffsetDateTime,
+    ) -> Result<Option<(ServerState, bool)>> {
+        let state = sqlx::query_as::<Sqlite, (ServerState, bool)>(
+            "SELECT state, in_maintenance FROM server_log WHERE server_id = ? AND created_at <= datetime(?) AND state != 'flapping' ORDER BY created_at DESC, id DESC LIMIT 1",
+        )
+        .bind(server_id)
+        .bind(at)
+        .fetch_optional(&mm.pool)
+        .await?;
+
+        Ok(state)
+    }
+
+    /// States logged within `from..to` and whether they were under maintenance, oldest first
+    pub async fn states_between(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        server_id: i64,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> Result<Vec<(OffsetDateTime, ServerState, bool)>> {
+        let rows = sqlx::query_as::<Sqlite, (PrimitiveDateTime, ServerState, bool)>(
+            "SELECT created_at, state, in_maintenance FROM server_log WHERE server_id = ? AND created_at > datetime(?) AND created_at < datetime(?) ORDER BY created_at, id",
+        )
+        .bind(server_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&mm.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(at, state, in_maintenance)| (at.assume_utc(), state, in_maintenance))
+            .collect())
+    }
+
     pub async fn delete(mm: &ModelManager, _ctx: &Ctx, id: i64) -> Result<()> {
         sqlx::query("DELETE FROM server_log WHERE id = ?")
             .bind(id)
@@ -148,6 +374,53 @@ impl ServerLogBmc {
         Ok(logs)
     }
 
+    /// Line that opened the incident, incidents opened by hand have none
+    pub async fn first_of_incident(
+        mm: &ModelManager,
+        ctx: &Ctx,
+        incident_id: i64,
+    ) -> Result<Option<ServerLog>> {
+        let first = Self::list_incident(mm, ctx, incident_id, 0, 1).await?;
+        Ok(first.into_iter().next())
+    }
+
+    /// Lines linked to the incident, oldest first
+    pub async fn list_incident(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        incident_id: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<ServerLog>> {
+        let logs = sqlx::query_as::<Sqlite, ServerLog>(
+            "SELECT * FROM server_log WHERE incident_id = ? ORDER BY created_at, id LIMIT ? OFFSET ?",
+        )
+        .bind(incident_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&mm.pool)
+        .await?;
+        Ok(logs)
+    }
+
+    pub async fn page_incident(
+        mm: &ModelManager,
+        _ctx: &Ctx,
+        incident_id: i64,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Page<ServerLog>> {
+        let items = Self::list_incident(mm, _ctx, incident_id, offset, limit).await?;
+        let count = sqlx::query_scalar::<Sqlite, i64>(
+            "SELECT COUNT(*) FROM server_log WHERE incident_id = ?",
+        )
+        .bind(incident_id)
+        .fetch_one(&mm.pool)
+        .await?;
+
+        Ok(Page::new(items, count, limit, offset))
+    }
+
     pub async fn page(
         mm: &ModelManager,
         _ctx: &Ctx,
@@ -174,3 +447,17 @@ impl ServerLogBmc {
         Ok(Page::new(items, count, limit, offset))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_humanize_duration() {
+        assert_eq!(humanize_duration(0), "0s");
+        assert_eq!(humanize_duration(45), "45s");
+        assert_eq!(humanize_duration(14 * 60 + 32), "14m 32s");
+        assert_eq!(humanize_duration(3 * 3600 + 59), "3h");
+        assert_eq!(humanize_duration(2 * 86_400 + 3 * 3600 + 120), "2d 3h");
+    }
+}
@@ -0,0 +1,91 @@
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+/// Status codes a server is allowed to answer with, parsed from lists and ranges like `200-299,401`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusCodes(Vec<RangeInclusive<u16>>);
+
+impl StatusCodes {
+    pub fn contains(&self, code: u16) -> bool {
+        self.0.iter().any(|range| range.contains(&code))
+    }
+}
+
+impl Default for StatusCodes {
+    fn default() -> Self {
+        Self(vec![200..=299])
+    }
+}
+
+impl FromStr for StatusCodes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |code: &str| {
+            code.trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|code| (100..=999).contains(code))
+                .ok_or_else(|| format!("invalid status code `{}`", code.trim()))
+        };
+
+        let ranges = s
+            .split(',')
+            .filter(|part| !part.trim().is_empty())
+            .map(|part| match part.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = (parse(start)?, parse(end)?);
+                    if start > end {
+                        return Err(format!("invalid status code range `{}`", part.trim()));
+                    }
+                    Ok(start..=end)
+                }
+                None => parse(part).map(|code| code..=code),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if ranges.is_empty() {
+            return Err("no accepted status codes".to_string());
+        }
+
+        Ok(Self(ranges))
+    }
+}
+
+impl fmt::Display for StatusCodes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .0
+            .iter()
+            .map(|range| match range.start() == range.end() {
+                true => range.start().to_string(),
+                false => format!("{}-{}", range.start(), range.end()),
+            })
+            .collect();
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_status_codes_parse() {
+        let codes: StatusCodes = "200-299, 401,301".parse().unwrap();
+
+        assert!(codes.contains(204));
+        assert!(codes.contains(401));
+        assert!(codes.contains(301));
+        assert!(!codes.contains(302));
+        assert!(!codes.contains(500));
+        assert_eq!(codes.to_string(), "200-299,401,301");
+    }
+
+    #[test]
+    fn test_status_codes_invalid() {
+        assert!("".parse::<StatusCodes>().is_err());
+        assert!("299-200".parse::<StatusCodes>().is_err());
+        assert!("2xx".parse::<StatusCodes>().is_err());
+        assert!("99".parse::<StatusCodes>().is_err());
+    }
+}
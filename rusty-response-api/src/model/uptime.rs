@@ -0,0 +1,232 @@
+//! Uptime figures computed from the transition history in `server_log`.
+//!
+//! The window is split into up, down and excluded time by the states the server went through.
+//! `online`, `warning` and `degraded` count as up, `unreachable` as down, `maintenance` and the
+//! time before the first log line are excluded, `flapping` keeps whatever state preceded it.
+//! Lines logged while the server was under maintenance are excluded whatever state they record,
+//! so failures during planned work don't count as downtime.
+
+use serde::Serialize;
+use time::{Duration, OffsetDateTime};
+use utoipa::ToSchema;
+
+use crate::model::ServerState;
+
+/// Longest window a report may cover
+pub const MAX_WINDOW: Duration = Duration::days(366);
+
+/// Parses a window length like `24h` or `7d`
+pub fn parse_window(window: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid window `{window}`, expected e.g `24h` or `7d`");
+    let split = window.char_indices().last().map_or(0, |(i, _)| i);
+    let (count, unit) = window.split_at(split);
+    let count: i64 = count.parse().map_err(|_| invalid())?;
+
+    let window = match unit {
+        "h" => Duration::hours(count),
+        "d" => Duration::days(count),
+        _ => return Err(invalid()),
+    };
+    if !window.is_positive() || window > MAX_WINDOW {
+        return Err(invalid());
+    }
+
+    Ok(window)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bucket {
+    Up,
+    Down,
+    Excluded,
+}
+
+impl Bucket {
+    /// Bucket of a logged state, paired with whether it was logged under maintenance
+    fn of(state: Option<(ServerState, bool)>, current: Bucket) -> Self {
+        match state {
+            Some((_, true)) => Self::Excluded,
+            Some((ServerState::Online | ServerState::Warning | ServerState::Degraded, _)) => {
+                Self::Up
+            }
+            Some((ServerState::Unreachable, _)) => Self::Down,
+            Some((ServerState::Maintenance, _)) | None => Self::Excluded,
+            Some((ServerState::Flapping, _)) => current,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Uptime {
+    #[serde(with = "time::serde::rfc3339")]
+    pub from: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub to: OffsetDateTime,
+    /// `None` when no part of the window was monitored
+    pub uptime_percent: Option<f64>,
+    /// Outages overlapping the window, one already going on at its start included
+    pub outages: u64,
+    pub uptime_secs: i64,
+    pub downtime_secs: i64,
+    /// Time under maintenance or before the server was first checked
+    pub excluded_secs: i64,
+    /// Mean time to recovery, average length of an outage
+    pub mttr_secs: Option<f64>,
+    /// Mean time between failures, uptime per outage
+    pub mtbf_secs: Option<f64>,
+}
+
+impl Uptime {
+    /// Computes the figures of `from..to`, `initial` is the state the server was in at `from`
+    /// and `transitions` the logged states within the window, oldest first. Both are paired with
+    /// whether the server was under maintenance
+    pub fn compute(
+        initial: Option<(ServerState, bool)>,
+        transitions: &[(OffsetDateTime, ServerState, bool)],
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> Self {
+        let mut up = Duration::ZERO;
+        let mut down = Duration::ZERO;
+        let mut excluded = Duration::ZERO;
+
+        let mut current = Bucket::of(initial, Bucket::Excluded);
+        let mut outages = u64::from(current == Bucket::Down);
+        let mut since = from;
+
+        let within = transitions.iter().filter(|(at, ..)| *at > from && *at < to);
+        // the window is closed by a `flapping` mark, so the last state lasts until `to`
+        for (at, state, in_maintenance) in within.chain([&(to, ServerState::Flapping, false)]) {
+            let span = *at - since;
+            match current {
+                Bucket::Up => up += span,
+                Bucket::Down => down += span,
+                Bucket::Excluded => excluded += span,
+            }
+
+            let next = Bucket::of(Some((*state, *in_maintenance)), current);
+            if next == Bucket::Down && current != Bucket::Down {
+                outages += 1;
+            }
+            current = next;
+            since = *at;
+        }
+
+        let monitored = up + down;
+        let per_outage =
+            |total: Duration| (outages > 0).then(|| total.as_seconds_f64() / outages as f64);
+
+        Self {
+            from,
+            to,
+            uptime_percent: (monitored.is_positive())
+                .then(|| up.as_seconds_f64() / monitored.as_seconds_f64() * 100.0),
+            outages,
+            uptime_secs: up.whole_seconds(),
+            downtime_secs: down.whole_seconds(),
+            excluded_secs: excluded.whole_seconds(),
+            mttr_secs: per_outage(down),
+            mtbf_secs: per_outage(up),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_uptime_outages() {
+        let from = OffsetDateTime::UNIX_EPOCH;
+        let at = |mins| from + Duration::minutes(mins);
+        let transitions = [
+            (at(10), ServerState::Unreachable, false),
+            (at(20), ServerState::Online, false),
+            (at(50), ServerState::Flapping, false),
+            (at(60), ServerState::Unreachable, false),
+            (at(70), ServerState::Degraded, false),
+        ];
+
+        let uptime = Uptime::compute(
+            Some((ServerState::Online, false)),
+            &transitions,
+            from,
+            at(100),
+        );
+        assert_eq!(uptime.outages, 2);
+        assert_eq!(uptime.downtime_secs, 20 * 60);
+        assert_eq!(uptime.uptime_secs, 80 * 60);
+        assert_eq!(uptime.uptime_percent, Some(80.0));
+        assert_eq!(uptime.mttr_secs, Some(600.0));
+        assert_eq!(uptime.mtbf_secs, Some(2400.0));
+    }
+
+    #[test]
+    fn test_uptime_exclusions() {
+        let from = OffsetDateTime::UNIX_EPOCH;
+        let at = |mins| from + Duration::minutes(mins);
+        // first checked 30 minutes into the window, then under maintenance for 10 minutes
+        let transitions = [
+            (at(30), ServerState::Unreachable, false),
+            (at(40), ServerState::Maintenance, false),
+            (at(50), ServerState::Online, false),
+        ];
+
+        let uptime = Uptime::compute(None, &transitions, from, at(60));
+        assert_eq!(uptime.outages, 1);
+        assert_eq!(uptime.excluded_secs, 40 * 60);
+        assert_eq!(uptime.uptime_percent, Some(50.0));
+
+        let unmonitored = Uptime::compute(None, &[], from, at(60));
+        assert_eq!(unmonitored.uptime_percent, None);
+        assert_eq!(unmonitored.mttr_secs, None);
+    }
+
+    #[test]
+    fn test_uptime_down_under_maintenance() {
+        let from = OffsetDateTime::UNIX_EPOCH;
+        let at = |mins| from + Duration::minutes(mins);
+        // goes down during planned work and stays down once the window ends
+        let transitions = [
+            (at(10), ServerState::Maintenance, false),
+            (at(20), ServerState::Unreachable, true),
+            (at(30), ServerState::Online, true),
+            (at(35), ServerState::Unreachable, true),
+            (at(40), ServerState::Unreachable, false),
+            (at(50), ServerState::Online, false),
+        ];
+
+        let uptime = Uptime::compute(
+            Some((ServerState::Online, false)),
+            &transitions,
+            from,
+            at(60),
+        );
+        assert_eq!(uptime.outages, 1);
+        assert_eq!(uptime.downtime_secs, 10 * 60);
+        assert_eq!(uptime.excluded_secs, 30 * 60);
+        assert_eq!(uptime.uptime_secs, 20 * 60);
+
+        // a window already running at the start of the range
+        let uptime = Uptime::compute(
+            Some((ServerState::Unreachable, true)),
+            &transitions[4..],
+            from,
+            at(60),
+        );
+        assert_eq!(uptime.outages, 1);
+        assert_eq!(uptime.excluded_secs, 40 * 60);
+    }
+
+    #[test]
+    fn test_parse_window() {
+        assert_eq!(parse_window("24h"), Ok(Duration::hours(24)));
+        assert_eq!(parse_window("30d"), Ok(Duration::days(30)));
+        assert!(parse_window("0d").is_err());
+        assert!(parse_window("400d").is_err());
+        assert!(parse_window("7w").is_err());
+        assert!(parse_window("").is_err());
+        assert!(parse_window("7é").is_err());
+        assert!(parse_window("é").is_err());
+    }
+}
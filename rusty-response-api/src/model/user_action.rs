@@ -6,11 +6,14 @@ use time::PrimitiveDateTime;
 
 use crate::{ModelManager, model::Ctx};
 
-static USER_ACTIONS: [&str; 6] = [
+static USER_ACTIONS: [&str; 9] = [
     // Server Actions
     "server_create",
     "server_delete",
     "server_modify",
+    "server_pause",
+    "server_resume",
+    "server_auto_resume",
     // User Actions
     "user_signup",
     "user_signin",
@@ -21,6 +24,10 @@ pub enum UserAction {
     ServerCreate { server_id: i64 },
     ServerDelete { server_id: i64 },
     ServerModify { server_id: i64 },
+    ServerPause { server_id: i64 },
+    ServerResume { server_id: i64 },
+    /// Resumed once the pause expired, logged on behalf of the server owner
+    ServerAutoResume { server_id: i64 },
     UserSignup { user_id: i64 },
     UserSignin { user_id: i64 },
     UserVerifyAuth { user_id: i64 },
@@ -32,6 +39,9 @@ impl UserAction {
             UserAction::ServerCreate { server_id } => (self.to_string(), Some(*server_id)),
             UserAction::ServerDelete { server_id } => (self.to_string(), Some(*server_id)),
             UserAction::ServerModify { server_id } => (self.to_string(), Some(*server_id)),
+            UserAction::ServerPause { server_id } => (self.to_string(), Some(*server_id)),
+            UserAction::ServerResume { server_id } => (self.to_string(), Some(*server_id)),
+            UserAction::ServerAutoResume { server_id } => (self.to_string(), Some(*server_id)),
             UserAction::UserSignup { user_id } => (self.to_string(), Some(*user_id)),
             UserAction::UserSignin { user_id } => (self.to_string(), Some(*user_id)),
             UserAction::UserVerifyAuth { user_id } => (self.to_string(), Some(*user_id)),
@@ -61,6 +71,18 @@ impl UserAction {
     pub fn server_delete(server_id: i64) -> Self {
         Self::ServerDelete { server_id }
     }
+
+    pub fn server_pause(server_id: i64) -> Self {
+        Self::ServerPause { server_id }
+    }
+
+    pub fn server_resume(server_id: i64) -> Self {
+        Self::ServerResume { server_id }
+    }
+
+    pub fn server_auto_resume(server_id: i64) -> Self {
+        Self::ServerAutoResume { server_id }
+    }
 }
 
 impl std::fmt::Display for UserAction {
@@ -69,6 +91,9 @@ impl std::fmt::Display for UserAction {
             UserAction::ServerCreate { .. } => write!(f, "server_create"),
             UserAction::ServerDelete { .. } => write!(f, "server_delete"),
             UserAction::ServerModify { .. } => write!(f, "server_modify"),
+            UserAction::ServerPause { .. } => write!(f, "server_pause"),
+            UserAction::ServerResume { .. } => write!(f, "server_resume"),
+            UserAction::ServerAutoResume { .. } => write!(f, "server_auto_resume"),
             UserAction::UserSignup { .. } => write!(f, "user_signup"),
             UserAction::UserSignin { .. } => write!(f, "user_signin"),
             UserAction::UserVerifyAuth { .. } => write!(f, "user_verifyauth"),
@@ -41,6 +41,10 @@ impl Notifier for DiscordNotifier {
         Ok(())
     }
 
+    fn credential(&self) -> &str {
+        self.discord_webhook.as_deref().unwrap_or_default()
+    }
+
     async fn notify(&self, line: String) -> Result<()> {
         if self.discord_webhook.is_none() || self.webhook_client.is_none() {
             return Err(super::Error::Other(eyre!(
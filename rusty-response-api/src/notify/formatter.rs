@@ -34,3 +34,63 @@ impl HJSFormatter {
         Ok(formatted)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::model::{Server, ServerLog, ServerState};
+
+    fn line() -> ServerLogLine {
+        let log = ServerLog {
+            id: 1,
+            server_id: 1,
+            state: ServerState::Unreachable,
+            failed: true,
+            status_code: 503,
+            body: None,
+            reason: Some("connection refused".to_string()),
+            latency_ms: None,
+            cert_expires_at: None,
+            cert_issuer: None,
+            incident_id: None,
+            in_maintenance: false,
+            created_at: datetime!(2026-01-01 00:00),
+        };
+        ServerLogLine::new(Server::mock(1, "http://localhost"), log)
+    }
+
+    #[tokio::test]
+    async fn test_format_reminder_count() {
+        let formatter = HJSFormatter::new();
+        let format =
+            "{{#if reminder_count}}Reminder #{{reminder_count}}: {{/if}}{{server.name}} is down";
+        formatter.load_format("key", format).await.unwrap();
+
+        let first = formatter.format("key", &line()).await.unwrap();
+        assert_eq!(first, "server-1 is down");
+
+        let reminder = formatter
+            .format("key", &line().with_reminder(2))
+            .await
+            .unwrap();
+        assert_eq!(reminder, "Reminder #2: server-1 is down");
+    }
+
+    #[tokio::test]
+    async fn test_format_cert_days_left() {
+        let formatter = HJSFormatter::new();
+        let format = "{{log.reason}}, {{cert_days_left}} days left";
+        formatter.load_format("key", format).await.unwrap();
+
+        let mut line = line();
+        line.log.reason = Some("certificate expires on 2026-01-01".to_string());
+        line.log.cert_expires_at =
+            Some(time::OffsetDateTime::now_utc() + time::Duration::hours(10 * 24 + 1));
+        let line = ServerLogLine::new(line.server, line.log);
+
+        let formatted = formatter.format("key", &line).await.unwrap();
+        assert_eq!(formatted, "certificate expires on 2026-01-01, 10 days left");
+    }
+}
@@ -0,0 +1,253 @@
+//! Rate limiting of outgoing messages.
+//!
+//! Every notifier has a token bucket, and so do provider credentials, so notifiers sharing a bot
+//! can't get it throttled together. Identical messages of a notifier are only sent once within the
+//! dedup window. Whatever is held back is counted, so it can be reported in a single summary.
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone)]
+pub struct LimiterOptions {
+    pub notifier_burst: u32,
+    pub notifier_per_minute: u32,
+    pub credential_burst: u32,
+    pub credential_per_minute: u32,
+    pub dedup_window: Duration,
+}
+
+impl LimiterOptions {
+    pub fn from_settings() -> Self {
+        let rate_limit = crate::Settings::global().rate_limit();
+        Self {
+            notifier_burst: rate_limit.notifier_burst().max(1),
+            notifier_per_minute: rate_limit.notifier_per_minute(),
+            credential_burst: rate_limit.credential_burst().max(1),
+            credential_per_minute: rate_limit.credential_per_minute(),
+            dedup_window: Duration::from_secs(rate_limit.dedup_window()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(burst: u32, per_minute: u32, now: Instant) -> Self {
+        Self {
+            capacity: burst as f64,
+            tokens: burst as f64,
+            refill_per_sec: per_minute as f64 / 60.0,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated = now;
+    }
+
+    fn has_token(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= 1.0
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Send,
+    /// Same message was sent by the notifier within the dedup window
+    Duplicate,
+    /// Notifier or its credentials ran out of tokens
+    Limited,
+}
+
+/// Key of the provider credentials, notifiers with the same key share a bucket
+pub fn credential_key(provider: &str, credential: &str) -> u64 {
+    hash(&(provider, credential))
+}
+
+fn hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug)]
+pub struct Limiter {
+    options: LimiterOptions,
+    notifiers: HashMap<i64, TokenBucket>,
+    credentials: HashMap<u64, TokenBucket>,
+    /// (notifier_id, message hash) → when it was last sent
+    recent: HashMap<(i64, u64), Instant>,
+    /// notifier_id → messages held back since the last summary
+    suppressed: HashMap<i64, u64>,
+}
+
+impl Limiter {
+    pub fn new(options: LimiterOptions) -> Self {
+        Self {
+            options,
+            notifiers: HashMap::new(),
+            credentials: HashMap::new(),
+            recent: HashMap::new(),
+            suppressed: HashMap::new(),
+        }
+    }
+
+    /// Takes a token from both the notifier's and the credentials' buckets, if both have one
+    fn take(&mut self, notifier_id: i64, credential: u64, now: Instant) -> bool {
+        let options = &self.options;
+        let notifier = self.notifiers.entry(notifier_id).or_insert_with(|| {
+            TokenBucket::new(options.notifier_burst, options.notifier_per_minute, now)
+        });
+        let credential = self.credentials.entry(credential).or_insert_with(|| {
+            TokenBucket::new(options.credential_burst, options.credential_per_minute, now)
+        });
+
+        if !notifier.has_token(now) || !credential.has_token(now) {
+            return false;
+        }
+        notifier.take();
+        credential.take();
+        true
+    }
+
+    /// Decides whether the message can be sent, messages that can't are counted as suppressed
+    pub fn check(
+        &mut self,
+        notifier_id: i64,
+        credential: u64,
+        message: &str,
+        now: Instant,
+    ) -> Verdict {
+        let key = (notifier_id, hash(&message));
+        let duplicate = self
+            .recent
+            .get(&key)
+            .is_some_and(|sent| now.saturating_duration_since(*sent) < self.options.dedup_window);
+        let verdict = if duplicate {
+            Verdict::Duplicate
+        } else if self.take(notifier_id, credential, now) {
+            Verdict::Send
+        } else {
+            Verdict::Limited
+        };
+
+        match verdict {
+            Verdict::Send => {
+                self.recent.insert(key, now);
+            }
+            _ => *self.suppressed.entry(notifier_id).or_default() += 1,
+        }
+        verdict
+    }
+
+    /// Returns how many messages the notifier had suppressed and resets the count, if it has
+    /// a token left to report them
+    pub fn summary(&mut self, notifier_id: i64, credential: u64, now: Instant) -> Option<u64> {
+        if !self.suppressed.contains_key(&notifier_id) || !self.take(notifier_id, credential, now) {
+            return None;
+        }
+        self.suppressed.remove(&notifier_id)
+    }
+
+    /// Drops dedup entries past the window
+    pub fn prune(&mut self, now: Instant) {
+        let window = self.options.dedup_window;
+        self.recent
+            .retain(|_, sent| now.saturating_duration_since(*sent) < window);
+    }
+
+    /// Drops the state of a removed notifier
+    pub fn forget(&mut self, notifier_id: i64) {
+        self.notifiers.remove(&notifier_id);
+        self.suppressed.remove(&notifier_id);
+        self.recent.retain(|(id, _), _| *id != notifier_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn limiter() -> Limiter {
+        Limiter::new(LimiterOptions {
+            notifier_burst: 2,
+            notifier_per_minute: 6,
+            credential_burst: 3,
+            credential_per_minute: 60,
+            dedup_window: Duration::from_secs(300),
+        })
+    }
+
+    #[test]
+    fn test_limiter_notifier_bucket() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+
+        assert_eq!(limiter.check(1, 0, "a", now), Verdict::Send);
+        assert_eq!(limiter.check(1, 0, "b", now), Verdict::Send);
+        assert_eq!(limiter.check(1, 0, "c", now), Verdict::Limited);
+
+        // 6 a minute is a token every 10 seconds
+        let later = now + Duration::from_secs(10);
+        assert_eq!(limiter.check(1, 0, "c", later), Verdict::Send);
+        assert_eq!(limiter.check(1, 0, "d", later), Verdict::Limited);
+    }
+
+    #[test]
+    fn test_limiter_shared_credentials() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+
+        assert_eq!(limiter.check(1, 7, "a", now), Verdict::Send);
+        assert_eq!(limiter.check(1, 7, "b", now), Verdict::Send);
+        assert_eq!(limiter.check(2, 7, "a", now), Verdict::Send);
+        assert_eq!(limiter.check(2, 7, "b", now), Verdict::Limited);
+        assert_eq!(limiter.check(3, 8, "a", now), Verdict::Send);
+    }
+
+    #[test]
+    fn test_limiter_duplicates() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+
+        assert_eq!(limiter.check(1, 0, "down", now), Verdict::Send);
+        assert_eq!(limiter.check(1, 0, "down", now), Verdict::Duplicate);
+        assert_eq!(limiter.check(2, 0, "down", now), Verdict::Send);
+
+        let later = now + Duration::from_secs(300);
+        assert_eq!(limiter.check(1, 0, "down", later), Verdict::Send);
+    }
+
+    #[test]
+    fn test_limiter_summary() {
+        let mut limiter = limiter();
+        let now = Instant::now();
+
+        assert_eq!(limiter.summary(1, 0, now), None);
+        for message in ["a", "b", "c", "d", "a"] {
+            limiter.check(1, 0, message, now);
+        }
+        // no token left to send the summary with
+        assert_eq!(limiter.summary(1, 0, now), None);
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(limiter.summary(1, 0, later), Some(3));
+        assert_eq!(limiter.summary(1, 0, later), None);
+    }
+}
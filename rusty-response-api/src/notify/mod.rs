@@ -2,34 +2,43 @@
 //!
 //! Each notifier is stored by ID, and indexed by `server_id`.
 //! Thread-safe access is ensured with `tokio::RwLock`.
+//!
+//! Outgoing messages go through the [`Limiter`](limiter::Limiter), messages it holds back are
+//! reported by [`NotifyManager::flush_suppressed`].
 
 mod discord;
 mod error;
 mod formatter;
+mod limiter;
 mod notifier;
 mod telegram;
 
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
     str::FromStr,
-    sync::Arc,
+    sync::{Arc, Mutex},
+    time::Instant,
 };
 
 use eyre::eyre;
 use tokio::sync::RwLock;
-use tracing::{error, trace};
+use tracing::{debug, error, trace};
 
 use crate::{
     ModelManager,
     model::{Ctx, Notifier as NotifierModel, NotifierBmc, ServerLogLine},
     notify::{
-        discord::DiscordNotifier, formatter::HJSFormatter, notifier::Notifier,
+        discord::DiscordNotifier,
+        formatter::HJSFormatter,
+        limiter::{Limiter, Verdict},
+        notifier::Notifier,
         telegram::TelegramNotifier,
     },
 };
 
 pub use error::{Error, Result};
 pub use formatter::NotifierFormatter;
+pub use limiter::LimiterOptions;
 
 pub type ArcNotifier = Arc<dyn Notifier>;
 
@@ -70,6 +79,8 @@ fn build_notifier(provider: NotifierType, credentials: &str) -> Result<ArcNotifi
 struct NotifierMeta {
     pub server_id: i64,
     pub notifier_key: String,
+    /// Key of the provider credentials, see [`limiter::credential_key`]
+    pub credential_key: u64,
     pub notifier: ArcNotifier,
 }
 
@@ -78,6 +89,7 @@ struct NotifierMeta {
 pub struct NotifyManager {
     inner: Arc<RwLock<NotifyState>>,
     formatter: HJSFormatter,
+    limiter: Arc<Mutex<Limiter>>,
 }
 
 struct NotifyState {
@@ -93,6 +105,7 @@ impl NotifyManager {
                 by_server: HashMap::new(),
             })),
             formatter: HJSFormatter::new(),
+            limiter: Arc::new(Mutex::new(Limiter::new(LimiterOptions::from_settings()))),
         }
     }
 
@@ -138,6 +151,10 @@ impl NotifyManager {
                 db_notifier.id,
                 NotifierMeta {
                     notifier_key,
+                    credential_key: limiter::credential_key(
+                        &db_notifier.provider,
+                        arc_notifier.credential(),
+                    ),
                     server_id: db_notifier.server_id,
                     notifier: Arc::clone(&arc_notifier),
                 },
@@ -180,6 +197,10 @@ impl NotifyManager {
             notifier.id,
             NotifierMeta {
                 notifier_key,
+                credential_key: limiter::credential_key(
+                    &notifier.provider,
+                    arc_notifier.credential(),
+                ),
                 server_id: notifier.server_id,
                 notifier: Arc::clone(&arc_notifier),
             },
@@ -195,34 +216,36 @@ impl NotifyManager {
 
     pub async fn remove_by_nid(&self, notifier_id: i64) -> Result<()> {
         let mut lock = self.inner.write().await;
-        if let Some(meta) = lock.by_id.remove(&notifier_id) {
-            if let Some(set) = lock.by_server.get_mut(&meta.server_id) {
-                set.remove(&notifier_id);
-                if set.is_empty() {
-                    lock.by_server.remove(&meta.server_id);
-                }
+        if let Some(meta) = lock.by_id.remove(&notifier_id)
+            && let Some(set) = lock.by_server.get_mut(&meta.server_id)
+        {
+            set.remove(&notifier_id);
+            if set.is_empty() {
+                lock.by_server.remove(&meta.server_id);
             }
         }
+        self.limiter.lock().unwrap().forget(notifier_id);
         Ok(())
     }
 
     pub async fn remove_by_sid(&self, server_id: i64) -> Result<()> {
         let mut lock = self.inner.write().await;
         if let Some(ids) = lock.by_server.remove(&server_id) {
+            let mut limiter = self.limiter.lock().unwrap();
             for id in ids {
                 lock.by_id.remove(&id);
+                limiter.forget(id);
             }
         }
         Ok(())
     }
 
-    async fn get_by_sid(&self, server_id: i64) -> Vec<NotifierMeta> {
+    async fn get_by_sid(&self, server_id: i64) -> Vec<(i64, NotifierMeta)> {
         let lock = self.inner.read().await;
         match lock.by_server.get(&server_id) {
             Some(id_set) => id_set
                 .iter()
-                .filter_map(|id| lock.by_id.get(id))
-                .cloned()
+                .filter_map(|id| lock.by_id.get(id).map(|meta| (*id, meta.clone())))
                 .collect(),
             None => vec![],
         }
@@ -230,16 +253,84 @@ impl NotifyManager {
 }
 
 impl NotifyManager {
+    /// Formats the line and sends it, unless the limiter holds it back
+    async fn send(
+        &self,
+        notifier_id: i64,
+        notifier: &NotifierMeta,
+        line: &ServerLogLine,
+    ) -> Result<()> {
+        let formatted = self.formatter.format(&notifier.notifier_key, line).await?;
+
+        let verdict = self.limiter.lock().unwrap().check(
+            notifier_id,
+            notifier.credential_key,
+            &formatted,
+            Instant::now(),
+        );
+        match verdict {
+            Verdict::Send => notifier.notifier.notify(formatted).await?,
+            Verdict::Duplicate => {
+                debug!("Notifier {notifier_id} already sent this message, suppressing")
+            }
+            Verdict::Limited => debug!("Notifier {notifier_id} is rate limited, suppressing"),
+        }
+        Ok(())
+    }
+
     pub async fn notify(&self, server_id: i64, line: ServerLogLine) -> Result<()> {
         let notifiers = self.get_by_sid(server_id).await;
 
-        for notifier in notifiers {
-            let formatted = self.formatter.format(&notifier.notifier_key, &line).await?;
-            notifier.notifier.notify(formatted).await?;
+        for (notifier_id, notifier) in notifiers {
+            self.send(notifier_id, &notifier, &line).await?;
         }
 
         Ok(())
     }
+
+    /// Sends the line through a single notifier, whichever server it's attached to.
+    /// Inactive or removed notifiers are skipped
+    pub async fn notify_by_nid(&self, notifier_id: i64, line: &ServerLogLine) -> Result<()> {
+        let notifier = self.inner.read().await.by_id.get(&notifier_id).cloned();
+        let Some(notifier) = notifier else {
+            trace!("Notifier {notifier_id} isn't active, skipping");
+            return Ok(());
+        };
+
+        self.send(notifier_id, &notifier, line).await
+    }
+
+    /// Sends every notifier that had messages suppressed a summary of how many, once it's
+    /// allowed to send again
+    pub async fn flush_suppressed(&self) {
+        let notifiers: Vec<_> = {
+            let lock = self.inner.read().await;
+            lock.by_id
+                .iter()
+                .map(|(id, meta)| (*id, meta.clone()))
+                .collect()
+        };
+
+        let now = Instant::now();
+        for (notifier_id, notifier) in notifiers {
+            let count = {
+                let mut limiter = self.limiter.lock().unwrap();
+                limiter.prune(now);
+                limiter.summary(notifier_id, notifier.credential_key, now)
+            };
+            let Some(count) = count else {
+                continue;
+            };
+
+            let message = match count {
+                1 => "1 alert suppressed by rate limiting".to_string(),
+                n => format!("{n} alerts suppressed by rate limiting"),
+            };
+            if let Err(e) = notifier.notifier.notify(message).await {
+                error!("Unable to report suppressed alerts of notifier {notifier_id}: {e}");
+            }
+        }
+    }
 }
 
 impl Default for NotifyManager {
@@ -259,13 +350,15 @@ mod test {
     fn get_mock(id: i64, user_id: i64, server_id: i64) -> NotifierModel {
         let utc = UtcDateTime::now();
         NotifierModel {
-            id: id,
-            user_id: user_id,
-            server_id: server_id,
+            id,
+            user_id,
+            server_id,
             provider: "telegram".to_string(),
             credentials: serde_json::to_value(TelegramOptions::new(-4444444, "tokenhere")).unwrap(),
             format: "{{server.id}}".to_string(),
             active: true,
+            remind_every: None,
+            remind_limit: 12,
             created_at: PrimitiveDateTime::new(utc.date(), utc.time()),
             updated_at: PrimitiveDateTime::new(utc.date(), utc.time()),
         }
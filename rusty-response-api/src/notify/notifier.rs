@@ -4,5 +4,7 @@ use async_trait::async_trait;
 #[async_trait]
 pub trait Notifier: Send + Sync {
     fn setup(&mut self, credentials_str: &str) -> Result<()>;
+    /// Credential the provider limits messages by, empty if not set up
+    fn credential(&self) -> &str;
     async fn notify(&self, line: String) -> Result<()>;
 }
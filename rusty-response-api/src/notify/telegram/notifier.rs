@@ -48,6 +48,10 @@ impl Notifier for TelegramNotifier {
         Ok(())
     }
 
+    fn credential(&self) -> &str {
+        self.token.as_deref().unwrap_or_default()
+    }
+
     async fn notify(&self, line: String) -> Result<()> {
         if self.bot.is_none() || self.token.is_none() || self.chat_id.is_none() {
             return Err(super::Error::Other(eyre!(
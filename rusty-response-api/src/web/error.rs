@@ -29,15 +29,66 @@ pub enum WebError {
     #[error("Server not found")]
     ServerNotFound,
 
+    #[error("Invalid server configuration: {0}")]
+    InvalidServerConfig(String),
+
     #[error("Not your server")]
     ServerNotAllowed,
 
+    #[error("Invalid server dependency: {0}")]
+    InvalidDependency(String),
+
+    #[error("Pause deadline is in the past")]
+    InvalidPauseDeadline,
+
+    #[error("Heartbeat not found")]
+    HeartbeatNotFound,
+
+    #[error("Invalid heartbeat signal: {0}")]
+    InvalidHeartbeatSignal(String),
+
+    #[error("Maintenance not found")]
+    MaintenanceNotFound,
+
+    #[error("Not your maintenance")]
+    MaintenanceNotAllowed,
+
+    #[error("Invalid maintenance: {0}")]
+    InvalidMaintenance(String),
+
+    #[error("Incident not found")]
+    IncidentNotFound,
+
+    #[error("Not your incident")]
+    IncidentNotAllowed,
+
+    #[error("Server already has an open incident")]
+    IncidentAlreadyOpen,
+
+    #[error("Escalation policy not found")]
+    EscalationPolicyNotFound,
+
+    #[error("Not your escalation policy")]
+    EscalationPolicyNotAllowed,
+
+    #[error("Invalid escalation policy: {0}")]
+    InvalidEscalationPolicy(String),
+
+    #[error("Invalid report range: {0}")]
+    InvalidReportRange(String),
+
+    #[error("Administrators only")]
+    AdminOnly,
+
     #[error("Notifier not found")]
     NotifierNotFound,
 
     #[error("Not your notifier")]
     NotifierNotAllowed,
 
+    #[error("Invalid notifier: {0}")]
+    InvalidNotifier(String),
+
     #[error(transparent)]
     NotifierError(#[from] crate::notify::Error),
 
@@ -74,17 +125,94 @@ impl IntoResponse for WebError {
                 "Server with such ID is not found",
                 None,
             ),
+            WebError::InvalidServerConfig(reason) => (
+                StatusCode::BAD_REQUEST,
+                "Invalid server configuration",
+                Some(reason.clone()),
+            ),
             WebError::ServerNotAllowed => (
                 StatusCode::FORBIDDEN,
                 "You don't own that server to interact with it",
                 None,
             ),
+            WebError::InvalidDependency(reason) => (
+                StatusCode::BAD_REQUEST,
+                "Invalid server dependency",
+                Some(reason.clone()),
+            ),
+            WebError::InvalidPauseDeadline => (
+                StatusCode::BAD_REQUEST,
+                "Pause deadline has to be in the future",
+                None,
+            ),
+            WebError::HeartbeatNotFound => (
+                StatusCode::NOT_FOUND,
+                "Heartbeat with such token is not found",
+                None,
+            ),
+            WebError::InvalidHeartbeatSignal(reason) => (
+                StatusCode::BAD_REQUEST,
+                "Invalid heartbeat signal",
+                Some(reason.clone()),
+            ),
+            WebError::MaintenanceNotFound => {
+                (StatusCode::NOT_FOUND, "Maintenance window not found", None)
+            }
+            WebError::MaintenanceNotAllowed => (
+                StatusCode::FORBIDDEN,
+                "You don't own that maintenance window to interact with it",
+                None,
+            ),
+            WebError::InvalidMaintenance(reason) => (
+                StatusCode::BAD_REQUEST,
+                "Invalid maintenance window",
+                Some(reason.clone()),
+            ),
+            WebError::IncidentNotFound => (StatusCode::NOT_FOUND, "Incident not found", None),
+            WebError::IncidentNotAllowed => (
+                StatusCode::FORBIDDEN,
+                "You don't own that incident to interact with it",
+                None,
+            ),
+            WebError::IncidentAlreadyOpen => (
+                StatusCode::CONFLICT,
+                "Server already has an open incident",
+                None,
+            ),
+            WebError::EscalationPolicyNotFound => {
+                (StatusCode::NOT_FOUND, "Escalation policy not found", None)
+            }
+            WebError::EscalationPolicyNotAllowed => (
+                StatusCode::FORBIDDEN,
+                "You don't own that escalation policy to interact with it",
+                None,
+            ),
+            WebError::InvalidEscalationPolicy(reason) => (
+                StatusCode::BAD_REQUEST,
+                "Invalid escalation policy",
+                Some(reason.clone()),
+            ),
             WebError::NotifierError(e) => (
                 StatusCode::BAD_REQUEST,
                 "Notifier error occured.",
                 Some(e.to_string()),
             ),
             WebError::NotifierNotFound => (StatusCode::NOT_FOUND, "Notifier not found", None),
+            WebError::InvalidNotifier(reason) => (
+                StatusCode::BAD_REQUEST,
+                "Invalid notifier",
+                Some(reason.clone()),
+            ),
+            WebError::InvalidReportRange(reason) => (
+                StatusCode::BAD_REQUEST,
+                "Invalid report range",
+                Some(reason.clone()),
+            ),
+            WebError::AdminOnly => (
+                StatusCode::FORBIDDEN,
+                "Only administrators can access this resource",
+                None,
+            ),
             WebError::NotifierNotAllowed => (
                 StatusCode::FORBIDDEN,
                 "You don't own that notifier to interact with it",
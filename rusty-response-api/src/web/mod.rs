@@ -12,7 +12,12 @@ use utoipa_swagger_ui::SwaggerUi;
 
 use axum::Router;
 
-use crate::{ModelManager, channel::ControlMessage, model::Ctx, notify::NotifyManager};
+use crate::{
+    ModelManager,
+    channel::{ChannelStats, ControlMessage},
+    model::Ctx,
+    notify::NotifyManager,
+};
 
 #[derive(OpenApi)]
 #[openapi(
@@ -26,6 +31,40 @@ use crate::{ModelManager, channel::ControlMessage, model::Ctx, notify::NotifyMan
         routes::user::user_signup,
 
         routes::server::create_server,
+        routes::server::pause_server,
+        routes::server::resume_server,
+        routes::server::acknowledge_server,
+
+        routes::heartbeat::ping,
+        routes::heartbeat::ping_signal,
+
+        routes::maintenance::create_maintenance,
+        routes::maintenance::list_maintenance,
+        routes::maintenance::get_maintenance,
+        routes::maintenance::update_maintenance,
+        routes::maintenance::remove_maintenance,
+
+        routes::escalation::create_policy,
+        routes::escalation::list_policies,
+        routes::escalation::get_policy,
+        routes::escalation::update_policy,
+        routes::escalation::remove_policy,
+
+        routes::incident::create_incident,
+        routes::incident::get_incident,
+        routes::incident::update_incident,
+        routes::incident::remove_incident,
+        routes::incident::acknowledge_incident,
+        routes::incident::add_incident_note,
+        routes::incident::list_incident_notes,
+
+        routes::report::get_uptime,
+        routes::report::get_sla,
+        routes::report::get_series,
+
+        routes::retention::get_retention_report,
+
+        routes::stats::get_stats,
     ),
 )]
 struct ApiDoc;
@@ -44,6 +83,7 @@ pub async fn app_state(
     jwt: &str,
     ctx: &Ctx,
     control_tx: UnboundedSender<ControlMessage>,
+    queue_stats: ChannelStats,
 ) -> Result<AppState, eyre::Report> {
     let notify_manager = NotifyManager::new();
     notify_manager.extend_from_db(mm, ctx).await?;
@@ -53,6 +93,7 @@ pub async fn app_state(
         jwt.to_string(),
         control_tx.clone(),
         notify_manager,
+        queue_stats,
     ))
 }
 
@@ -74,6 +115,34 @@ pub fn app<S>(state: AppState) -> Router<S> {
             "/api/v1/logs/server/",
             routes::server_log_routes(AppState::clone(&state)),
         )
+        .nest(
+            "/api/v1/heartbeat/",
+            routes::heartbeat_routes(AppState::clone(&state)),
+        )
+        .nest(
+            "/api/v1/maintenance/",
+            routes::maintenance_routes(AppState::clone(&state)),
+        )
+        .nest(
+            "/api/v1/escalation/",
+            routes::escalation_routes(AppState::clone(&state)),
+        )
+        .nest(
+            "/api/v1/incident/",
+            routes::incident_routes(AppState::clone(&state)),
+        )
+        .nest(
+            "/api/v1/report/",
+            routes::report_routes(AppState::clone(&state)),
+        )
+        .nest(
+            "/api/v1/retention/",
+            routes::retention_routes(AppState::clone(&state)),
+        )
+        .nest(
+            "/api/v1/stats/",
+            routes::stats_routes(AppState::clone(&state)),
+        )
         .merge(SwaggerUi::new("/api/v1/docs").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .layer(tower_cookies::CookieManagerLayer::new())
         .layer(CorsLayer::very_permissive())
@@ -0,0 +1,202 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use reqwest::StatusCode;
+
+use crate::{
+    model::{
+        Ctx, EscalationPolicy, EscalationPolicyBmc, EscalationPolicyCreate, NotifierBmc, Page,
+        UserRole,
+    },
+    web::{WebError, error::WebErrorSchema, utils::PageQuery},
+};
+
+use super::{AppState, middlewares::verify_token_middleware};
+
+pub fn routes<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/", post(create_policy).get(list_policies))
+        .route(
+            "/{id}",
+            get(get_policy).put(update_policy).delete(remove_policy),
+        )
+        .layer(middleware::from_fn_with_state(
+            AppState::clone(&state),
+            verify_token_middleware,
+        ))
+        .with_state(state)
+}
+
+/// Validates the steps and checks every notified notifier belongs to the user
+async fn check_policy(
+    state: &AppState,
+    ctx: &Ctx,
+    pc: &EscalationPolicyCreate,
+) -> Result<(), WebError> {
+    pc.validate().map_err(WebError::InvalidEscalationPolicy)?;
+
+    for notifier_id in pc.steps.iter().flat_map(|step| &step.notifier_ids) {
+        let notifier = NotifierBmc::find_by_id(&state.mm, ctx, *notifier_id)
+            .await?
+            .ok_or(WebError::NotifierNotFound)?;
+
+        if notifier.user_id != ctx.user_id && !matches!(ctx.role, UserRole::Admin) {
+            return Err(WebError::NotifierNotAllowed);
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetches the policy, making sure it belongs to the user
+pub async fn owned_policy(
+    state: &AppState,
+    ctx: &Ctx,
+    id: i64,
+) -> Result<EscalationPolicy, WebError> {
+    let policy = EscalationPolicyBmc::get(&state.mm, ctx, id)
+        .await?
+        .ok_or(WebError::EscalationPolicyNotFound)?;
+
+    if policy.user_id != ctx.user_id && !matches!(ctx.role, UserRole::Admin) {
+        return Err(WebError::EscalationPolicyNotAllowed);
+    }
+
+    Ok(policy)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/escalation/",
+    tag = "escalation",
+    responses(
+        (status = 200, description = "Escalation policy created", body = EscalationPolicy),
+        (status = 400, description = "Policy is invalid", body = WebErrorSchema),
+        (status = 403, description = "One of the notifiers belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "One of the notifiers is not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+    request_body = EscalationPolicyCreate,
+)]
+pub async fn create_policy(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Json(pc): Json<EscalationPolicyCreate>,
+) -> Result<Response, WebError> {
+    check_policy(&state, &ctx, &pc).await?;
+
+    let id = EscalationPolicyBmc::insert(&state.mm, &ctx, pc).await?;
+    let policy = EscalationPolicyBmc::get(&state.mm, &ctx, id)
+        .await?
+        .ok_or(WebError::EscalationPolicyNotFound)?;
+
+    Ok((StatusCode::OK, Json(policy)).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/escalation/",
+    tag = "escalation",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "Escalation policies of the user", body = Page<EscalationPolicy>),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn list_policies(
+    State(state): State<AppState>,
+    Query(query): Query<PageQuery>,
+    ctx: Ctx,
+) -> Result<Response, WebError> {
+    let policies = EscalationPolicyBmc::page(&state.mm, &ctx, query.offset, query.limit).await?;
+    Ok((StatusCode::OK, Json(policies)).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/escalation/{id}",
+    tag = "escalation",
+    params(("id" = i64, Path, description = "Escalation policy id")),
+    responses(
+        (status = 200, description = "Escalation policy", body = EscalationPolicy),
+        (status = 403, description = "Policy belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Policy not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn get_policy(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<Response, WebError> {
+    let policy = owned_policy(&state, &ctx, id).await?;
+    Ok((StatusCode::OK, Json(policy)).into_response())
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/escalation/{id}",
+    tag = "escalation",
+    params(("id" = i64, Path, description = "Escalation policy id")),
+    responses(
+        (status = 200, description = "Escalation policy updated", body = EscalationPolicy),
+        (status = 400, description = "Policy is invalid", body = WebErrorSchema),
+        (status = 403, description = "Policy or one of the notifiers belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Policy or one of the notifiers is not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+    request_body = EscalationPolicyCreate,
+)]
+pub async fn update_policy(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Json(pc): Json<EscalationPolicyCreate>,
+) -> Result<Response, WebError> {
+    owned_policy(&state, &ctx, id).await?;
+    check_policy(&state, &ctx, &pc).await?;
+
+    EscalationPolicyBmc::update(&state.mm, &ctx, id, pc).await?;
+    let policy = EscalationPolicyBmc::get(&state.mm, &ctx, id)
+        .await?
+        .ok_or(WebError::EscalationPolicyNotFound)?;
+
+    Ok((StatusCode::OK, Json(policy)).into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/escalation/{id}",
+    tag = "escalation",
+    params(("id" = i64, Path, description = "Escalation policy id")),
+    responses(
+        (status = 200, description = "Escalation policy removed, its servers are no longer escalated", body = EscalationPolicy),
+        (status = 403, description = "Policy belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Policy not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn remove_policy(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<Response, WebError> {
+    let policy = owned_policy(&state, &ctx, id).await?;
+    EscalationPolicyBmc::delete(&state.mm, &ctx, id).await?;
+
+    Ok((StatusCode::OK, Json(policy)).into_response())
+}
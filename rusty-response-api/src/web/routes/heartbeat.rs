@@ -0,0 +1,96 @@
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use eyre::Context;
+use reqwest::StatusCode;
+
+use crate::{
+    Ctx,
+    channel::{ControlMessage, HeartbeatPing, HeartbeatSignal},
+    model::{ServerBmc, ServerKind},
+    web::{AppState, WebError, error::WebErrorSchema},
+};
+
+/// Log payloads longer than this are truncated
+const MAX_PAYLOAD: usize = 10 * 1024;
+
+/// Unauthenticated on purpose, the token in the path is the credential
+pub fn routes<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/{token}", get(ping).post(ping))
+        .route("/{token}/{signal}", get(ping_signal).post(ping_signal))
+        .with_state(state)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/heartbeat/{token}",
+    tag = "heartbeat",
+    params(("token" = String, Path, description = "Heartbeat token of the server")),
+    request_body(content = String, description = "Optional log output of the job"),
+    responses(
+        (status = 200, description = "Success ping received"),
+        (status = 404, description = "No heartbeat server with such token", body = WebErrorSchema),
+    ),
+)]
+pub async fn ping(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    body: Bytes,
+) -> Result<Response, WebError> {
+    handle_ping(&state, &token, HeartbeatSignal::Success, body).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/heartbeat/{token}/{signal}",
+    tag = "heartbeat",
+    params(
+        ("token" = String, Path, description = "Heartbeat token of the server"),
+        ("signal" = String, Path, description = "`start`, `success`, `fail` or an exit code, 0 meaning success"),
+    ),
+    request_body(content = String, description = "Optional log output of the job"),
+    responses(
+        (status = 200, description = "Ping received"),
+        (status = 400, description = "Unknown signal", body = WebErrorSchema),
+        (status = 404, description = "No heartbeat server with such token", body = WebErrorSchema),
+    ),
+)]
+pub async fn ping_signal(
+    State(state): State<AppState>,
+    Path((token, signal)): Path<(String, String)>,
+    body: Bytes,
+) -> Result<Response, WebError> {
+    let signal = signal
+        .parse::<HeartbeatSignal>()
+        .map_err(WebError::InvalidHeartbeatSignal)?;
+    handle_ping(&state, &token, signal, body).await
+}
+
+async fn handle_ping(
+    state: &AppState,
+    token: &str,
+    signal: HeartbeatSignal,
+    body: Bytes,
+) -> Result<Response, WebError> {
+    let server = ServerBmc::get_by_heartbeat_token(&state.mm, &Ctx::admin_root(), token)
+        .await?
+        .filter(|server| server.kind == ServerKind::Heartbeat)
+        .ok_or(WebError::HeartbeatNotFound)?;
+
+    let payload = body[..body.len().min(MAX_PAYLOAD)].to_vec();
+
+    state
+        .control_tx
+        .send(ControlMessage::Heartbeat {
+            server_id: server.id,
+            ping: HeartbeatPing::new(signal, payload),
+        })
+        .wrap_err("Failed to send control message")?;
+
+    Ok((StatusCode::OK, "OK").into_response())
+}
@@ -0,0 +1,295 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::{
+    model::{
+        Ctx, Incident, IncidentBmc, IncidentCreate, IncidentFilter, IncidentNote,
+        IncidentNoteCreate, IncidentUpdate, ServerBmc, ServerLogBmc, UserRole,
+    },
+    web::{WebError, error::WebErrorSchema, utils::PageQuery},
+};
+
+use super::{AppState, middlewares::verify_token_middleware};
+
+pub fn routes<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/", post(create_incident).get(list_incidents))
+        .route(
+            "/{id}",
+            get(get_incident)
+                .put(update_incident)
+                .delete(remove_incident),
+        )
+        .route("/{id}/ack", post(acknowledge_incident))
+        .route("/{id}/logs", get(list_incident_logs))
+        .route(
+            "/{id}/notes",
+            post(add_incident_note).get(list_incident_notes),
+        )
+        .layer(middleware::from_fn_with_state(
+            AppState::clone(&state),
+            verify_token_middleware,
+        ))
+        .with_state(state)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IncidentQuery {
+    pub limit: i64,
+    pub offset: i64,
+    pub server_id: Option<i64>,
+    pub open: Option<bool>,
+}
+
+/// Checks the server exists and belongs to the user
+async fn check_server(state: &AppState, ctx: &Ctx, server_id: i64) -> Result<(), WebError> {
+    let server = ServerBmc::get_by_id(&state.mm, ctx, server_id)
+        .await?
+        .ok_or(WebError::ServerNotFound)?;
+
+    if server.user_id != ctx.user_id && !matches!(ctx.role, UserRole::Admin) {
+        return Err(WebError::ServerNotAllowed);
+    }
+
+    Ok(())
+}
+
+/// Fetches the incident, making sure its server belongs to the user
+async fn owned_incident(state: &AppState, ctx: &Ctx, id: i64) -> Result<Incident, WebError> {
+    let incident = IncidentBmc::get(&state.mm, ctx, id)
+        .await?
+        .ok_or(WebError::IncidentNotFound)?;
+
+    check_server(state, ctx, incident.server_id)
+        .await
+        .map_err(|e| match e {
+            WebError::ServerNotAllowed => WebError::IncidentNotAllowed,
+            e => e,
+        })?;
+
+    Ok(incident)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/incident/",
+    tag = "incident",
+    responses(
+        (status = 200, description = "Incident opened", body = Incident),
+        (status = 403, description = "Server belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Server not found", body = WebErrorSchema),
+        (status = 409, description = "Server already has an open incident", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+    request_body = IncidentCreate,
+)]
+pub async fn create_incident(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Json(ic): Json<IncidentCreate>,
+) -> Result<Response, WebError> {
+    check_server(&state, &ctx, ic.server_id).await?;
+
+    let id = IncidentBmc::insert(&state.mm, &ctx, ic)
+        .await?
+        .ok_or(WebError::IncidentAlreadyOpen)?;
+    let incident = IncidentBmc::get(&state.mm, &ctx, id)
+        .await?
+        .ok_or(WebError::IncidentNotFound)?;
+
+    Ok((StatusCode::OK, Json(incident)).into_response())
+}
+
+pub async fn list_incidents(
+    State(state): State<AppState>,
+    Query(query): Query<IncidentQuery>,
+    ctx: Ctx,
+) -> Result<Response, WebError> {
+    let filter = IncidentFilter {
+        server_id: query.server_id,
+        open: query.open,
+    };
+    let incidents = IncidentBmc::page(&state.mm, &ctx, &filter, query.offset, query.limit).await?;
+    Ok((StatusCode::OK, Json(incidents)).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/incident/{id}",
+    tag = "incident",
+    params(("id" = i64, Path, description = "Incident id")),
+    responses(
+        (status = 200, description = "Incident", body = Incident),
+        (status = 403, description = "Incident of another user's server", body = WebErrorSchema),
+        (status = 404, description = "Incident not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn get_incident(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<Response, WebError> {
+    let incident = owned_incident(&state, &ctx, id).await?;
+    Ok((StatusCode::OK, Json(incident)).into_response())
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/incident/{id}",
+    tag = "incident",
+    params(("id" = i64, Path, description = "Incident id")),
+    responses(
+        (status = 200, description = "Incident updated", body = Incident),
+        (status = 403, description = "Incident of another user's server", body = WebErrorSchema),
+        (status = 404, description = "Incident not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+    request_body = IncidentUpdate,
+)]
+pub async fn update_incident(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Json(iu): Json<IncidentUpdate>,
+) -> Result<Response, WebError> {
+    owned_incident(&state, &ctx, id).await?;
+
+    IncidentBmc::update(&state.mm, &ctx, id, iu).await?;
+    let incident = IncidentBmc::get(&state.mm, &ctx, id)
+        .await?
+        .ok_or(WebError::IncidentNotFound)?;
+
+    Ok((StatusCode::OK, Json(incident)).into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/incident/{id}",
+    tag = "incident",
+    params(("id" = i64, Path, description = "Incident id")),
+    responses(
+        (status = 200, description = "Incident removed", body = Incident),
+        (status = 403, description = "Incident of another user's server", body = WebErrorSchema),
+        (status = 404, description = "Incident not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn remove_incident(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<Response, WebError> {
+    let incident = owned_incident(&state, &ctx, id).await?;
+    IncidentBmc::delete(&state.mm, &ctx, id).await?;
+
+    Ok((StatusCode::OK, Json(incident)).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/incident/{id}/ack",
+    tag = "incident",
+    params(("id" = i64, Path, description = "Incident id")),
+    responses(
+        (status = 200, description = "Incident acknowledged, no more reminders are sent for it", body = Incident),
+        (status = 403, description = "Incident of another user's server", body = WebErrorSchema),
+        (status = 404, description = "Incident not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn acknowledge_incident(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<Response, WebError> {
+    owned_incident(&state, &ctx, id).await?;
+
+    IncidentBmc::acknowledge(&state.mm, &ctx, id).await?;
+    let incident = IncidentBmc::get(&state.mm, &ctx, id)
+        .await?
+        .ok_or(WebError::IncidentNotFound)?;
+
+    Ok((StatusCode::OK, Json(incident)).into_response())
+}
+
+pub async fn list_incident_logs(
+    State(state): State<AppState>,
+    Query(query): Query<PageQuery>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<Response, WebError> {
+    owned_incident(&state, &ctx, id).await?;
+
+    let logs = ServerLogBmc::page_incident(&state.mm, &ctx, id, query.offset, query.limit).await?;
+    Ok((StatusCode::OK, Json(logs)).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/incident/{id}/notes",
+    tag = "incident",
+    params(("id" = i64, Path, description = "Incident id")),
+    responses(
+        (status = 200, description = "Note added", body = IncidentNote),
+        (status = 403, description = "Incident of another user's server", body = WebErrorSchema),
+        (status = 404, description = "Incident not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+    request_body = IncidentNoteCreate,
+)]
+pub async fn add_incident_note(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Json(nc): Json<IncidentNoteCreate>,
+) -> Result<Response, WebError> {
+    owned_incident(&state, &ctx, id).await?;
+
+    let note = IncidentBmc::add_note(&state.mm, &ctx, id, nc).await?;
+    Ok((StatusCode::OK, Json(note)).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/incident/{id}/notes",
+    tag = "incident",
+    params(("id" = i64, Path, description = "Incident id")),
+    responses(
+        (status = 200, description = "Notes of the incident, oldest first", body = Vec<IncidentNote>),
+        (status = 403, description = "Incident of another user's server", body = WebErrorSchema),
+        (status = 404, description = "Incident not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn list_incident_notes(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<Response, WebError> {
+    owned_incident(&state, &ctx, id).await?;
+
+    let notes = IncidentBmc::notes(&state.mm, &ctx, id).await?;
+    Ok((StatusCode::OK, Json(notes)).into_response())
+}
@@ -0,0 +1,197 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use reqwest::StatusCode;
+
+use crate::{
+    model::{Ctx, Maintenance, MaintenanceBmc, MaintenanceCreate, Page, ServerBmc, UserRole},
+    web::{WebError, error::WebErrorSchema, utils::PageQuery},
+};
+
+use super::{AppState, middlewares::verify_token_middleware};
+
+pub fn routes<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/", post(create_maintenance).get(list_maintenance))
+        .route(
+            "/{id}",
+            get(get_maintenance)
+                .put(update_maintenance)
+                .delete(remove_maintenance),
+        )
+        .layer(middleware::from_fn_with_state(
+            AppState::clone(&state),
+            verify_token_middleware,
+        ))
+        .with_state(state)
+}
+
+/// Validates the window and checks every covered server belongs to the user
+async fn check_maintenance(
+    state: &AppState,
+    ctx: &Ctx,
+    mc: &MaintenanceCreate,
+) -> Result<(), WebError> {
+    mc.validate().map_err(WebError::InvalidMaintenance)?;
+
+    for server_id in &mc.server_ids {
+        let server = ServerBmc::get_by_id(&state.mm, ctx, *server_id)
+            .await?
+            .ok_or(WebError::ServerNotFound)?;
+
+        if server.user_id != ctx.user_id && !matches!(ctx.role, UserRole::Admin) {
+            return Err(WebError::ServerNotAllowed);
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetches the window, making sure it belongs to the user
+async fn owned_maintenance(state: &AppState, ctx: &Ctx, id: i64) -> Result<Maintenance, WebError> {
+    let found = MaintenanceBmc::get(&state.mm, ctx, id)
+        .await?
+        .ok_or(WebError::MaintenanceNotFound)?;
+
+    if found.user_id != ctx.user_id && !matches!(ctx.role, UserRole::Admin) {
+        return Err(WebError::MaintenanceNotAllowed);
+    }
+
+    Ok(found)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/maintenance/",
+    tag = "maintenance",
+    responses(
+        (status = 200, description = "Maintenance window created", body = Maintenance),
+        (status = 400, description = "Window is invalid", body = WebErrorSchema),
+        (status = 403, description = "One of the servers belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "One of the servers is not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+    request_body = MaintenanceCreate,
+)]
+pub async fn create_maintenance(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Json(mc): Json<MaintenanceCreate>,
+) -> Result<Response, WebError> {
+    check_maintenance(&state, &ctx, &mc).await?;
+
+    let id = MaintenanceBmc::insert(&state.mm, &ctx, mc).await?;
+    let maintenance = MaintenanceBmc::get(&state.mm, &ctx, id)
+        .await?
+        .ok_or(WebError::MaintenanceNotFound)?;
+
+    Ok((StatusCode::OK, Json(maintenance)).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/maintenance/",
+    tag = "maintenance",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "Maintenance windows of the user", body = Page<Maintenance>),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn list_maintenance(
+    State(state): State<AppState>,
+    Query(query): Query<PageQuery>,
+    ctx: Ctx,
+) -> Result<Response, WebError> {
+    let windows = MaintenanceBmc::page(&state.mm, &ctx, query.offset, query.limit).await?;
+    Ok((StatusCode::OK, Json(windows)).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/maintenance/{id}",
+    tag = "maintenance",
+    params(("id" = i64, Path, description = "Maintenance window id")),
+    responses(
+        (status = 200, description = "Maintenance window", body = Maintenance),
+        (status = 403, description = "Window belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Window not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn get_maintenance(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<Response, WebError> {
+    let maintenance = owned_maintenance(&state, &ctx, id).await?;
+    Ok((StatusCode::OK, Json(maintenance)).into_response())
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/maintenance/{id}",
+    tag = "maintenance",
+    params(("id" = i64, Path, description = "Maintenance window id")),
+    responses(
+        (status = 200, description = "Maintenance window updated", body = Maintenance),
+        (status = 400, description = "Window is invalid", body = WebErrorSchema),
+        (status = 403, description = "Window or one of the servers belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Window or one of the servers is not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+    request_body = MaintenanceCreate,
+)]
+pub async fn update_maintenance(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Json(mc): Json<MaintenanceCreate>,
+) -> Result<Response, WebError> {
+    owned_maintenance(&state, &ctx, id).await?;
+    check_maintenance(&state, &ctx, &mc).await?;
+
+    MaintenanceBmc::update(&state.mm, &ctx, id, mc).await?;
+    let maintenance = MaintenanceBmc::get(&state.mm, &ctx, id)
+        .await?
+        .ok_or(WebError::MaintenanceNotFound)?;
+
+    Ok((StatusCode::OK, Json(maintenance)).into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/maintenance/{id}",
+    tag = "maintenance",
+    params(("id" = i64, Path, description = "Maintenance window id")),
+    responses(
+        (status = 200, description = "Maintenance window removed", body = Maintenance),
+        (status = 403, description = "Window belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Window not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn remove_maintenance(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<Response, WebError> {
+    let maintenance = owned_maintenance(&state, &ctx, id).await?;
+    MaintenanceBmc::delete(&state.mm, &ctx, id).await?;
+
+    Ok((StatusCode::OK, Json(maintenance)).into_response())
+}
@@ -1,23 +1,45 @@
 mod middlewares;
 use tokio::sync::mpsc::UnboundedSender;
 
+pub mod escalation;
+pub mod heartbeat;
+pub mod incident;
+pub mod maintenance;
 pub mod notifier;
+pub mod report;
+pub mod retention;
 pub mod server;
 pub mod server_log;
+pub mod stats;
 pub mod user;
 
+pub use escalation::routes as escalation_routes;
+pub use heartbeat::routes as heartbeat_routes;
+pub use incident::routes as incident_routes;
+pub use maintenance::routes as maintenance_routes;
 pub use notifier::routes as notify_routes;
+pub use report::routes as report_routes;
+pub use retention::routes as retention_routes;
 pub use server::routes as server_routes;
 pub use server_log::routes as server_log_routes;
+pub use stats::routes as stats_routes;
 pub use user::routes as user_routes;
 
-use crate::{ModelManager, channel::ControlMessage, notify::NotifyManager};
+use crate::{
+    ModelManager,
+    channel::{ChannelStats, ControlMessage, UnderMaintenance},
+    notify::NotifyManager,
+};
 
 pub struct RawState {
     pub mm: ModelManager,
     secret: String,
     pub control_tx: UnboundedSender<ControlMessage>,
     pub notify_manager: NotifyManager,
+    /// Counters of the queue between probes and their handling
+    pub queue_stats: ChannelStats,
+    /// Servers under maintenance, kept up to date by the monitoring backend
+    pub under_maintenance: UnderMaintenance,
 }
 
 pub type AppState = std::sync::Arc<RawState>;
@@ -28,12 +50,15 @@ impl RawState {
         secret: String,
         tx: UnboundedSender<ControlMessage>,
         notify_manager: NotifyManager,
+        queue_stats: ChannelStats,
     ) -> AppState {
         AppState::new(Self {
             mm,
             secret,
             control_tx: tx,
             notify_manager,
+            queue_stats,
+            under_maintenance: UnderMaintenance::default(),
         })
     }
 }
@@ -9,7 +9,9 @@ use reqwest::StatusCode;
 use serde_json::json;
 
 use crate::{
-    model::{Ctx, Notifier, NotifierBmc, NotifierCreate, ServerBmc, UserRole},
+    model::{
+        Ctx, DEFAULT_REMIND_LIMIT, Notifier, NotifierBmc, NotifierCreate, ServerBmc, UserRole,
+    },
     web::WebError,
 };
 
@@ -33,6 +35,8 @@ async fn notifier_add(
     ctx: Ctx,
     Json(payload): Json<NotifierCreate>,
 ) -> Result<Response, WebError> {
+    payload.validate().map_err(WebError::InvalidNotifier)?;
+
     let found = ServerBmc::get_by_id(&state.mm, &ctx, payload.server_id).await?;
     if found.is_none() {
         return Err(WebError::ServerNotFound);
@@ -84,6 +88,8 @@ async fn notifier_modify(
     Path(id): Path<i64>,
     Json(payload): Json<NotifierCreate>,
 ) -> Result<Response, WebError> {
+    payload.validate().map_err(WebError::InvalidNotifier)?;
+
     let found = NotifierBmc::find_by_id(&state.mm, &ctx, id).await?;
     if found.is_none() {
         return Err(WebError::NotifierNotFound);
@@ -103,6 +109,8 @@ async fn notifier_modify(
         credentials: payload.credentials,
         format: payload.format,
         active: payload.active.unwrap_or(found.active),
+        remind_every: payload.remind_every,
+        remind_limit: payload.remind_limit.unwrap_or(DEFAULT_REMIND_LIMIT),
         created_at: found.created_at,
         updated_at,
     };
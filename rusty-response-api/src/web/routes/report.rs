@@ -0,0 +1,221 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::{
+    Ctx, ModelManager,
+    model::{
+        MAX_WINDOW, Rollup, RollupBmc, RollupPeriod, ServerBmc, ServerLogBmc, Uptime, UserRole,
+        parse_window,
+    },
+    web::{
+        AppState, WebError, error::WebErrorSchema, routes::middlewares::verify_token_middleware,
+    },
+};
+
+pub fn routes<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/server/{id}/uptime", get(get_uptime))
+        .route("/server/{id}/sla", get(get_sla))
+        .route("/server/{id}/series", get(get_series))
+        .layer(middleware::from_fn_with_state(
+            AppState::clone(&state),
+            verify_token_middleware,
+        ))
+        .with_state(state)
+}
+
+/// Either a `window` ending now or a `from`..`to` range, defaults to the last 24 hours
+#[derive(Debug, Deserialize)]
+pub struct UptimeQuery {
+    window: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    to: Option<OffsetDateTime>,
+}
+
+impl UptimeQuery {
+    fn range(&self) -> Result<(OffsetDateTime, OffsetDateTime), WebError> {
+        let now = OffsetDateTime::now_utc();
+
+        let (from, to) = match (&self.window, self.from) {
+            (Some(_), Some(_)) => {
+                let reason = "either `window` or `from` may be given".to_string();
+                return Err(WebError::InvalidReportRange(reason));
+            }
+            (Some(window), None) => {
+                let window = parse_window(window).map_err(WebError::InvalidReportRange)?;
+                (now - window, now)
+            }
+            (None, Some(from)) => (from, self.to.unwrap_or(now).min(now)),
+            (None, None) => (now - time::Duration::DAY, now),
+        };
+
+        if from >= to {
+            let reason = "range has to start before it ends and before now".to_string();
+            return Err(WebError::InvalidReportRange(reason));
+        }
+        if to - from > MAX_WINDOW {
+            let reason = format!(
+                "range can't be longer than {} days",
+                MAX_WINDOW.whole_days()
+            );
+            return Err(WebError::InvalidReportRange(reason));
+        }
+
+        Ok((from, to))
+    }
+}
+
+/// Range of an [`UptimeQuery`] split into `period` buckets, hourly by default
+#[derive(Debug, Deserialize)]
+pub struct SeriesQuery {
+    period: Option<RollupPeriod>,
+    #[serde(flatten)]
+    range: UptimeQuery,
+}
+
+/// Uptime figures of the usual SLA windows, all ending now
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Sla {
+    pub day: Uptime,
+    pub week: Uptime,
+    pub month: Uptime,
+}
+
+async fn owned_server_check(state: &AppState, ctx: &Ctx, id: i64) -> Result<(), WebError> {
+    let server = ServerBmc::get_by_id(&state.mm, ctx, id)
+        .await?
+        .ok_or(WebError::ServerNotFound)?;
+
+    if server.user_id != ctx.user_id && !matches!(ctx.role, UserRole::Admin) {
+        return Err(WebError::ServerNotAllowed);
+    }
+
+    Ok(())
+}
+
+async fn uptime(
+    mm: &ModelManager,
+    ctx: &Ctx,
+    id: i64,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+) -> Result<Uptime, WebError> {
+    let initial = ServerLogBmc::state_at(mm, ctx, id, from).await?;
+    let transitions = ServerLogBmc::states_between(mm, ctx, id, from, to).await?;
+
+    Ok(Uptime::compute(initial, &transitions, from, to))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/report/server/{id}/uptime",
+    tag = "report",
+    params(
+        ("id" = i64, Path, description = "Server id"),
+        ("window" = Option<String>, Query, description = "Window ending now, e.g `24h`, `7d` or `30d`"),
+        ("from" = Option<String>, Query, description = "Start of a custom range, RFC3339"),
+        ("to" = Option<String>, Query, description = "End of a custom range, RFC3339, defaults to now"),
+    ),
+    responses(
+        (status = 200, description = "Uptime figures of the range", body = Uptime),
+        (status = 400, description = "Range is invalid", body = WebErrorSchema),
+        (status = 403, description = "Server belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Server not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn get_uptime(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Query(query): Query<UptimeQuery>,
+) -> Result<Response, WebError> {
+    let (from, to) = query.range()?;
+    owned_server_check(&state, &ctx, id).await?;
+
+    let uptime = uptime(&state.mm, &ctx, id, from, to).await?;
+    Ok((StatusCode::OK, Json(uptime)).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/report/server/{id}/sla",
+    tag = "report",
+    params(("id" = i64, Path, description = "Server id")),
+    responses(
+        (status = 200, description = "Uptime figures of the last 24 hours, 7 and 30 days", body = Sla),
+        (status = 403, description = "Server belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Server not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn get_sla(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<Response, WebError> {
+    owned_server_check(&state, &ctx, id).await?;
+
+    let now = OffsetDateTime::now_utc();
+    let since = |days| now - time::Duration::days(days);
+    let sla = Sla {
+        day: uptime(&state.mm, &ctx, id, since(1), now).await?,
+        week: uptime(&state.mm, &ctx, id, since(7), now).await?,
+        month: uptime(&state.mm, &ctx, id, since(30), now).await?,
+    };
+
+    Ok((StatusCode::OK, Json(sla)).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/report/server/{id}/series",
+    tag = "report",
+    params(
+        ("id" = i64, Path, description = "Server id"),
+        ("period" = Option<RollupPeriod>, Query, description = "Bucket length, `hour` or `day`, defaults to `hour`"),
+        ("window" = Option<String>, Query, description = "Window ending now, e.g `24h`, `7d` or `30d`"),
+        ("from" = Option<String>, Query, description = "Start of a custom range, RFC3339"),
+        ("to" = Option<String>, Query, description = "End of a custom range, RFC3339, defaults to now"),
+    ),
+    responses(
+        (status = 200, description = "Buckets overlapping the range, oldest first", body = Vec<Rollup>),
+        (status = 400, description = "Range is invalid", body = WebErrorSchema),
+        (status = 403, description = "Server belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Server not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn get_series(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Query(query): Query<SeriesQuery>,
+) -> Result<Response, WebError> {
+    let (from, to) = query.range.range()?;
+    owned_server_check(&state, &ctx, id).await?;
+
+    // the bucket `from` falls in is included as well
+    let period = query.period.unwrap_or(RollupPeriod::Hour);
+    let from = period.start_of(from).assume_utc();
+    let series = RollupBmc::list(&state.mm, &ctx, id, period, from, to).await?;
+
+    Ok((StatusCode::OK, Json(series)).into_response())
+}
@@ -0,0 +1,68 @@
+use axum::{
+    Json, Router,
+    extract::State,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use reqwest::StatusCode;
+use serde::Serialize;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::{
+    Settings,
+    model::{Ctx, ExpiredRows, RetentionBmc, UserRole},
+    web::{WebError, error::WebErrorSchema},
+};
+
+use super::{AppState, middlewares::verify_token_middleware};
+
+pub fn routes<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/", get(get_retention_report))
+        .layer(middleware::from_fn_with_state(
+            AppState::clone(&state),
+            verify_token_middleware,
+        ))
+        .with_state(state)
+}
+
+/// What the retention job would prune if it ran now
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RetentionReport {
+    /// Whether the job only reports instead of pruning
+    pub dry_run: bool,
+    pub expired: Vec<ExpiredRows>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/retention/",
+    tag = "retention",
+    responses(
+        (status = 200, description = "Expired rows per table, nothing is deleted", body = RetentionReport),
+        (status = 403, description = "Caller is not an administrator", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn get_retention_report(
+    State(state): State<AppState>,
+    ctx: Ctx,
+) -> Result<Response, WebError> {
+    if !matches!(ctx.role, UserRole::Admin) {
+        return Err(WebError::AdminOnly);
+    }
+
+    let retention = Settings::global().retention();
+    let now = OffsetDateTime::now_utc();
+    let expiries = RetentionBmc::expiries(&state.mm, &ctx, retention, now).await?;
+    let report = RetentionReport {
+        dry_run: retention.dry_run(),
+        expired: RetentionBmc::report(&state.mm, &ctx, &expiries).await?,
+    };
+
+    Ok((StatusCode::OK, Json(report)).into_response())
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use axum::{
     Json, Router,
     extract::{Path, Query, State},
@@ -7,9 +9,15 @@ use axum::{
 };
 use eyre::Context;
 use reqwest::StatusCode;
+use serde::Deserialize;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
 
 use crate::{
-    model::{Ctx, Server, ServerBmc, ServerCreate, UserAction, UserActionLogBmc, UserRole},
+    model::{
+        Ctx, Incident, IncidentBmc, Server, ServerBmc, ServerCreate, UserAction, UserActionLogBmc,
+        UserRole, dependency_cycle,
+    },
     web::{error::WebErrorSchema, utils::PageQuery, WebError},
 };
 
@@ -22,6 +30,9 @@ pub fn routes<S>(state: AppState) -> Router<S> {
             "/{id}",
             get(get_server).delete(remove_server).put(update_server),
         )
+        .route("/{id}/pause", post(pause_server))
+        .route("/{id}/resume", post(resume_server))
+        .route("/{id}/ack", post(acknowledge_server))
         .layer(middleware::from_fn_with_state(
             AppState::clone(&state),
             verify_token_middleware,
@@ -29,12 +40,61 @@ pub fn routes<S>(state: AppState) -> Router<S> {
         .with_state(state)
 }
 
+/// Checks parents of `server_id` (`None` for a new server) exist, belong to the user and don't form a cycle
+async fn check_parents(
+    state: &AppState,
+    ctx: &Ctx,
+    server_id: Option<i64>,
+    parent_ids: &[i64],
+) -> Result<(), WebError> {
+    if parent_ids.is_empty() {
+        return Ok(());
+    }
+
+    let servers = ServerBmc::all(&state.mm, ctx).await?;
+    for srv in servers.iter().filter(|srv| parent_ids.contains(&srv.id)) {
+        if srv.user_id != ctx.user_id && !matches!(ctx.role, UserRole::Admin) {
+            return Err(WebError::ServerNotAllowed);
+        }
+    }
+
+    let mut parents: HashMap<i64, Vec<i64>> = servers
+        .into_iter()
+        .map(|srv| (srv.id, srv.parent_ids.0))
+        .collect();
+
+    if let Some(missing) = parent_ids.iter().find(|id| !parents.contains_key(id)) {
+        return Err(WebError::InvalidDependency(format!(
+            "parent server {missing} is not found"
+        )));
+    }
+
+    // a brand new server has no children, so it can't close a cycle
+    let Some(server_id) = server_id else {
+        return Ok(());
+    };
+
+    parents.insert(server_id, parent_ids.to_vec());
+    if let Some(cycle) = dependency_cycle(&parents, server_id) {
+        let cycle: Vec<String> = cycle.iter().map(i64::to_string).collect();
+        return Err(WebError::InvalidDependency(format!(
+            "dependency cycle {}",
+            cycle.join(" -> ")
+        )));
+    }
+
+    Ok(())
+}
+
 #[utoipa::path(
     post,
     path = "/api/v1/server/",
     tag = "server",
     responses(
         (status = 200, description = "Server created successfully", body = Server),
+        (status = 400, description = "Server configuration is invalid for its kind or its parents form a cycle", body = WebErrorSchema),
+        (status = 403, description = "One of the parents or the escalation policy belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Escalation policy is not found", body = WebErrorSchema),
         (status = 409, description = "Server with the same name already exists", body = WebErrorSchema),
     ),
     security(
@@ -52,6 +112,13 @@ pub async fn create_server(
         return Err(WebError::ServerAlreadyExists);
     }
 
+    crate::channel::validate_server(&sc).map_err(WebError::InvalidServerConfig)?;
+    let parent_ids = sc.parent_ids.as_deref().unwrap_or_default();
+    check_parents(&state, &ctx, None, parent_ids).await?;
+    if let Some(policy_id) = sc.escalation_policy_id {
+        super::escalation::owned_policy(&state, &ctx, policy_id).await?;
+    }
+
     let srv = ServerBmc::insert(&state.mm, &ctx, sc).await?;
 
     // Send control message to monitoring backend to create a new monitoring instance dynamically
@@ -61,7 +128,7 @@ pub async fn create_server(
         .wrap_err("Failed to send control message")?;
 
     UserActionLogBmc::log(&state.mm, &ctx, UserAction::server_create(srv.id)).await?;
-    Ok((StatusCode::OK, Json(srv)).into_response())
+    Ok((StatusCode::OK, Json(srv.redacted())).into_response())
 }
 
 pub async fn list_servers(
@@ -69,7 +136,14 @@ pub async fn list_servers(
     Query(query): Query<PageQuery>,
     ctx: Ctx,
 ) -> Result<Response, WebError> {
-    let servers = ServerBmc::page(&state.mm, &ctx, query.offset, query.limit).await?;
+    let maintenance = state.under_maintenance.read().await.clone();
+    let servers = ServerBmc::page(&state.mm, &ctx, query.offset, query.limit)
+        .await?
+        .map(|srv| match maintenance.contains(&srv.id) {
+            true => srv.in_maintenance(),
+            false => srv,
+        })
+        .map(Server::redacted);
     Ok((StatusCode::OK, Json(servers)).into_response())
 }
 
@@ -85,27 +159,24 @@ pub async fn update_server(
     }
 
     let found = found.unwrap();
-    let sc_clone = sc.clone();
 
     if found.user_id != ctx.user_id && !matches!(ctx.role, UserRole::Admin) {
         return Err(WebError::ServerNotAllowed);
     }
 
-    let updated_at = ServerBmc::update_server(&state.mm, &ctx, id, sc).await?;
-
-    let modified_server = Server {
-        id,
-        user_id: found.user_id,
-        name: sc_clone.name,
-        url: sc_clone.url,
-        timeout: sc_clone.timeout.unwrap_or(found.timeout),
-        interval: sc_clone.interval.unwrap_or(found.interval),
-        last_seen_reason: found.last_seen_reason,
-        last_seen_status_code: found.last_seen_status_code,
-        is_turned_on: sc_clone.is_turned_on.unwrap_or(found.is_turned_on),
-        created_at: found.created_at,
-        updated_at,
-    };
+    crate::channel::validate_server(&sc).map_err(WebError::InvalidServerConfig)?;
+    let parent_ids = sc.parent_ids.as_deref().unwrap_or_default();
+    check_parents(&state, &ctx, Some(id), parent_ids).await?;
+    if let Some(policy_id) = sc.escalation_policy_id {
+        super::escalation::owned_policy(&state, &ctx, policy_id).await?;
+    }
+
+    ServerBmc::update_server(&state.mm, &ctx, &found, sc).await?;
+
+    // re-read, so the monitoring backend gets sealed headers instead of the submitted ones
+    let modified_server = ServerBmc::get_by_id(&state.mm, &ctx, id)
+        .await?
+        .ok_or(WebError::ServerNotFound)?;
 
     state
         .control_tx
@@ -120,7 +191,7 @@ pub async fn update_server(
         UserAction::server_modify(modified_server.id),
     )
     .await?;
-    Ok((StatusCode::OK, Json(modified_server)).into_response())
+    Ok((StatusCode::OK, Json(modified_server.redacted())).into_response())
 }
 
 pub async fn remove_server(
@@ -147,7 +218,7 @@ pub async fn remove_server(
         .wrap_err("Failed to send control message")?;
 
     UserActionLogBmc::log(&state.mm, &ctx, UserAction::server_delete(srv.id)).await?;
-    Ok((StatusCode::OK, Json(srv)).into_response())
+    Ok((StatusCode::OK, Json(srv.redacted())).into_response())
 }
 
 pub async fn get_server(
@@ -166,6 +237,162 @@ pub async fn get_server(
         return Err(WebError::ServerNotAllowed);
     }
 
+    let maintenance = state.under_maintenance.read().await.contains(&srv.id);
+    let srv = match maintenance {
+        true => srv.in_maintenance(),
+        false => srv,
+    };
+
     // won't be logged because this is an API, user will get em whenever he opens his "Servers" page
-    Ok((StatusCode::OK, Json(srv)).into_response())
+    Ok((StatusCode::OK, Json(srv.redacted())).into_response())
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PauseRequest {
+    /// Server is resumed automatically at this moment, omitted pauses it until resumed by hand
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub until: Option<OffsetDateTime>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/server/{id}/pause",
+    tag = "server",
+    params(("id" = i64, Path, description = "Server id")),
+    responses(
+        (status = 200, description = "Server paused", body = Server),
+        (status = 400, description = "Pause deadline is in the past", body = WebErrorSchema),
+        (status = 403, description = "Server belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Server not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+    request_body(content = Option<PauseRequest>, description = "Optional pause deadline"),
+)]
+pub async fn pause_server(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    body: Option<Json<PauseRequest>>,
+) -> Result<Response, WebError> {
+    let found = ServerBmc::get_by_id(&state.mm, &ctx, id).await?;
+    if found.is_none() {
+        return Err(WebError::ServerNotFound);
+    }
+
+    let srv = found.unwrap();
+
+    if srv.user_id != ctx.user_id && !matches!(ctx.role, UserRole::Admin) {
+        return Err(WebError::ServerNotAllowed);
+    }
+
+    let until = body.and_then(|Json(body)| body.until);
+    if until.is_some_and(|until| until <= OffsetDateTime::now_utc()) {
+        return Err(WebError::InvalidPauseDeadline);
+    }
+
+    ServerBmc::pause(&state.mm, &ctx, id, until).await?;
+
+    state
+        .control_tx
+        .send(crate::channel::ControlMessage::PauseServer {
+            server_id: id,
+            until,
+        })
+        .wrap_err("Failed to send control message")?;
+
+    UserActionLogBmc::log(&state.mm, &ctx, UserAction::server_pause(id)).await?;
+
+    let srv = Server {
+        is_turned_on: false,
+        paused_until: until,
+        ..srv
+    };
+    Ok((StatusCode::OK, Json(srv.redacted())).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/server/{id}/resume",
+    tag = "server",
+    params(("id" = i64, Path, description = "Server id")),
+    responses(
+        (status = 200, description = "Server resumed", body = Server),
+        (status = 403, description = "Server belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Server not found", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn resume_server(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<Response, WebError> {
+    let found = ServerBmc::get_by_id(&state.mm, &ctx, id).await?;
+    if found.is_none() {
+        return Err(WebError::ServerNotFound);
+    }
+
+    let srv = found.unwrap();
+
+    if srv.user_id != ctx.user_id && !matches!(ctx.role, UserRole::Admin) {
+        return Err(WebError::ServerNotAllowed);
+    }
+
+    ServerBmc::resume(&state.mm, &ctx, id).await?;
+
+    state
+        .control_tx
+        .send(crate::channel::ControlMessage::ResumeServer(id))
+        .wrap_err("Failed to send control message")?;
+
+    UserActionLogBmc::log(&state.mm, &ctx, UserAction::server_resume(id)).await?;
+
+    let srv = Server {
+        is_turned_on: true,
+        paused_until: None,
+        ..srv
+    };
+    Ok((StatusCode::OK, Json(srv.redacted())).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/server/{id}/ack",
+    tag = "server",
+    params(("id" = i64, Path, description = "Server id")),
+    responses(
+        (status = 200, description = "Current outage acknowledged, it's no longer escalated", body = Incident),
+        (status = 403, description = "Server belongs to another user", body = WebErrorSchema),
+        (status = 404, description = "Server not found or not down", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn acknowledge_server(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+) -> Result<Response, WebError> {
+    let srv = ServerBmc::get_by_id(&state.mm, &ctx, id)
+        .await?
+        .ok_or(WebError::ServerNotFound)?;
+
+    if srv.user_id != ctx.user_id && !matches!(ctx.role, UserRole::Admin) {
+        return Err(WebError::ServerNotAllowed);
+    }
+
+    let incident = IncidentBmc::get_open(&state.mm, &ctx, id)
+        .await?
+        .ok_or(WebError::IncidentNotFound)?;
+    IncidentBmc::acknowledge(&state.mm, &ctx, incident.id).await?;
+
+    let incident = IncidentBmc::get(&state.mm, &ctx, incident.id)
+        .await?
+        .ok_or(WebError::IncidentNotFound)?;
+    Ok((StatusCode::OK, Json(incident)).into_response())
 }
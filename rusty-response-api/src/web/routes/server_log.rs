@@ -10,8 +10,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     Ctx,
-    model::{ServerBmc, ServerLogBmc, UserRole},
-    web::{AppState, WebError, routes::middlewares::verify_token_middleware},
+    model::{CheckResultBmc, ServerBmc, ServerLogBmc, UserRole},
+    web::{AppState, WebError, routes::middlewares::verify_token_middleware, utils::PageQuery},
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,6 +24,7 @@ pub struct LogsPageQuery {
 pub fn routes<S>(state: AppState) -> Router<S> {
     Router::new()
         .route("/{id}", get(get_server_logs))
+        .route("/{id}/checks", get(get_server_checks))
         .layer(middleware::from_fn_with_state(
             AppState::clone(&state),
             verify_token_middleware,
@@ -55,3 +56,22 @@ pub async fn get_server_logs(
 
     Ok((StatusCode::OK, Json(logs)).into_response())
 }
+
+/// Recorded checks of the server, latest first
+pub async fn get_server_checks(
+    State(state): State<AppState>,
+    ctx: Ctx,
+    Path(id): Path<i64>,
+    Query(query): Query<PageQuery>,
+) -> Result<Response, WebError> {
+    let server = ServerBmc::get_by_id(&state.mm, &ctx, id)
+        .await?
+        .ok_or(WebError::ServerNotFound)?;
+
+    if server.user_id != ctx.user_id && !matches!(ctx.role, UserRole::Admin) {
+        return Err(WebError::ServerNotAllowed);
+    }
+
+    let checks = CheckResultBmc::page(&state.mm, &ctx, id, query.offset, query.limit).await?;
+    Ok((StatusCode::OK, Json(checks)).into_response())
+}
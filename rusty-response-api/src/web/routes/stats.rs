@@ -0,0 +1,58 @@
+use axum::{
+    Json, Router,
+    extract::State,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use reqwest::StatusCode;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    channel::ChannelStatsSnapshot,
+    model::{Ctx, UserRole},
+    web::{WebError, error::WebErrorSchema},
+};
+
+use super::{AppState, middlewares::verify_token_middleware};
+
+pub fn routes<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/", get(get_stats))
+        .layer(middleware::from_fn_with_state(
+            AppState::clone(&state),
+            verify_token_middleware,
+        ))
+        .with_state(state)
+}
+
+/// Internal counters of the monitoring backend
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InternalStats {
+    /// Queue between probes and the handling of their results
+    pub server_queue: ChannelStatsSnapshot,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats/",
+    tag = "stats",
+    responses(
+        (status = 200, description = "Internal counters", body = InternalStats),
+        (status = 403, description = "Caller is not an administrator", body = WebErrorSchema),
+    ),
+    security(
+        ("jwt_key" = [])
+    ),
+)]
+pub async fn get_stats(State(state): State<AppState>, ctx: Ctx) -> Result<Response, WebError> {
+    if !matches!(ctx.role, UserRole::Admin) {
+        return Err(WebError::AdminOnly);
+    }
+
+    let stats = InternalStats {
+        server_queue: state.queue_stats.snapshot(),
+    };
+    Ok((StatusCode::OK, Json(stats)).into_response())
+}
@@ -2,10 +2,12 @@ use serde::{Deserialize, Serialize};
 use tokio::{signal, sync::mpsc};
 use tokio_util::sync::CancellationToken;
 use tracing::info;
+use utoipa::IntoParams;
 
 use crate::channel::ControlMessage;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct PageQuery {
     pub limit: i64,
     pub offset: i64,